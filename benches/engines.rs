@@ -0,0 +1,48 @@
+// Compare the parsing engines, `cargo bench --bench engines`.
+//
+// Both engines parse the same random expressions (of increasing depth),
+// the trees are checked to be identical.
+use d7050e_lab2::{
+    generate::{Config, Generator},
+    pratt::Engine,
+};
+use std::time::{Duration, Instant};
+
+fn time(engine: Engine, sources: &[String]) -> Duration {
+    let start = Instant::now();
+    for s in sources {
+        engine.parse_str(s).unwrap();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    for depth in &[2, 4, 6] {
+        let config = Config {
+            depth: *depth,
+            ..Default::default()
+        };
+        let mut g = Generator::new(*depth as u64, config);
+        let sources: Vec<String> = (0..2000).map(|_| g.expr().to_string()).collect();
+        for s in &sources {
+            assert_eq!(
+                Engine::Climb.parse_str(s).unwrap().expr,
+                Engine::Pratt.parse_str(s).unwrap().expr,
+                "{}",
+                s
+            );
+        }
+        let bytes: usize = sources.iter().map(|s| s.len()).sum();
+        for engine in &[Engine::Climb, Engine::Pratt] {
+            // the best of a few runs
+            let t = (0..5).map(|_| time(*engine, &sources)).min().unwrap();
+            println!(
+                "depth {:2}, {:?}: {:8.2?} ({:.1} MB/s)",
+                depth,
+                engine,
+                t,
+                bytes as f64 / t.as_secs_f64() / 1e6
+            );
+        }
+    }
+}
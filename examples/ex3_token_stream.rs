@@ -1,8 +1,5 @@
-use proc_macro2;
-
 // The same ast as in ex2_ast.rs, but now part of the d7050e_lab2 library.
 use d7050e_lab2::ast;
-use syn;
 
 // Rust has a hand written parser (hidden from the user).
 // It's hidden since the compiler implementation may change over time.
@@ -15,6 +12,8 @@ use syn;
 // As an example we use it to implement RTIC https://crates.io/crates/cortex-m-rtic
 //
 
+// Your conversion, the complete one is `ast::Expr::try_from` in
+// `src/syn_expr.rs` (try your own first).
 fn ast_expr(e: &syn::Expr) -> ast::Expr {
     match e {
         syn::Expr::Lit(syn::ExprLit {
@@ -25,8 +24,8 @@ fn ast_expr(e: &syn::Expr) -> ast::Expr {
             ast::Expr::from(i)
         }
         syn::Expr::Binary(b) => {
-            let left = ast_expr(&*b.left);
-            let right = ast_expr(&*b.right);
+            let left = ast_expr(&b.left);
+            let right = ast_expr(&b.right);
             let op = match b.op {
                 syn::BinOp::Add(_) => ast::Op::Add,
                 _ => unimplemented!(),
@@ -0,0 +1,17 @@
+use d7050e_lab2::grammar::Grammar;
+
+// Print the EBNF of the grammar accepted by the parser, compare with your
+// hand written EBNF in `examples/ex4.md`.
+//
+// cargo run --example ex4_grammar
+// cargo run --example ex4_grammar -- grammar.svg
+//
+// The second form also writes the railroad diagrams to the given file.
+fn main() {
+    let g = Grammar::expr();
+    println!("{}", g.to_ebnf());
+    if let Some(path) = std::env::args().nth(1) {
+        std::fs::write(&path, g.to_svg()).unwrap();
+        println!("railroad diagrams written to {}", path);
+    }
+}
@@ -1,7 +1,5 @@
-use proc_macro2;
-
+#[cfg(test)]
 use d7050e_lab2::{ast::*, parse::*};
-use syn;
 
 // Study in details the `src/ast.rs` and `src/parser.rs`.
 
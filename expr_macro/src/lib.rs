@@ -0,0 +1,295 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Literal as Lit2, Span, TokenStream as TokenStream2};
+use quote::quote;
+
+use syn::{
+    parse::{Parse, ParseStream},
+    Ident, Token,
+};
+
+use d7050e_lab2::ast::{ArithMode, Literal, Program};
+
+// Evaluate an expression at compile time, e.g.,
+// `expr!(2 - 3 * 4)` expands to `-10i32`.
+//
+// The expression is parsed using the d7050e_lab2 parser,
+// climbed, type checked and evaluated. Type errors and
+// evaluation errors are reported as `compile_error!`
+// at the span of the offending operator.
+//
+// Arithmetic is checked by default, the mode can be given
+// as a prefix, e.g., `expr!(wrapping; 2147483647 + 1)`.
+//
+// Struct and enum declarations may precede the expression, struct and
+// enum values expand to literals of the same name, which must be in scope.
+//
+// Dates, e.g., `d"2024-02-29" + 1`, expand to strings, `"2024-03-01"`.
+//
+// Blocks may declare variables and borrow them, borrows are checked
+// by a simplified borrow checker.
+#[proc_macro]
+pub fn expr(input: TokenStream) -> TokenStream {
+    expand(input.into()).into()
+}
+
+fn expand(input: TokenStream2) -> TokenStream2 {
+    match eval(input) {
+        Ok(l) => tokens(l),
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+// The value as a Rust expression
+fn tokens(l: Literal) -> TokenStream2 {
+    let l = match l {
+        Literal::Bool(b) => return quote!(#b),
+        Literal::Int(i) => Lit2::i32_suffixed(i),
+        Literal::I8(i) => Lit2::i8_suffixed(i),
+        Literal::I16(i) => Lit2::i16_suffixed(i),
+        Literal::I64(i) => Lit2::i64_suffixed(i),
+        Literal::I128(i) => Lit2::i128_suffixed(i),
+        Literal::U8(i) => Lit2::u8_suffixed(i),
+        Literal::U16(i) => Lit2::u16_suffixed(i),
+        Literal::U32(i) => Lit2::u32_suffixed(i),
+        Literal::U64(i) => Lit2::u64_suffixed(i),
+        Literal::U128(i) => Lit2::u128_suffixed(i),
+        Literal::F32(f) if f.is_finite() => Lit2::f32_suffixed(f),
+        Literal::F64(f) if f.is_finite() => Lit2::f64_suffixed(f),
+        // infinities and NaN have no literals
+        Literal::F32(f) => return non_finite(quote!(f32), f.is_nan(), f < 0.0),
+        Literal::F64(f) => return non_finite(quote!(f64), f.is_nan(), f < 0.0),
+        Literal::Char(c) => Lit2::character(c),
+        Literal::Str(s) => Lit2::string(&s),
+        // Rust has no dates, as a string
+        Literal::Date(d) => Lit2::string(&d.to_string()),
+        Literal::Tuple(t) => {
+            let t = t.into_iter().map(tokens);
+            return quote!((#(#t,)*));
+        }
+        Literal::Array(a) => {
+            let a = a.into_iter().map(tokens);
+            return quote!([#(#a),*]);
+        }
+        Literal::Struct(name, fields) => {
+            let name = Ident::new(&name, Span::call_site());
+            let (f, v): (Vec<_>, Vec<_>) = fields
+                .into_iter()
+                .map(|(f, v)| (Ident::new(&f, Span::call_site()), tokens(v)))
+                .unzip();
+            return quote!(#name { #(#f: #v),* });
+        }
+        Literal::Enum(e, v, args) => {
+            let e = Ident::new(&e, Span::call_site());
+            let v = Ident::new(&v, Span::call_site());
+            if args.is_empty() {
+                return quote!(#e::#v);
+            }
+            let args = args.into_iter().map(tokens);
+            return quote!(#e::#v(#(#args),*));
+        }
+        // an Int that does not fit in i32 (with the bigint feature),
+        // as an unsuffixed literal its type is inferred from the context
+        #[allow(unreachable_patterns)]
+        l => return l.to_string().parse().unwrap(),
+    };
+    quote!(#l)
+}
+
+fn non_finite(ty: TokenStream2, nan: bool, neg: bool) -> TokenStream2 {
+    match (nan, neg) {
+        (true, _) => quote!(#ty::NAN),
+        (_, false) => quote!(#ty::INFINITY),
+        (_, true) => quote!(#ty::NEG_INFINITY),
+    }
+}
+
+// The macro input, `[mode;] items expr`
+struct Input {
+    mode: ArithMode,
+    p: Program,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mode = if input.peek(Ident) && input.peek2(Token![;]) {
+            let mode: Ident = input.parse()?;
+            let _: Token![;] = input.parse()?;
+            match mode.to_string().as_str() {
+                "checked" => ArithMode::Checked,
+                "wrapping" => ArithMode::Wrapping,
+                "saturating" => ArithMode::Saturating,
+                _ => {
+                    return Err(syn::Error::new(
+                        mode.span(),
+                        "expected `checked`, `wrapping` or `saturating`",
+                    ))
+                }
+            }
+        } else {
+            ArithMode::default()
+        };
+        let p = input.parse()?;
+        Ok(Input { mode, p })
+    }
+}
+
+fn eval(input: TokenStream2) -> syn::Result<Literal> {
+    let Input { mode, p } = syn::parse2(input)?;
+    p.type_check()?;
+    p.borrow_check()?;
+    Ok(p.eval_with(mode)?)
+}
+
+#[test]
+fn expand_int() {
+    let ts = expand("2 - 3 * 4".parse().unwrap());
+    assert_eq!(ts.to_string(), quote!(-10i32).to_string());
+}
+
+#[test]
+fn expand_bigint() {
+    if !d7050e_lab2::BIGINT {
+        return;
+    }
+    let ts = expand("2147483647 + 1".parse().unwrap());
+    assert_eq!(ts.to_string(), "2147483648");
+    let ts = expand("0xffff_ffff_ffff_ffff * -2".parse().unwrap());
+    assert_eq!(ts.to_string(), quote!(-36893488147419103230).to_string());
+}
+
+#[test]
+fn expand_bool() {
+    let ts = expand("true && (false || true)".parse().unwrap());
+    assert_eq!(ts.to_string(), quote!(true).to_string());
+}
+
+#[test]
+fn expand_widths() {
+    let ts = expand("200u8 + 55u8".parse().unwrap());
+    assert_eq!(ts.to_string(), quote!(255u8).to_string());
+    let ts = expand("200u8 + 56u8".parse().unwrap());
+    assert!(ts.to_string().contains("compile_error"));
+    if !d7050e_lab2::BIGINT {
+        let ts = expand("3_000_000_000".parse().unwrap());
+        assert!(ts.to_string().contains("compile_error"));
+    }
+}
+
+#[test]
+fn expand_float() {
+    let ts = expand("1.5 * 2.0".parse().unwrap());
+    assert_eq!(ts.to_string(), quote!(3f64).to_string());
+    let ts = expand("-1f32 / 0f32".parse().unwrap());
+    assert_eq!(ts.to_string(), quote!(f32::NEG_INFINITY).to_string());
+}
+
+#[test]
+fn expand_str() {
+    let ts = expand(r#"upper("ab" + "c") + substr("xyz", 1, 1)"#.parse().unwrap());
+    assert_eq!(ts.to_string(), quote!("ABCy").to_string());
+    let ts = expand("98u8 as char".parse().unwrap());
+    assert_eq!(ts.to_string(), quote!('b').to_string());
+    let ts = expand(r#""a" * 2"#.parse().unwrap());
+    assert!(ts.to_string().contains("compile_error"));
+}
+
+#[test]
+fn expand_date() {
+    let ts = expand(r#"d"2024-02-28" + 1"#.parse().unwrap());
+    assert_eq!(ts.to_string(), quote!("2024-02-29").to_string());
+    let ts = expand(r#"d"2023-02-29""#.parse().unwrap());
+    assert!(ts.to_string().contains("February 2023 has 28 days"));
+}
+
+#[test]
+fn expand_tuple_array() {
+    let ts = expand("([1, 2][1], [0u8; 2], (true,))".parse().unwrap());
+    assert_eq!(
+        ts.to_string(),
+        quote!((2i32, [0u8, 0u8], (true,),)).to_string()
+    );
+    let ts = expand("[1, 2][2]".parse().unwrap());
+    assert!(ts.to_string().contains("index out of bounds"));
+    let ts = expand("[0; 100000000000]".parse().unwrap());
+    assert!(ts.to_string().contains("exceeds the limit 65536"));
+    let ts = expand("[[0; 65536]; 65536]".parse().unwrap());
+    assert!(ts
+        .to_string()
+        .contains("exceeds the limit of 65536 elements"));
+}
+
+#[test]
+fn expand_struct() {
+    let ts = expand(
+        "struct P { x: i32, y: bool } P { y: true, x: 1 + 2 }"
+            .parse()
+            .unwrap(),
+    );
+    assert_eq!(ts.to_string(), quote!(P { x: 3i32, y: true }).to_string());
+    let ts = expand("struct P { x: i32 } P { x: 1 }.y".parse().unwrap());
+    assert!(ts.to_string().contains("no field `y` on type `P`"));
+}
+
+#[test]
+fn expand_enum() {
+    let ts = expand(
+        "enum E { A(i32, bool), B } match E::A(1, true) { E::A(x, true) => E::A(x + 1, false), _ => E::B }"
+            .parse()
+            .unwrap(),
+    );
+    assert_eq!(ts.to_string(), quote!(E::A(2i32, false)).to_string());
+    let ts = expand("enum E { A, B } match E::A { E::A => 1 }".parse().unwrap());
+    assert!(ts
+        .to_string()
+        .contains("non-exhaustive patterns: `E::B` not covered"));
+}
+
+#[test]
+fn expand_borrow() {
+    let ts = expand(
+        "{ let mut x = 1; let r = &mut x; *r = 2; x }"
+            .parse()
+            .unwrap(),
+    );
+    assert_eq!(ts.to_string(), quote!(2i32).to_string());
+    // both borrows are reported
+    let ts = expand(
+        "{ let mut x = 1; let a = &mut x; let b = &mut x; *a + *b }"
+            .parse()
+            .unwrap(),
+    );
+    let ts = ts.to_string();
+    assert!(ts.contains("cannot borrow `x` as mutable more than once at a time"));
+    assert!(ts.contains("first mutable borrow occurs here"));
+}
+
+#[test]
+fn expand_type_error() {
+    let ts = expand("1 + true".parse().unwrap());
+    println!("ts {}", ts);
+    assert!(ts.to_string().contains("compile_error"));
+}
+
+#[test]
+fn expand_modes() {
+    // Int overflow (not possible with the bigint feature)
+    if !d7050e_lab2::BIGINT {
+        let ts = expand("2147483647 + 1".parse().unwrap());
+        assert!(ts.to_string().contains("attempt to compute with overflow"));
+        let ts = expand("wrapping; 2147483647 + 1".parse().unwrap());
+        assert_eq!(ts.to_string(), quote!(-2147483648i32).to_string());
+        let ts = expand("saturating; 2147483647 + 1".parse().unwrap());
+        assert_eq!(ts.to_string(), quote!(2147483647i32).to_string());
+    }
+    let ts = expand("wrapping; 255u8 + 1u8".parse().unwrap());
+    assert_eq!(ts.to_string(), quote!(0u8).to_string());
+    let ts = expand("unknown; 1".parse().unwrap());
+    assert!(ts.to_string().contains("compile_error"));
+}
+
+#[test]
+fn expand_div_by_zero() {
+    let ts = expand("4 / (2 - 2)".parse().unwrap());
+    println!("ts {}", ts);
+    assert!(ts.to_string().contains("attempt to divide by zero"));
+}
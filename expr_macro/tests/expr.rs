@@ -0,0 +1,147 @@
+use d7050e_lab2_macro::expr;
+
+#[test]
+fn expr_int() {
+    const X: i32 = expr!(2 - 3 * 4);
+    assert_eq!(X, 2 - 3 * 4);
+}
+
+#[test]
+fn expr_left_assoc() {
+    assert_eq!(expr!(8 - 7 - 6 * 5 - 4 * 3), 8 - 7 - 6 * 5 - 4 * 3);
+}
+
+#[test]
+fn expr_par() {
+    assert_eq!(expr!((2 - 3) * 4 / 2), (2 - 3) * 4 / 2);
+}
+
+#[test]
+fn expr_bool() {
+    let b: bool = expr!(true && false || true);
+    assert!(b);
+}
+
+#[test]
+fn expr_modes() {
+    // Int overflow (not possible with the bigint feature, which this
+    // crate forwards to the library, or the library may have on its own)
+    #[cfg(not(feature = "bigint"))]
+    if !d7050e_lab2::BIGINT {
+        assert_eq!(expr!(wrapping; 2147483647 + 1), i32::MIN);
+        assert_eq!(expr!(saturating; -2147483648 - 1), i32::MIN);
+        assert_eq!(expr!(checked; 2147483647 - 1), i32::MAX - 1);
+    }
+    assert_eq!(expr!(wrapping; 127i8 + 1i8), i8::MIN);
+    assert_eq!(expr!(saturating; -128i8 - 1i8), i8::MIN);
+    assert_eq!(expr!(checked; 127i8 - 1i8), i8::MAX - 1);
+}
+
+#[cfg(feature = "bigint")]
+#[test]
+fn expr_bigint() {
+    let x: i64 = expr!(2147483647 * 4 / 2);
+    assert_eq!(x, 4294967294);
+    let y: u128 = expr!(0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff / 3);
+    assert_eq!(y, u128::MAX / 3);
+    assert_eq!(
+        expr!(99999999999999999999999 - 99999999999999999999998),
+        1i32
+    );
+}
+
+#[test]
+fn expr_widths() {
+    let x: u64 = expr!(3_000_000_000u64 * 4u64);
+    assert_eq!(x, 12_000_000_000);
+    let y: i8 = expr!((300 as i8) - 1i8);
+    assert_eq!(y, 43);
+}
+
+#[test]
+fn expr_float() {
+    let x: f64 = expr!(0.1 + 0.2);
+    assert_eq!(x, 0.1 + 0.2);
+    let y: f32 = expr!(1f32 / 3f32);
+    assert_eq!(y, 1f32 / 3f32);
+    assert!(expr!(0.0 / 0.0).is_nan());
+}
+
+#[test]
+fn expr_str() {
+    let s: &str = expr!("hello" + " " + upper("world"));
+    assert_eq!(s, "hello WORLD");
+    assert_eq!(expr!(len(substr("abcdef", 1, 3))), 3);
+    let b: bool = expr!('a' < 'b' && "abc" < "abd");
+    assert!(b);
+}
+
+#[test]
+fn expr_tuple_array() {
+    let (a, t) = expr!(([1, 2, 3], (1.5, 'x')));
+    assert_eq!(a, [1, 2, 3]);
+    assert_eq!(t, (1.5, 'x'));
+    assert_eq!(expr!([[1, 2], [3, 4]][1][0] + (5, 6).1), 9);
+}
+
+#[derive(Debug, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+// rustfmt leaves macros invoked with braces as is
+#[test]
+fn expr_struct() {
+    let p = expr! {
+        struct Point { x: i32, y: i32 }
+        Point { x: 1 + 2, y: [4, 5][1] }
+    };
+    assert_eq!(p, Point { x: 3, y: 5 });
+    let x = expr! {
+        struct Point { x: i32, y: i32 }
+        struct Line { from: Point, to: Point }
+        Line { from: Point { x: 1, y: 2 }, to: Point { x: 3, y: 4 } }.to.x
+    };
+    assert_eq!(x, 3);
+}
+
+#[derive(Debug, PartialEq)]
+enum Shape {
+    Circle(i32),
+    Rect(i32, i32),
+    Empty,
+}
+
+#[test]
+fn expr_enum() {
+    let area = expr! {
+        enum Shape { Circle(i32), Rect(i32, i32), Empty }
+        match Shape::Rect(2, 3) {
+            Shape::Circle(r) => 3 * r * r,
+            Shape::Rect(w, h) => w * h,
+            Shape::Empty => 0,
+        }
+    };
+    assert_eq!(area, 6);
+    let s = expr! {
+        enum Shape { Circle(i32), Rect(i32, i32), Empty }
+        match 2 { 0 => Shape::Empty, n => Shape::Circle(n) }
+    };
+    assert_eq!(s, Shape::Circle(2));
+    assert_ne!(s, Shape::Rect(0, 0));
+    assert_ne!(s, Shape::Empty);
+}
+
+#[test]
+fn expr_block_ref() {
+    let x = expr! {{
+        let mut sum = 0;
+        let r = &mut sum;
+        *r = *r + 1;
+        *r = *r * 10;
+        let s = &sum;
+        *s + 1
+    }};
+    assert_eq!(x, 11);
+}
@@ -1,21 +1,548 @@
+#[cfg(feature = "bigint")]
+use crate::bigint::BigInt;
+use crate::{date::Date, typecheck::Type};
+use std::{
+    convert::TryFrom,
+    fmt,
+    ops::{Add, Div, Mul, Sub},
+};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Lit(Literal),
-    BinOp(Op, Box<Expr>, Box<Expr>),
+    // The span refers to the operator token
+    BinOp(Op, Box<Expr>, Box<Expr>, Span),
     Par(Box<Expr>),
+    // `e as ty`, the span refers to the `as` token
+    Cast(Box<Expr>, Type, Span),
+    // A call to a built in function, the span refers to the name
+    Call(Builtin, Vec<Expr>, Span),
+    // `(e1, e2, ...)`, distinct from Par by the commas, e.g., `(e,)`
+    Tuple(Vec<Expr>),
+    // `[e1, e2, ...]`, the span refers to the brackets
+    Array(Vec<Expr>, Span),
+    // `[e; n]`, n copies of e, n at most `MAX_REPEAT`
+    Repeat(Box<Expr>, usize),
+    // `a[i]`, the span refers to the index expression
+    Index(Box<Expr>, Box<Expr>, Span),
+    // `t.0` or `p.x`, the span refers to the field
+    Field(Box<Expr>, Member, Span),
+    // `Name { field: e, ... }`, the span refers to the name
+    Struct(String, Vec<(String, Expr)>, Span),
+    // `Enum::Variant(e, ...)`, or `Enum::Variant` without payload,
+    // the span refers to the variant
+    Variant(String, String, Vec<Expr>, Span),
+    // A variable bound by a pattern
+    Var(String, Span),
+    // `match e { pat => e, ... }`, the span refers to the `match` token
+    Match(Box<Expr>, Vec<Arm>, Span),
+    // `{ stmt; ... e }`, the value of a block without a final
+    // expression is `()`, the span refers to the braces
+    Block(Vec<Stmt>, Box<Expr>, Span),
+    // `&e` or `&mut e`, the span refers to the `&`
+    Ref(bool, Box<Expr>, Span),
+    // `*e`, the span refers to the `*`
+    Deref(Box<Expr>, Span),
+}
+
+// The largest length of `[e; n]`, also for nested arrays in total,
+// as the arrays are allocated when evaluated
+pub const MAX_REPEAT: usize = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    // `let x = e;` or `let mut x = e;`, the span refers to the name
+    Let(String, bool, Expr, Span),
+    // `x = e;` or `*r = e;`, the span refers to the `=`
+    Assign(Expr, Expr, Span),
+    // `e;`
+    Expr(Expr),
+}
+
+// `pat => body`, the span refers to the pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm {
+    pub pat: Pattern,
+    pub body: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    // `_`
+    Wild,
+    // `x`, binds the value
+    Bind(String),
+    // a literal, e.g., `1` or `true`
+    Lit(Literal),
+    // `Enum::Variant(p, ...)`, or `Enum::Variant` without payload
+    Variant(String, String, Vec<Pattern>),
+}
+
+impl Pattern {
+    // Match the value against the pattern, on success the
+    // bound variables are pushed to vars
+    pub fn matches(&self, v: &Literal, vars: &mut Vec<(String, Literal)>) -> bool {
+        match (self, v) {
+            (Pattern::Wild, _) => true,
+            (Pattern::Bind(x), v) => {
+                vars.push((x.clone(), v.clone()));
+                true
+            }
+            (Pattern::Lit(l), v) => l == v,
+            (Pattern::Variant(e, var, ps), Literal::Enum(e2, var2, vs)) => {
+                e == e2
+                    && var == var2
+                    && ps.len() == vs.len()
+                    && ps.iter().zip(vs).all(|(p, v)| p.matches(v, vars))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Pattern {
+    // The variables bound by the pattern
+    pub fn bindings(&self) -> Vec<String> {
+        match self {
+            Pattern::Bind(x) => vec![x.clone()],
+            Pattern::Variant(_, _, ps) => ps.iter().flat_map(|p| p.bindings()).collect(),
+            Pattern::Wild | Pattern::Lit(_) => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Wild => f.write_str("_"),
+            Pattern::Bind(x) => f.write_str(x),
+            Pattern::Lit(l) => write!(f, "{}", l),
+            Pattern::Variant(e, v, ps) if ps.is_empty() => write!(f, "{}::{}", e, v),
+            Pattern::Variant(e, v, ps) => write!(f, "{}::{}({})", e, v, list(ps)),
+        }
+    }
+}
+
+// The expression as source, parentheses are added where
+// needed to preserve the structure of the tree
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // operands of unary operators, casts, indexing and field access
+        let operand = |e: &Expr, postfix: bool| match e {
+            Expr::BinOp(..) | Expr::Cast(..) => format!("({})", e),
+            Expr::Ref(..) | Expr::Deref(..) if postfix => format!("({})", e),
+            _ => e.to_string(),
+        };
+        match self {
+            Expr::Lit(l) => write!(f, "{}", l),
+            // left associative, the right operand needs parentheses
+            // also for equal priority
+            Expr::BinOp(op, l, r, _) => {
+                let l = match &**l {
+                    Expr::BinOp(o, ..) if o.priority() < op.priority() => format!("({})", l),
+                    _ => l.to_string(),
+                };
+                let r = match &**r {
+                    Expr::BinOp(o, ..) if o.priority() <= op.priority() => format!("({})", r),
+                    _ => r.to_string(),
+                };
+                write!(f, "{} {} {}", l, op, r)
+            }
+            Expr::Par(e) => write!(f, "({})", e),
+            Expr::Cast(e, ty, _) => match &**e {
+                Expr::BinOp(..) => write!(f, "({}) as {}", e, ty),
+                _ => write!(f, "{} as {}", e, ty),
+            },
+            Expr::Call(b, args, _) => write!(f, "{}({})", b, list(args)),
+            Expr::Tuple(es) if es.len() == 1 => write!(f, "({},)", es[0]),
+            Expr::Tuple(es) => write!(f, "({})", list(es)),
+            Expr::Array(es, _) => write!(f, "[{}]", list(es)),
+            Expr::Repeat(e, n) => write!(f, "[{}; {}]", e, n),
+            Expr::Index(a, i, _) => write!(f, "{}[{}]", operand(a, true), i),
+            Expr::Field(t, m, _) => write!(f, "{}.{}", operand(t, true), m),
+            Expr::Struct(name, fields, _) => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|(f, e)| format!("{}: {}", f, e))
+                    .collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+            Expr::Variant(e, v, args, _) if args.is_empty() => write!(f, "{}::{}", e, v),
+            Expr::Variant(e, v, args, _) => write!(f, "{}::{}({})", e, v, list(args)),
+            Expr::Var(x, _) => f.write_str(x),
+            Expr::Match(e, arms, _) => {
+                let arms: Vec<_> = arms
+                    .iter()
+                    .map(|arm| format!("{} => {}", arm.pat, arm.body))
+                    .collect();
+                write!(f, "match {} {{ {} }}", e, arms.join(", "))
+            }
+            Expr::Block(stmts, e, _) => {
+                f.write_str("{ ")?;
+                for stmt in stmts {
+                    write!(f, "{} ", stmt)?;
+                }
+                match &**e {
+                    Expr::Tuple(es) if es.is_empty() => f.write_str("}"),
+                    e => write!(f, "{} }}", e),
+                }
+            }
+            Expr::Ref(true, e, _) => write!(f, "&mut {}", operand(e, false)),
+            Expr::Ref(false, e, _) => write!(f, "&{}", operand(e, false)),
+            Expr::Deref(e, _) => write!(f, "*{}", operand(e, false)),
+        }
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmt::Let(x, true, e, _) => write!(f, "let mut {} = {};", x, e),
+            Stmt::Let(x, false, e, _) => write!(f, "let {} = {};", x, e),
+            Stmt::Assign(place, e, _) => write!(f, "{} = {};", place, e),
+            Stmt::Expr(e) => write!(f, "{};", e),
+        }
+    }
+}
+
+// A tuple field or a named struct field
+#[derive(Debug, Clone, PartialEq)]
+pub enum Member {
+    Index(usize),
+    Named(String),
+}
+
+impl fmt::Display for Member {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Member::Index(n) => write!(f, "{}", n),
+            Member::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+// `struct Name { field: ty, ... }`, the span refers to the name
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDecl {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+    pub span: Span,
+}
+
+// `enum Name { Variant(ty, ...), Variant, ... }`, the span refers to the name
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDecl {
+    pub name: String,
+    // the variants and their payload types
+    pub variants: Vec<(String, Vec<Type>)>,
+    pub span: Span,
+}
+
+// A top level declaration
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Struct(StructDecl),
+    Enum(EnumDecl),
+}
+
+// The items followed by the (climbed) expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub items: Vec<Item>,
+    pub expr: Expr,
+}
+
+// A plain comment, the text after the `//` or between the `/*` and `*/`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comment {
+    Line(String),
+    Block(String),
+}
+
+impl fmt::Display for Comment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comment::Line(text) => write!(f, "//{}", text),
+            Comment::Block(text) => write!(f, "/*{}*/", text),
+        }
+    }
+}
+
+// A top level expression of a file, with its doc comments, the text
+// after the `///` (or between the `/**` and `*/`), e.g., `" The total"`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Documented {
+    // the plain comments before the expression (and its doc comments)
+    pub before: Vec<Comment>,
+    pub docs: Vec<String>,
+    pub expr: Expr,
+    // the plain comments within the expression, after the given number
+    // of its tokens, e.g., `/* 2 + */` after 2 in `1 + /* 2 + */ 3`
+    pub inside: Vec<(usize, Comment)>,
+    // the plain comments after the `;`, on the same line
+    pub trailing: Vec<Comment>,
+}
+
+// A file of expressions separated by `;`, doc comments are kept with
+// their expression. Parsed by `str::parse` (`lexer_parse::file`), the
+// plain comments (`//` and `/* */`) are kept too, while `proc_macro2`
+// (and so `syn`) drops them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprFile {
+    pub exprs: Vec<Documented>,
+    // the plain comments after the last expression
+    pub comments: Vec<Comment>,
+}
+
+// The comments before, one per line, followed by the expression with
+// the comments within it. The trailing comments are printed by
+// `ExprFile`, after the `;`.
+impl fmt::Display for Documented {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for comment in &self.before {
+            writeln!(f, "{}", comment)?;
+        }
+        for doc in &self.docs {
+            if doc.contains('\n') {
+                writeln!(f, "/**{}*/", doc)?;
+            } else {
+                writeln!(f, "///{}", doc)?;
+            }
+        }
+        let expr = self.expr.to_string();
+        // the ends of the printed tokens, as lexed from the source
+        let ends: Vec<usize> = crate::lexer::lex(&expr)
+            .map(|tokens| tokens.iter().map(|t| t.end).collect())
+            .unwrap_or_default();
+        let mut at = 0;
+        for (n, comment) in &self.inside {
+            let i = match n {
+                0 => 0,
+                n => ends.get(n - 1).copied().unwrap_or(expr.len()).max(at),
+            };
+            f.write_str(&expr[at..i])?;
+            match comment {
+                Comment::Line(_) if i == 0 => writeln!(f, "{}", comment)?,
+                Comment::Line(_) => writeln!(f, " {}", comment)?,
+                Comment::Block(_) if i == 0 => write!(f, "{} ", comment)?,
+                Comment::Block(_) => write!(f, " {}", comment)?,
+            }
+            at = i;
+            if let Comment::Line(_) = comment {
+                at += expr[i..].len() - expr[i..].trim_start().len();
+            }
+        }
+        f.write_str(&expr[at..])
+    }
+}
+
+impl fmt::Display for ExprFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for e in &self.exprs {
+            write!(f, "{};", e)?;
+            for comment in &e.trailing {
+                write!(f, " {}", comment)?;
+            }
+            writeln!(f)?;
+        }
+        for comment in &self.comments {
+            writeln!(f, "{}", comment)?;
+        }
+        Ok(())
+    }
 }
 
 impl Expr {
+    // Evaluate expression, panics on evaluation errors
     pub fn eval(&self) -> Literal {
+        match self.try_eval() {
+            Ok(l) => l,
+            Err(err) => panic!("evaluation failed: {}", err),
+        }
+    }
+
+    // Evaluate expression using checked arithmetic,
+    // errors are reported with the span of the operator
+    pub fn try_eval(&self) -> Result<Literal, EvalError> {
+        self.eval_with(ArithMode::Checked)
+    }
+
+    // Evaluate expression using the given arithmetic mode
+    pub fn eval_with(&self, mode: ArithMode) -> Result<Literal, EvalError> {
+        self.eval_in(mode, &mut vec![])
+    }
+
+    // Evaluate expression with the variables in scope, later variables
+    // shadow earlier ones, references are indices into the variables
+    pub fn eval_in(
+        &self,
+        mode: ArithMode,
+        vars: &mut Vec<(String, Literal)>,
+    ) -> Result<Literal, EvalError> {
+        match self {
+            Expr::Lit(literal) => Ok(literal.clone()),
+            // && and || are short circuiting, the right operand is only
+            // evaluated if needed
+            Expr::BinOp(op @ (Op::And | Op::Or), left, right, span) => {
+                let l = left.eval_in(mode, vars)?;
+                match (op, &l) {
+                    (Op::And, Literal::Bool(false)) | (Op::Or, Literal::Bool(true)) => Ok(l),
+                    (_, Literal::Bool(_)) => {
+                        let r = right.eval_in(mode, vars)?;
+                        op.eval_with(mode, l, r)
+                            .map_err(|kind| EvalError::new(kind, *span))
+                    }
+                    _ => Err(EvalError::new(EvalErrorKind::TypeMismatch, *span)),
+                }
+            }
+            Expr::BinOp(op, left, right, span) => {
+                let l = left.eval_in(mode, vars)?;
+                let r = right.eval_in(mode, vars)?;
+                op.eval_with(mode, l, r)
+                    .map_err(|kind| EvalError::new(kind, *span))
+            }
+            Expr::Par(e) => e.eval_in(mode, vars),
+            Expr::Cast(e, ty, span) => e
+                .eval_in(mode, vars)?
+                .cast(ty)
+                .ok_or_else(|| EvalError::new(EvalErrorKind::InvalidCast, *span)),
+            Expr::Call(f, args, span) => {
+                let args = args
+                    .iter()
+                    .map(|a| a.eval_in(mode, vars))
+                    .collect::<Result<Vec<_>, _>>()?;
+                f.eval(args).map_err(|kind| EvalError::new(kind, *span))
+            }
+            Expr::Tuple(es) => Ok(Literal::Tuple(eval_all(es, mode, vars)?)),
+            Expr::Array(es, _) => Ok(Literal::Array(eval_all(es, mode, vars)?)),
+            Expr::Repeat(e, n) => Ok(Literal::Array(vec![e.eval_in(mode, vars)?; *n])),
+            Expr::Index(a, i, span) => match (a.eval_in(mode, vars)?, i.eval_in(mode, vars)?) {
+                (Literal::Array(a), i) if i.get_type().is_int() => i
+                    .to_index()
+                    .and_then(|i| a.into_iter().nth(i))
+                    .ok_or_else(|| EvalError::new(EvalErrorKind::OutOfBounds, *span)),
+                _ => Err(EvalError::new(EvalErrorKind::TypeMismatch, *span)),
+            },
+            Expr::Field(t, m, span) => match (t.eval_in(mode, vars)?, m) {
+                (Literal::Tuple(t), Member::Index(n)) if *n < t.len() => {
+                    Ok(t.into_iter().nth(*n).unwrap())
+                }
+                (Literal::Struct(_, fields), Member::Named(name)) => fields
+                    .into_iter()
+                    .find(|(f, _)| f == name)
+                    .map(|(_, v)| v)
+                    .ok_or_else(|| EvalError::new(EvalErrorKind::TypeMismatch, *span)),
+                _ => Err(EvalError::new(EvalErrorKind::TypeMismatch, *span)),
+            },
+            // the fields are kept sorted by name, so values compare equal
+            // regardless of the order of the fields in the literal
+            Expr::Struct(name, fields, _) => {
+                let mut fields = fields
+                    .iter()
+                    .map(|(f, e)| Ok((f.clone(), e.eval_in(mode, vars)?)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Ok(Literal::Struct(name.clone(), fields))
+            }
+            Expr::Variant(e, v, args, _) => Ok(Literal::Enum(
+                e.clone(),
+                v.clone(),
+                eval_all(args, mode, vars)?,
+            )),
+            Expr::Var(x, span) => vars
+                .iter()
+                .rev()
+                .find(|(y, _)| x == y)
+                .map(|(_, v)| v.clone())
+                .ok_or_else(|| EvalError::new(EvalErrorKind::UnboundVar, *span)),
+            // the first matching arm is evaluated, with the
+            // pattern variables in scope
+            Expr::Match(e, arms, span) => {
+                let v = e.eval_in(mode, vars)?;
+                let len = vars.len();
+                for arm in arms {
+                    if arm.pat.matches(&v, vars) {
+                        let v = arm.body.eval_in(mode, vars);
+                        vars.truncate(len);
+                        return v;
+                    }
+                    vars.truncate(len);
+                }
+                Err(EvalError::new(EvalErrorKind::NoMatch, *span))
+            }
+            // the variables declared in the block go out of scope at its end
+            Expr::Block(stmts, e, _) => {
+                let len = vars.len();
+                let v = (|| {
+                    for stmt in stmts {
+                        match stmt {
+                            Stmt::Let(x, _, e, _) => {
+                                let v = e.eval_in(mode, vars)?;
+                                vars.push((x.clone(), v));
+                            }
+                            Stmt::Assign(place, e, _) => {
+                                let v = e.eval_in(mode, vars)?;
+                                let i = place.place(mode, vars)?;
+                                vars[i].1 = v;
+                            }
+                            Stmt::Expr(e) => {
+                                e.eval_in(mode, vars)?;
+                            }
+                        }
+                    }
+                    e.eval_in(mode, vars)
+                })();
+                vars.truncate(len);
+                v
+            }
+            Expr::Ref(_, e, _) => Ok(Literal::Ref(e.place(mode, vars)?)),
+            Expr::Deref(..) => {
+                let i = self.place(mode, vars)?;
+                Ok(vars[i].1.clone())
+            }
+        }
+    }
+
+    // The index of the variable a place expression (`x` or `*r`) refers to
+    fn place(
+        &self,
+        mode: ArithMode,
+        vars: &mut Vec<(String, Literal)>,
+    ) -> Result<usize, EvalError> {
         match self {
-            Expr::Lit(literal) => literal.clone(),
-            Expr::BinOp(op, left, right) => op.eval(left.eval(), right.eval()),
-            Expr::Par(e) => e.eval(),
+            Expr::Var(x, span) => vars
+                .iter()
+                .rposition(|(y, _)| x == y)
+                .ok_or_else(|| EvalError::new(EvalErrorKind::UnboundVar, *span)),
+            Expr::Deref(e, span) => match e.eval_in(mode, vars)? {
+                Literal::Ref(i) if i < vars.len() => Ok(i),
+                Literal::Ref(_) => Err(EvalError::new(EvalErrorKind::Dangling, *span)),
+                _ => Err(EvalError::new(EvalErrorKind::TypeMismatch, *span)),
+            },
+            Expr::Par(e) => e.place(mode, vars),
+            // rejected by the type checker
+            _ => Err(EvalError::new(EvalErrorKind::TypeMismatch, Span::default())),
         }
     }
 
     pub fn bin_op(o: Op, left: Expr, right: Expr) -> Self {
-        Expr::BinOp(o, Box::new(left), Box::new(right))
+        Expr::BinOp(o, Box::new(left), Box::new(right), Span::default())
+    }
+}
+
+fn eval_all(
+    es: &[Expr],
+    mode: ArithMode,
+    vars: &mut Vec<(String, Literal)>,
+) -> Result<Vec<Literal>, EvalError> {
+    es.iter().map(|e| e.eval_in(mode, vars)).collect()
+}
+
+impl Program {
+    // Evaluate the expression, the items only matter for type checking
+    pub fn eval_with(&self, mode: ArithMode) -> Result<Literal, EvalError> {
+        self.expr.eval_with(mode)
     }
 }
 
@@ -31,10 +558,122 @@ impl From<i32> for Expr {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+impl From<bool> for Expr {
+    fn from(b: bool) -> Self {
+        Expr::Lit(Literal::Bool(b))
+    }
+}
+
+// Int is the default integer type (i32) and F64 the default float
+// type, as for unsuffixed literals in Rust, other types are given
+// by the suffix.
+//
+// With the `bigint` feature Int is unbounded, values that do not fit
+// in an i32 are represented by Big (and values that fit never are).
+#[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     Bool(bool),
     Int(i32),
+    #[cfg(feature = "bigint")]
+    Big(BigInt),
+    I8(i8),
+    I16(i16),
+    I64(i64),
+    I128(i128),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    Str(String),
+    // `d"2024-02-29"`
+    Date(Date),
+    Tuple(Vec<Literal>),
+    Array(Vec<Literal>),
+    // the struct name and the fields, sorted by name
+    Struct(String, Vec<(String, Literal)>),
+    // the enum name, the variant and the payload
+    Enum(String, String, Vec<Literal>),
+    // a reference, the index of the variable referred to
+    Ref(usize),
+}
+
+// Print a float such that it parses back to the exact same value.
+// Debug gives the shortest representation that round-trips, and
+// always includes a `.` or exponent (so it is not parsed as an integer).
+// Infinities and NaN have no literals, so we print an expression
+// evaluating to them.
+fn fmt_float<T: fmt::Debug>(
+    f: &mut fmt::Formatter<'_>,
+    v: T,
+    finite: bool,
+    nan: bool,
+    neg: bool,
+    suffix: &str,
+) -> fmt::Result {
+    match (finite, nan, neg) {
+        (true, _, _) => write!(f, "{:?}{}", v, suffix),
+        (_, true, _) => write!(f, "(0.0{} / 0.0{})", suffix, suffix),
+        (_, _, false) => write!(f, "(1.0{} / 0.0{})", suffix, suffix),
+        (_, _, true) => write!(f, "(-1.0{} / 0.0{})", suffix, suffix),
+    }
+}
+
+// Integers other than i32 (and floats other than f64) are printed
+// with their suffix
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::Int(i) => write!(f, "{}", i),
+            #[cfg(feature = "bigint")]
+            Literal::Big(i) => write!(f, "{}", i),
+            Literal::I8(i) => write!(f, "{}i8", i),
+            Literal::I16(i) => write!(f, "{}i16", i),
+            Literal::I64(i) => write!(f, "{}i64", i),
+            Literal::I128(i) => write!(f, "{}i128", i),
+            Literal::U8(i) => write!(f, "{}u8", i),
+            Literal::U16(i) => write!(f, "{}u16", i),
+            Literal::U32(i) => write!(f, "{}u32", i),
+            Literal::U64(i) => write!(f, "{}u64", i),
+            Literal::U128(i) => write!(f, "{}u128", i),
+            Literal::F32(v) => {
+                fmt_float(f, v, v.is_finite(), v.is_nan(), v.is_sign_negative(), "f32")
+            }
+            Literal::F64(v) => fmt_float(f, v, v.is_finite(), v.is_nan(), v.is_sign_negative(), ""),
+            // quoted and escaped as Rust literals
+            Literal::Char(c) => write!(f, "{:?}", c),
+            Literal::Str(s) => write!(f, "{:?}", s),
+            Literal::Date(d) => write!(f, "d\"{}\"", d),
+            // a single element tuple needs a trailing comma
+            Literal::Tuple(t) if t.len() == 1 => write!(f, "({},)", t[0]),
+            Literal::Tuple(t) => write!(f, "({})", list(t)),
+            Literal::Array(a) => write!(f, "[{}]", list(a)),
+            Literal::Struct(name, fields) => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|(f, v)| format!("{}: {}", f, v))
+                    .collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+            Literal::Enum(e, v, vs) if vs.is_empty() => write!(f, "{}::{}", e, v),
+            Literal::Enum(e, v, vs) => write!(f, "{}::{}({})", e, v, list(vs)),
+            // references have no literals
+            Literal::Ref(i) => write!(f, "&{{{}}}", i),
+        }
+    }
+}
+
+// Elements separated by `, `
+pub(crate) fn list<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 impl From<i32> for Literal {
@@ -43,6 +682,35 @@ impl From<i32> for Literal {
     }
 }
 
+// An Int, Big only if the value does not fit in an i32
+#[cfg(feature = "bigint")]
+impl From<BigInt> for Literal {
+    fn from(i: BigInt) -> Self {
+        match i.to_i32() {
+            Some(i) => Literal::Int(i),
+            None => Literal::Big(i),
+        }
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(f: f64) -> Self {
+        Literal::F64(f)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(s: &str) -> Self {
+        Literal::Str(s.to_string())
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(b: bool) -> Self {
+        Literal::Bool(b)
+    }
+}
+
 impl From<Expr> for Literal {
     fn from(e: Expr) -> Self {
         match e {
@@ -60,8 +728,144 @@ impl Literal {
         }
     }
 
+    // The value of an integer literal as an index, None if negative
+    // or too large
+    pub fn to_index(&self) -> Option<usize> {
+        use std::convert::TryInto;
+        use Literal::*;
+        match *self {
+            Int(i) => i.try_into().ok(),
+            I8(i) => i.try_into().ok(),
+            I16(i) => i.try_into().ok(),
+            I64(i) => i.try_into().ok(),
+            I128(i) => i.try_into().ok(),
+            U8(i) => Some(i.into()),
+            U16(i) => Some(i.into()),
+            U32(i) => i.try_into().ok(),
+            U64(i) => i.try_into().ok(),
+            U128(i) => i.try_into().ok(),
+            _ => None,
+        }
+    }
+
+    // The value of an Int (or Big) literal
+    #[cfg(feature = "bigint")]
+    pub fn to_big(&self) -> BigInt {
+        match self {
+            Literal::Int(i) => BigInt::from(*i),
+            Literal::Big(i) => i.clone(),
+            _ => panic!("cannot get integer from {}", self),
+        }
+    }
+
     pub fn get_bool(&self) -> bool {
-        unimplemented!()
+        match self {
+            Literal::Bool(b) => *b,
+            _ => panic!("cannot get Bool from integer"),
+        }
+    }
+
+    // Cast with the semantics of Rust `as`, i.e., integers are
+    // truncated or extended, floats are rounded towards zero and
+    // saturated (NaN to 0), Bool is cast to 0 or 1, char to its
+    // code point and u8 to char.
+    // None if the cast is not allowed (e.g., to Bool).
+    pub fn cast(&self, ty: &Type) -> Option<Literal> {
+        use Literal::*;
+        match (self, ty) {
+            (Char(c), ty) if ty.is_int() => return U32(*c as u32).cast(ty),
+            (Char(_), Type::Char) => return Some(self.clone()),
+            (U8(i), Type::Char) => return Some(Char(*i as char)),
+            (
+                Char(_)
+                | Str(_)
+                | Literal::Date(_)
+                | Tuple(_)
+                | Array(_)
+                | Struct(..)
+                | Enum(..)
+                | Ref(_),
+                _,
+            )
+            | (
+                _,
+                Type::Char
+                | Type::Str
+                | Type::Date
+                | Type::Tuple(_)
+                | Type::Array(..)
+                | Type::Struct(_)
+                | Type::Enum(_)
+                | Type::Ref(..),
+            ) => return None,
+            _ => {}
+        }
+        // truncating to 128 bits preserves the result of integer casts
+        #[cfg(feature = "bigint")]
+        if let Big(i) = self {
+            return match ty {
+                Type::Bool => None,
+                Type::F32 => Some(F32(i.to_f64() as f32)),
+                Type::F64 => Some(F64(i.to_f64())),
+                _ => U128(i.to_u128_wrapping()).cast(ty),
+            };
+        }
+        macro_rules! cast {
+            ($t:ty) => {
+                match *self {
+                    Bool(b) => b as u8 as $t,
+                    Int(i) => i as $t,
+                    I8(i) => i as $t,
+                    I16(i) => i as $t,
+                    I64(i) => i as $t,
+                    I128(i) => i as $t,
+                    U8(i) => i as $t,
+                    U16(i) => i as $t,
+                    U32(i) => i as $t,
+                    U64(i) => i as $t,
+                    U128(i) => i as $t,
+                    F32(f) => f as $t,
+                    F64(f) => f as $t,
+                    Char(_)
+                    | Str(_)
+                    | Literal::Date(_)
+                    | Tuple(_)
+                    | Array(_)
+                    | Struct(..)
+                    | Enum(..)
+                    | Ref(_) => unreachable!(),
+                    #[cfg(feature = "bigint")]
+                    Big(_) => unreachable!(),
+                }
+            };
+        }
+        Some(match ty {
+            Type::Bool => match self {
+                Bool(_) => self.clone(),
+                _ => return None,
+            },
+            Type::Int => Int(cast!(i32)),
+            Type::I8 => I8(cast!(i8)),
+            Type::I16 => I16(cast!(i16)),
+            Type::I64 => I64(cast!(i64)),
+            Type::I128 => I128(cast!(i128)),
+            Type::U8 => U8(cast!(u8)),
+            Type::U16 => U16(cast!(u16)),
+            Type::U32 => U32(cast!(u32)),
+            Type::U64 => U64(cast!(u64)),
+            Type::U128 => U128(cast!(u128)),
+            Type::F32 => F32(cast!(f32)),
+            Type::F64 => F64(cast!(f64)),
+            // handled above
+            Type::Char
+            | Type::Str
+            | Type::Date
+            | Type::Tuple(_)
+            | Type::Array(..)
+            | Type::Struct(_)
+            | Type::Enum(_)
+            | Type::Ref(..) => unreachable!(),
+        })
     }
 }
 
@@ -71,31 +875,399 @@ pub enum Op {
     Sub,
     Mul,
     Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
     And,
     Or,
 }
 
+// How integer overflow is handled by arithmetic operators.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum ArithMode {
+    // overflow is an evaluation error
+    #[default]
+    Checked,
+    // wrap around (two's complement)
+    Wrapping,
+    // clamp to the min/max value
+    Saturating,
+}
+
+// Arithmetic for the integer types
+trait Integer: Copy + PartialOrd {
+    fn arith(self, op: Op, mode: ArithMode, r: Self) -> Result<Self, EvalErrorKind>;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(impl Integer for $t {
+            fn arith(self, op: Op, mode: ArithMode, r: Self) -> Result<Self, EvalErrorKind> {
+                use ArithMode::*;
+                let l = self;
+                match (op, mode) {
+                    (Op::Div, _) if r == 0 => Err(EvalErrorKind::DivByZero),
+                    (Op::Add, Checked) => l.checked_add(r).ok_or(EvalErrorKind::Overflow),
+                    (Op::Add, Wrapping) => Ok(l.wrapping_add(r)),
+                    (Op::Add, Saturating) => Ok(l.saturating_add(r)),
+                    (Op::Sub, Checked) => l.checked_sub(r).ok_or(EvalErrorKind::Overflow),
+                    (Op::Sub, Wrapping) => Ok(l.wrapping_sub(r)),
+                    (Op::Sub, Saturating) => Ok(l.saturating_sub(r)),
+                    (Op::Mul, Checked) => l.checked_mul(r).ok_or(EvalErrorKind::Overflow),
+                    (Op::Mul, Wrapping) => Ok(l.wrapping_mul(r)),
+                    (Op::Mul, Saturating) => Ok(l.saturating_mul(r)),
+                    (Op::Div, Checked) => l.checked_div(r).ok_or(EvalErrorKind::Overflow),
+                    (Op::Div, Wrapping) => Ok(l.wrapping_div(r)),
+                    (Op::Div, Saturating) => Ok(l.saturating_div(r)),
+                    _ => Err(EvalErrorKind::TypeMismatch),
+                }
+            }
+        })*
+    };
+}
+
+impl_integer!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+
+// Evaluate a comparison operator
+fn cmp_op<T: PartialOrd>(op: Op, l: T, r: T) -> Result<Literal, EvalErrorKind> {
+    use Literal::Bool;
+    match op {
+        Op::Eq => Ok(Bool(l == r)),
+        Op::Ne => Ok(Bool(l != r)),
+        Op::Lt => Ok(Bool(l < r)),
+        Op::Le => Ok(Bool(l <= r)),
+        Op::Gt => Ok(Bool(l > r)),
+        Op::Ge => Ok(Bool(l >= r)),
+        _ => Err(EvalErrorKind::TypeMismatch),
+    }
+}
+
+// Evaluate an operator on floats of the same type, following IEEE 754
+// (division by zero gives an infinity or NaN, comparisons with NaN are false)
+fn float_op<T>(op: Op, l: T, r: T, lit: fn(T) -> Literal) -> Result<Literal, EvalErrorKind>
+where
+    T: Copy + PartialOrd + Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T>,
+{
+    use Literal::Bool;
+    match op {
+        Op::Add => Ok(lit(l + r)),
+        Op::Sub => Ok(lit(l - r)),
+        Op::Mul => Ok(lit(l * r)),
+        Op::Div => Ok(lit(l / r)),
+        Op::Eq => Ok(Bool(l == r)),
+        Op::Ne => Ok(Bool(l != r)),
+        Op::Lt => Ok(Bool(l < r)),
+        Op::Le => Ok(Bool(l <= r)),
+        Op::Gt => Ok(Bool(l > r)),
+        Op::Ge => Ok(Bool(l >= r)),
+        Op::And | Op::Or => Err(EvalErrorKind::TypeMismatch),
+    }
+}
+
+// Evaluate an operator on unbounded Int values, the arithmetic
+// never overflows so the mode is irrelevant
+#[cfg(feature = "bigint")]
+fn big_op(op: Op, l: BigInt, r: BigInt) -> Result<Literal, EvalErrorKind> {
+    use Literal::Bool;
+    match op {
+        Op::Add => Ok((&l + &r).into()),
+        Op::Sub => Ok((&l - &r).into()),
+        Op::Mul => Ok((&l * &r).into()),
+        Op::Div => Ok(l.checked_div(&r).ok_or(EvalErrorKind::DivByZero)?.into()),
+        Op::Eq => Ok(Bool(l == r)),
+        Op::Ne => Ok(Bool(l != r)),
+        Op::Lt => Ok(Bool(l < r)),
+        Op::Le => Ok(Bool(l <= r)),
+        Op::Gt => Ok(Bool(l > r)),
+        Op::Ge => Ok(Bool(l >= r)),
+        Op::And | Op::Or => Err(EvalErrorKind::TypeMismatch),
+    }
+}
+
+// Evaluate an operator on integers of the same type,
+// `lit` turns the result back into a literal
+fn int_op<T: Integer>(
+    op: Op,
+    mode: ArithMode,
+    l: T,
+    r: T,
+    lit: fn(T) -> Literal,
+) -> Result<Literal, EvalErrorKind> {
+    use Literal::Bool;
+    match op {
+        Op::Add | Op::Sub | Op::Mul | Op::Div => Ok(lit(l.arith(op, mode, r)?)),
+        Op::Eq => Ok(Bool(l == r)),
+        Op::Ne => Ok(Bool(l != r)),
+        Op::Lt => Ok(Bool(l < r)),
+        Op::Le => Ok(Bool(l <= r)),
+        Op::Gt => Ok(Bool(l > r)),
+        Op::Ge => Ok(Bool(l >= r)),
+        Op::And | Op::Or => Err(EvalErrorKind::TypeMismatch),
+    }
+}
+
 impl Op {
+    // All operators, in the order they are tried by the parser
+    pub const ALL: [Op; 12] = [
+        Op::Add,
+        Op::Sub,
+        Op::Mul,
+        Op::Div,
+        Op::Eq,
+        Op::Ne,
+        Op::Le,
+        Op::Ge,
+        Op::Lt,
+        Op::Gt,
+        Op::And,
+        Op::Or,
+    ];
+
+    // Evaluate operator to literal, using checked arithmetic
+    pub fn eval(&self, left: Literal, right: Literal) -> Result<Literal, EvalErrorKind> {
+        self.eval_with(ArithMode::Checked, left, right)
+    }
+
     // Evaluate operator to literal
-    pub fn eval(&self, left: Literal, right: Literal) -> Literal {
-        match self {
-            Op::Add => Literal::Int(left.get_int() + right.get_int()),
-            Op::Sub => Literal::Int(left.get_int() - right.get_int()),
-            Op::Mul => Literal::Int(left.get_int() * right.get_int()),
-            _ => unimplemented!(),
+    // Both operands must have the same type, integer overflow
+    // is handled according to the mode
+    pub fn eval_with(
+        &self,
+        mode: ArithMode,
+        left: Literal,
+        right: Literal,
+    ) -> Result<Literal, EvalErrorKind> {
+        use Literal::*;
+        let op = *self;
+        match (left, right) {
+            #[cfg(feature = "bigint")]
+            (l @ (Int(_) | Big(_)), r @ (Int(_) | Big(_))) => big_op(op, l.to_big(), r.to_big()),
+            #[cfg(not(feature = "bigint"))]
+            (Int(l), Int(r)) => int_op(op, mode, l, r, Int),
+            (I8(l), I8(r)) => int_op(op, mode, l, r, I8),
+            (I16(l), I16(r)) => int_op(op, mode, l, r, I16),
+            (I64(l), I64(r)) => int_op(op, mode, l, r, I64),
+            (I128(l), I128(r)) => int_op(op, mode, l, r, I128),
+            (U8(l), U8(r)) => int_op(op, mode, l, r, U8),
+            (U16(l), U16(r)) => int_op(op, mode, l, r, U16),
+            (U32(l), U32(r)) => int_op(op, mode, l, r, U32),
+            (U64(l), U64(r)) => int_op(op, mode, l, r, U64),
+            (U128(l), U128(r)) => int_op(op, mode, l, r, U128),
+            (F32(l), F32(r)) => float_op(op, l, r, F32),
+            (F64(l), F64(r)) => float_op(op, l, r, F64),
+            // `+` is concatenation, strings are ordered lexicographically
+            (Str(l), Str(r)) => match op {
+                Op::Add => Ok(Str(l + &r)),
+                _ => cmp_op(op, l, r),
+            },
+            (Char(l), Char(r)) => cmp_op(op, l, r),
+            // the difference in days, or the days added to a date
+            (Literal::Date(l), Literal::Date(r)) => match op {
+                Op::Sub => Ok(Int((l.days() - r.days()) as i32)),
+                _ => cmp_op(op, l, r),
+            },
+            (Literal::Date(d), Int(n)) if matches!(op, Op::Add | Op::Sub) => {
+                let n = if op == Op::Add { n as i64 } else { -(n as i64) };
+                d.add_days(n)
+                    .map(Literal::Date)
+                    .ok_or(EvalErrorKind::Overflow)
+            }
+            #[cfg(feature = "bigint")]
+            (Literal::Date(_), Big(_)) if matches!(op, Op::Add | Op::Sub) => {
+                Err(EvalErrorKind::Overflow)
+            }
+            // compared element wise
+            (l @ Tuple(_), r @ Tuple(_))
+            | (l @ Array(_), r @ Array(_))
+            | (l @ Struct(..), r @ Struct(..))
+            | (l @ Enum(..), r @ Enum(..)) => match op {
+                Op::Eq => Ok(Bool(l == r)),
+                Op::Ne => Ok(Bool(l != r)),
+                _ => Err(EvalErrorKind::TypeMismatch),
+            },
+            (Bool(l), Bool(r)) => match op {
+                Op::Eq => Ok(Bool(l == r)),
+                Op::Ne => Ok(Bool(l != r)),
+                Op::And => Ok(Bool(l && r)),
+                Op::Or => Ok(Bool(l || r)),
+                _ => Err(EvalErrorKind::TypeMismatch),
+            },
+            _ => Err(EvalErrorKind::TypeMismatch),
         }
     }
 
-    // The operator priority
-    // Mul/Div has higher priority (binds harder) than Add/Sub
+    // The operator priority, following Rust
+    // Mul/Div has higher priority (binds harder) than Add/Sub,
+    // which binds harder than comparisons, And and finally Or
     pub fn priority(&self) -> u8 {
         match self {
-            Op::Add => 0,
-            Op::Sub => 0,
-            Op::Mul => 1,
-            Op::Div => 1,
-            Op::And => 0,
+            Op::Add => 3,
+            Op::Sub => 3,
+            Op::Mul => 4,
+            Op::Div => 4,
+            Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge => 2,
+            Op::And => 1,
             Op::Or => 0,
         }
     }
 }
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::And => "&&",
+            Op::Or => "||",
+        })
+    }
+}
+
+// The built in functions, operating on strings.
+// Lengths and indices count chars (not bytes).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Builtin {
+    // `len(s)`, the number of chars in s
+    Len,
+    // `upper(s)`, s in upper case
+    Upper,
+    // `substr(s, i, n)`, the n chars of s starting at char i
+    Substr,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 3] = [Builtin::Len, Builtin::Upper, Builtin::Substr];
+
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        Some(match name {
+            "len" => Builtin::Len,
+            "upper" => Builtin::Upper,
+            "substr" => Builtin::Substr,
+            _ => return None,
+        })
+    }
+
+    // Evaluate the function applied to the arguments
+    pub fn eval(&self, args: Vec<Literal>) -> Result<Literal, EvalErrorKind> {
+        use Literal::*;
+        let len = |s: &str| i32::try_from(s.chars().count()).map_err(|_| EvalErrorKind::Overflow);
+        match (self, args.as_slice()) {
+            (Builtin::Len, [Str(s)]) => Ok(Int(len(s)?)),
+            (Builtin::Upper, [Str(s)]) => Ok(Str(s.to_uppercase())),
+            (Builtin::Substr, [Str(s), Int(i), Int(n)]) => {
+                let end = i.checked_add(*n).ok_or(EvalErrorKind::OutOfBounds)?;
+                if *i < 0 || *n < 0 || end > len(s)? {
+                    return Err(EvalErrorKind::OutOfBounds);
+                }
+                Ok(Str(s.chars().skip(*i as usize).take(*n as usize).collect()))
+            }
+            _ => Err(EvalErrorKind::TypeMismatch),
+        }
+    }
+}
+
+impl fmt::Display for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Builtin::Len => "len",
+            Builtin::Upper => "upper",
+            Builtin::Substr => "substr",
+        })
+    }
+}
+
+// The source location of an AST node.
+// Spans are ignored when comparing trees, two expressions are
+// equal if they have the same structure regardless of where
+// in the source they were parsed from.
+#[derive(Copy, Clone)]
+pub struct Span(pub proc_macro2::Span);
+
+impl Default for Span {
+    fn default() -> Self {
+        Span(proc_macro2::Span::call_site())
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Span) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Span")
+    }
+}
+
+impl From<proc_macro2::Span> for Span {
+    fn from(span: proc_macro2::Span) -> Self {
+        Span(span)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EvalErrorKind {
+    DivByZero,
+    Overflow,
+    TypeMismatch,
+    InvalidCast,
+    OutOfBounds,
+    UnboundVar,
+    NoMatch,
+    Dangling,
+}
+
+impl fmt::Display for EvalErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EvalErrorKind::DivByZero => "attempt to divide by zero",
+            EvalErrorKind::Overflow => "attempt to compute with overflow",
+            EvalErrorKind::TypeMismatch => "mismatched operand types",
+            EvalErrorKind::InvalidCast => "invalid cast",
+            EvalErrorKind::OutOfBounds => "index out of bounds",
+            EvalErrorKind::UnboundVar => "cannot find value in this scope",
+            EvalErrorKind::NoMatch => "no match arm matched",
+            EvalErrorKind::Dangling => "dereference of a dangling reference",
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EvalError {
+    pub kind: EvalErrorKind,
+    pub span: Span,
+}
+
+impl EvalError {
+    pub fn new(kind: EvalErrorKind, span: Span) -> Self {
+        EvalError { kind, span }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+// Turn an evaluation error into a syn error (e.g., for compile_error! reporting)
+impl From<EvalError> for syn::Error {
+    fn from(err: EvalError) -> Self {
+        syn::Error::new(err.span.0, err)
+    }
+}
@@ -0,0 +1,236 @@
+// A C99 backend, emitting a function computing the value of the
+// expression, using `int32_t` for Int and `bool` for Bool.
+//
+// Parentheses are emitted according to `Op::priority` (and for
+// explicit `Expr::Par`). Arithmetic is computed exactly using
+// `int64_t`, and then fitted into an `int32_t` by a helper function
+// according to the arithmetic mode. Division is guarded against
+// division by zero. On errors the flag passed by the caller is set.
+use crate::{
+    ast::{ArithMode, Expr, Literal, Op},
+    typecheck::Type,
+};
+
+fn expr(e: &Expr) -> String {
+    match e {
+        Expr::Lit(Literal::Int(i32::MIN)) => "INT32_MIN".to_string(),
+        Expr::Lit(l) => l.to_string(),
+        Expr::Par(e) => format!("({})", expr(e)),
+        // only casts between i32 and bool are supported
+        Expr::Cast(e, Type::Int, _) => match &**e {
+            Expr::BinOp(..) => format!("(int32_t)({})", expr(e)),
+            _ => format!("(int32_t){}", expr(e)),
+        },
+        Expr::Cast(e, _, _) => expr(e),
+        Expr::BinOp(Op::Div, left, right, _) => {
+            format!("lab2_div({}, {}, err)", expr(left), expr(right))
+        }
+        // the operands are literals, calls or parenthesized
+        Expr::BinOp(op @ (Op::Add | Op::Sub | Op::Mul), left, right, _) => {
+            format!(
+                "lab2_fit((int64_t){} {} {}, err)",
+                expr(left),
+                op,
+                expr(right)
+            )
+        }
+        Expr::BinOp(op, left, right, _) => format!(
+            "{} {} {}",
+            operand(left, *op, false),
+            op,
+            operand(right, *op, true)
+        ),
+        // rejected by `check`
+        _ => unreachable!(),
+    }
+}
+
+// Parenthesize operands binding weaker than the operator.
+// All operators are left associative, so a right operand with
+// the same priority needs parentheses. Comparisons have a single
+// priority level in our language but two in C, so nested
+// comparisons are always parenthesized.
+fn operand(e: &Expr, op: Op, right: bool) -> String {
+    match e {
+        Expr::BinOp(o, ..)
+            if o.priority() < op.priority()
+                || o.priority() == op.priority() && (right || is_cmp(op)) =>
+        {
+            format!("({})", expr(e))
+        }
+        _ => expr(e),
+    }
+}
+
+fn is_cmp(op: Op) -> bool {
+    matches!(op, Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge)
+}
+
+// The body of `lab2_fit` for each arithmetic mode
+fn fit(mode: ArithMode) -> &'static str {
+    match mode {
+        ArithMode::Checked => {
+            r#"    if (v < INT32_MIN || v > INT32_MAX) {
+        *err = true;
+        return 0;
+    }
+    return (int32_t)v;"#
+        }
+        // conversion of out of range values to signed types is
+        // implementation defined, so we go through uint32_t
+        ArithMode::Wrapping => {
+            r#"    uint32_t u = (uint32_t)v;
+    (void)err;
+    return u <= (uint32_t)INT32_MAX ? (int32_t)u : -(int32_t)~u - 1;"#
+        }
+        ArithMode::Saturating => {
+            r#"    (void)err;
+    return v < INT32_MIN ? INT32_MIN : v > INT32_MAX ? INT32_MAX : (int32_t)v;"#
+        }
+    }
+}
+
+// Compile the (climbed) expression into a C function `name`,
+// taking a pointer to the error flag.
+// The expression is type checked first.
+pub fn compile(e: &Expr, name: &str, mode: ArithMode) -> syn::Result<String> {
+    let ty = match super::check(e)? {
+        Type::Bool => "bool",
+        _ => "int32_t",
+    };
+    Ok(format!(
+        r#"#include <stdbool.h>
+#include <stdint.h>
+
+static inline int32_t lab2_fit(int64_t v, bool *err)
+{{
+{}
+}}
+
+static inline int32_t lab2_div(int32_t l, int32_t r, bool *err)
+{{
+    if (r == 0) {{
+        *err = true;
+        return 0;
+    }}
+    return lab2_fit((int64_t)l / r, err);
+}}
+
+{} {}(bool *err)
+{{
+    *err = false;
+    return {};
+}}
+"#,
+        fit(mode),
+        ty,
+        name,
+        expr(e)
+    ))
+}
+
+#[cfg(test)]
+fn compile_run(s: &str) -> Option<String> {
+    compile_run_with(s, ArithMode::Checked)
+}
+
+#[cfg(test)]
+fn compile_run_with(s: &str, mode: ArithMode) -> Option<String> {
+    let e = super::test_expr(s)?;
+    let mut c = compile(&e, "lab2_expr", mode).unwrap();
+    let fmt = match e.type_check().unwrap() {
+        Type::Bool => r#"printf("%s\n", v ? "true" : "false")"#,
+        _ => r#"printf("%d\n", (int)v)"#,
+    };
+    c.push_str(&format!(
+        r#"
+#include <stdio.h>
+
+int main(void)
+{{
+    bool err;
+    __typeof__(lab2_expr(&err)) v = lab2_expr(&err);
+    if (err) {{
+        printf("error\n");
+    }} else {{
+        {};
+    }}
+    return 0;
+}}
+"#,
+        fmt
+    ));
+    println!("{}", c);
+    let out = super::cc_run(&c, "c", &["-std=c99", "-Wall", "-Wextra", "-Werror"])?;
+    match e.eval_with(mode) {
+        Ok(v) => assert_eq!(out, format!("{}\n", v)),
+        Err(_) => assert_eq!(out, "error\n"),
+    }
+    Some(out)
+}
+
+#[test]
+fn c_arith() {
+    compile_run("2 - 3 * 4 - 5");
+    compile_run("2 - (3 - 4)");
+    compile_run("(8 - 7) * -6 / 4");
+    compile_run("100 / (2 - 5) * 3 - -2147483648");
+}
+
+#[test]
+fn c_logic() {
+    compile_run("1 + 2 < 4 && 2 != 3");
+    compile_run("false || (3 >= 2) == true");
+    compile_run("true && (false || true)");
+    compile_run("false && 1 / 0 == 1");
+}
+
+#[test]
+fn c_cast() {
+    compile_run("(true as i32 + 2) * (1 < 2) as i32");
+}
+
+#[test]
+fn c_div_by_zero() {
+    if let Some(out) = compile_run("1 + 1 / (2 - 2)") {
+        assert_eq!(out, "error\n");
+    }
+}
+
+// the backends implement Int as i32, which may overflow
+#[test]
+fn c_arith_modes() {
+    for mode in [
+        ArithMode::Checked,
+        ArithMode::Wrapping,
+        ArithMode::Saturating,
+    ] {
+        compile_run_with("2147483647 + 2 - 10", mode);
+        compile_run_with("-2147483648 - 1 + 1", mode);
+        compile_run_with("65536 * 65536 / 3", mode);
+        compile_run_with("-2147483648 / -1", mode);
+    }
+}
+
+#[test]
+fn c_parenthesize() {
+    let ts: proc_macro2::TokenStream = "true || false && (1 < 2) == (3 > 4)".parse().unwrap();
+    let e = crate::parse::climb(syn::parse2(ts).unwrap());
+    assert_eq!(expr(&e), "true || false && (1 < 2) == (3 > 4)");
+    let e = Expr::bin_op(
+        Op::And,
+        Expr::bin_op(Op::Or, true.into(), false.into()),
+        Expr::bin_op(
+            Op::Eq,
+            Expr::bin_op(Op::Lt, 1.into(), 2.into()),
+            true.into(),
+        ),
+    );
+    assert_eq!(expr(&e), "(true || false) && (1 < 2) == true");
+    // a cast of an operation, without `Par`
+    let cast = |e: Expr| Expr::Cast(Box::new(e), Type::Int, crate::ast::Span::default());
+    let e = cast(Expr::bin_op(Op::Lt, 1.into(), 2.into()));
+    assert_eq!(expr(&e), "(int32_t)(1 < 2)");
+    let e = Expr::bin_op(Op::Mul, e, 3.into());
+    assert_eq!(expr(&e), "lab2_fit((int64_t)(int32_t)(1 < 2) * 3, err)");
+}
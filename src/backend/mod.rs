@@ -0,0 +1,133 @@
+// Code generation backends, turning an `Expr` into source
+// code for another language or machine.
+pub mod c;
+pub mod wat;
+pub mod wat_interp;
+pub mod x86;
+
+#[cfg(feature = "bigint")]
+use crate::ast::{Literal, Op};
+use crate::{ast::Expr, typecheck::Type};
+
+// Type check the expression, and make sure it only uses the
+// types supported by the backends (i32 and bool).
+// Int is always i32 in the backends, so with the bigint feature (where
+// Int is unbounded) the arithmetic on Int is rejected.
+pub(crate) fn check(e: &Expr) -> syn::Result<Type> {
+    let ty = e.type_check()?;
+    supported(e)?;
+    Ok(ty)
+}
+
+// All types stem from literals and casts
+fn supported(e: &Expr) -> syn::Result<()> {
+    let unsupported = |ty: Type, span: proc_macro2::Span| {
+        Err(syn::Error::new(
+            span,
+            format!("type `{}` is not supported by the backend", ty),
+        ))
+    };
+    match e {
+        #[cfg(feature = "bigint")]
+        Expr::Lit(Literal::Big(i)) => Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            format!("integer literal `{}` does not fit in `i32`", i),
+        )),
+        Expr::Lit(l) => match l.get_type() {
+            Type::Int | Type::Bool => Ok(()),
+            ty => unsupported(ty, proc_macro2::Span::call_site()),
+        },
+        // Int is the only arithmetic type supported
+        #[cfg(feature = "bigint")]
+        Expr::BinOp(op @ (Op::Add | Op::Sub | Op::Mul | Op::Div), _, _, span) => {
+            Err(syn::Error::new(
+                span.0,
+                format!(
+                    "`{}` on unbounded Int (the bigint feature) is not supported by the backend",
+                    op
+                ),
+            ))
+        }
+        Expr::BinOp(_, l, r, _) => {
+            supported(l)?;
+            supported(r)
+        }
+        Expr::Par(e) => supported(e),
+        Expr::Cast(e, ty, span) => match ty {
+            Type::Int | Type::Bool => supported(e),
+            ty => unsupported(ty.clone(), span.0),
+        },
+        // the built in functions operate on strings
+        Expr::Call(f, _, span) => Err(syn::Error::new(
+            span.0,
+            format!("function `{}` is not supported by the backend", f),
+        )),
+        Expr::Tuple(_)
+        | Expr::Array(..)
+        | Expr::Repeat(..)
+        | Expr::Index(..)
+        | Expr::Field(..)
+        | Expr::Struct(..) => Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "tuples, arrays and structs are not supported by the backend",
+        )),
+        Expr::Variant(.., span) | Expr::Var(_, span) | Expr::Match(_, _, span) => Err(
+            syn::Error::new(span.0, "enums and `match` are not supported by the backend"),
+        ),
+        Expr::Block(.., span) | Expr::Ref(.., span) | Expr::Deref(_, span) => Err(syn::Error::new(
+            span.0,
+            "blocks and references are not supported by the backend",
+        )),
+    }
+}
+
+// The climbed expression, None if rejected by `check` for its Int
+// arithmetic (with the bigint feature)
+#[cfg(test)]
+pub(crate) fn test_expr(s: &str) -> Option<Expr> {
+    let e = crate::parse::climb(syn::parse_str(s).unwrap());
+    match check(&e) {
+        Err(err) if crate::BIGINT => {
+            assert!(err.to_string().contains("the bigint feature"), "{}", err);
+            None
+        }
+        _ => Some(e),
+    }
+}
+
+#[cfg(test)]
+use std::{
+    path::PathBuf,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// A fresh path in the temp directory for build artifacts.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNT.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir().join(format!("d7050e_lab2_{}_{}_{}", std::process::id(), n, name))
+}
+
+// Compile `src` (with extension `ext`) using the system C compiler
+// and run the resulting program, returning its standard output.
+// Returns None if no C compiler is available.
+#[cfg(test)]
+pub(crate) fn cc_run(src: &str, ext: &str, args: &[&str]) -> Option<String> {
+    let src_path = temp_path(&format!("prog.{}", ext));
+    let bin_path = temp_path("prog");
+    std::fs::write(&src_path, src).unwrap();
+    let status = Command::new("cc")
+        .args(args)
+        .arg("-o")
+        .arg(&bin_path)
+        .arg(&src_path)
+        .status()
+        .ok()?;
+    assert!(status.success(), "failed to compile:\n{}", src);
+    let output = Command::new(&bin_path).output().unwrap();
+    let _ = std::fs::remove_file(&src_path);
+    let _ = std::fs::remove_file(&bin_path);
+    Some(String::from_utf8(output.stdout).unwrap())
+}
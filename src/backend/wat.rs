@@ -0,0 +1,199 @@
+// A WebAssembly backend, emitting a module in the text format (WAT)
+// exporting a function `main` returning the value of the expression.
+//
+// Both Int and Bool are represented as `i32`, with Bool as 0 and 1.
+// The short circuiting `&&` and `||` are compiled into `if` blocks.
+//
+// Arithmetic is computed exactly using `i64`, and then fitted into
+// an `i32` according to the arithmetic mode. The local `$r` holds
+// the intermediate `i64` values.
+use crate::ast::{ArithMode, Expr, Literal, Op};
+use std::fmt::Write;
+
+struct Wat {
+    code: String,
+    indent: usize,
+    mode: ArithMode,
+}
+
+impl Wat {
+    fn emit(&mut self, instr: &str) {
+        writeln!(self.code, "{:width$}{}", "", instr, width = self.indent * 2).unwrap();
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Lit(Literal::Int(i)) => self.emit(&format!("i32.const {}", i)),
+            Expr::Lit(Literal::Bool(b)) => self.emit(&format!("i32.const {}", *b as i32)),
+            // only casts between i32 and bool are supported,
+            // for which the representation is unchanged
+            Expr::Par(e) | Expr::Cast(e, ..) => self.expr(e),
+            Expr::BinOp(op @ (Op::And | Op::Or), left, right, _) => {
+                self.expr(left);
+                self.emit("if (result i32)");
+                self.indent += 1;
+                match op {
+                    Op::And => self.expr(right),
+                    _ => self.emit("i32.const 1"),
+                }
+                self.indent -= 1;
+                self.emit("else");
+                self.indent += 1;
+                match op {
+                    Op::And => self.emit("i32.const 0"),
+                    _ => self.expr(right),
+                }
+                self.indent -= 1;
+                self.emit("end");
+            }
+            Expr::BinOp(op, left, right, _) => {
+                self.expr(left);
+                self.expr(right);
+                match op {
+                    Op::Add | Op::Sub | Op::Mul | Op::Div => self.arith(*op),
+                    _ => self.emit(instr(*op)),
+                }
+            }
+            // rejected by `check`
+            _ => unreachable!(),
+        }
+    }
+
+    // Extend the i32 operands, compute in i64 and fit the result
+    fn arith(&mut self, op: Op) {
+        self.emit("i64.extend_i32_s");
+        self.emit("local.set $r");
+        self.emit("i64.extend_i32_s");
+        self.emit("local.get $r");
+        self.emit(&instr(op).replace("i32", "i64"));
+        match self.mode {
+            ArithMode::Checked => {
+                self.emit("local.set $r");
+                self.emit("local.get $r");
+                self.emit("local.get $r");
+                self.emit("i32.wrap_i64");
+                self.emit("i64.extend_i32_s");
+                self.emit("i64.ne");
+                self.emit("if");
+                self.emit("  unreachable");
+                self.emit("end");
+                self.emit("local.get $r");
+            }
+            ArithMode::Wrapping => {}
+            ArithMode::Saturating => {
+                for (bound, cmp) in [(i32::MAX, "i64.gt_s"), (i32::MIN, "i64.lt_s")] {
+                    self.emit("local.set $r");
+                    self.emit(&format!("i64.const {}", bound));
+                    self.emit("local.get $r");
+                    self.emit("local.get $r");
+                    self.emit(&format!("i64.const {}", bound));
+                    self.emit(cmp);
+                    self.emit("select");
+                }
+            }
+        }
+        self.emit("i32.wrap_i64");
+    }
+}
+
+// The instruction for a (non short circuiting) operator
+fn instr(op: Op) -> &'static str {
+    match op {
+        Op::Add => "i32.add",
+        Op::Sub => "i32.sub",
+        Op::Mul => "i32.mul",
+        Op::Div => "i32.div_s",
+        Op::Eq => "i32.eq",
+        Op::Ne => "i32.ne",
+        Op::Lt => "i32.lt_s",
+        Op::Le => "i32.le_s",
+        Op::Gt => "i32.gt_s",
+        Op::Ge => "i32.ge_s",
+        Op::And | Op::Or => unreachable!(),
+    }
+}
+
+// Compile the (climbed) expression into a WAT module.
+// The expression is type checked first.
+pub fn compile(e: &Expr, mode: ArithMode) -> syn::Result<String> {
+    super::check(e)?;
+    let mut wat = Wat {
+        code: String::new(),
+        indent: 2,
+        mode,
+    };
+    wat.code
+        .push_str("(module\n  (func (export \"main\") (result i32) (local $r i64)\n");
+    wat.expr(e);
+    wat.code.push_str("  )\n)\n");
+    Ok(wat.code)
+}
+
+#[cfg(test)]
+use super::wat_interp;
+
+// None if rejected by `check` (see `super::test_expr`)
+#[cfg(test)]
+fn compile_run_with(s: &str, mode: ArithMode) -> Option<Result<i32, wat_interp::Trap>> {
+    let e = super::test_expr(s)?;
+    let wat = compile(&e, mode).unwrap();
+    println!("{}", wat);
+    let module = wat_interp::Module::parse(&wat).unwrap();
+    let res = module.run();
+    match e.eval_with(mode) {
+        Ok(Literal::Int(i)) => assert_eq!(res, Ok(i)),
+        Ok(Literal::Bool(b)) => assert_eq!(res, Ok(b as i32)),
+        Ok(_) => unreachable!(),
+        Err(_) => assert!(res.is_err()),
+    }
+    Some(res)
+}
+
+#[cfg(test)]
+fn compile_run(s: &str) -> Option<Result<i32, wat_interp::Trap>> {
+    compile_run_with(s, ArithMode::Checked)
+}
+
+#[test]
+fn wat_arith() {
+    compile_run("2 - 3 * 4 - 5");
+    compile_run("(8 - 7) * -6 / 4");
+}
+
+#[test]
+fn wat_logic() {
+    compile_run("1 + 2 < 4 && 2 != 3");
+    compile_run("false || 3 >= 2 == true");
+    assert_eq!(compile_run("true || false == false"), Some(Ok(1)));
+    compile_run("false && 1 / 0 == 1");
+    compile_run("true || 1 / 0 == 1");
+}
+
+#[test]
+fn wat_div_by_zero() {
+    if let Some(res) = compile_run("1 / (2 - 2)") {
+        assert_eq!(res, Err(wat_interp::Trap::DivByZero));
+    }
+}
+
+// the backends implement Int as i32, which may overflow
+#[test]
+fn wat_arith_modes() {
+    for mode in [
+        ArithMode::Checked,
+        ArithMode::Wrapping,
+        ArithMode::Saturating,
+    ] {
+        let _ = compile_run_with("2147483647 + 2 - 10", mode);
+        let _ = compile_run_with("-2147483648 - 1 + 1", mode);
+        let _ = compile_run_with("65536 * 65536 / 3", mode);
+        let _ = compile_run_with("-2147483648 / -1", mode);
+    }
+    // with the bigint feature Int does not overflow, rejected by `check`
+    let res = compile_run("2147483647 + 1");
+    if crate::BIGINT {
+        assert_eq!(res, None);
+    } else {
+        assert_eq!(res, Some(Err(wat_interp::Trap::Unreachable)));
+    }
+}
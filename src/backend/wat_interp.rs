@@ -0,0 +1,535 @@
+// A validator and interpreter for the subset of WAT emitted by
+// the `wat` backend. Modules are expected to have the shape
+//
+// (module
+//   (func (export "main") (result i32) (local $name i64)*
+//     instr*
+//   )
+// )
+//
+// where instructions are i32/i64 constants, arithmetic, comparisons
+// and conversions, local variable access, `select`, `unreachable`,
+// and `if (result i32)? instr* (else instr*)? end` blocks.
+use std::{collections::HashMap, fmt};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ValType {
+    I32,
+    I64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Val {
+    I32(i32),
+    I64(i64),
+}
+
+impl Val {
+    fn i32(self) -> i32 {
+        match self {
+            Val::I32(i) => i,
+            _ => unreachable!(),
+        }
+    }
+
+    fn i64(self) -> i64 {
+        match self {
+            Val::I64(i) => i,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    Const(Val),
+    // a binary instruction, e.g., `i32.add`
+    Bin(String),
+    // a conversion, e.g., `i64.extend_i32_s`
+    Convert(String),
+    LocalGet(String),
+    LocalSet(String),
+    LocalTee(String),
+    Select,
+    Unreachable,
+    If(Option<ValType>, Vec<Instr>, Vec<Instr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub locals: Vec<(String, ValType)>,
+    pub body: Vec<Instr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError(pub String);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Trap {
+    DivByZero,
+    Overflow,
+    Unreachable,
+}
+
+// S-expressions, the textual structure of WAT
+#[derive(Debug)]
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+fn err<T>(msg: &str) -> Result<T, ValidationError> {
+    Err(ValidationError(msg.to_string()))
+}
+
+fn tokenize(s: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ')' => tokens.push(c.to_string()),
+            c if c.is_whitespace() => {}
+            '"' => {
+                let mut t = String::from('"');
+                for c in chars.by_ref() {
+                    t.push(c);
+                    if c == '"' {
+                        break;
+                    }
+                }
+                tokens.push(t);
+            }
+            c => {
+                let mut t = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c == '(' || c == ')' || c.is_whitespace() {
+                        break;
+                    }
+                    t.push(c);
+                    chars.next();
+                }
+                tokens.push(t);
+            }
+        }
+    }
+    tokens
+}
+
+fn sexp(tokens: &mut std::vec::IntoIter<String>) -> Result<Sexp, ValidationError> {
+    match tokens.next() {
+        Some(t) if t == "(" => {
+            let mut list = vec![];
+            loop {
+                match tokens.as_slice().first() {
+                    Some(t) if t == ")" => {
+                        tokens.next();
+                        return Ok(Sexp::List(list));
+                    }
+                    Some(_) => list.push(sexp(tokens)?),
+                    None => return err("unbalanced parenthesis"),
+                }
+            }
+        }
+        Some(t) if t == ")" => err("unexpected `)`"),
+        Some(t) => Ok(Sexp::Atom(t)),
+        None => err("unexpected end of input"),
+    }
+}
+
+fn is_atom(s: &Sexp, a: &str) -> bool {
+    matches!(s, Sexp::Atom(t) if t == a)
+}
+
+fn val_type(s: &str) -> Option<ValType> {
+    match s {
+        "i32" => Some(ValType::I32),
+        "i64" => Some(ValType::I64),
+        _ => None,
+    }
+}
+
+// `(result i32)`
+fn result_type(s: &Sexp) -> Option<ValType> {
+    match s {
+        Sexp::List(l) if l.len() == 2 && is_atom(&l[0], "result") => match &l[1] {
+            Sexp::Atom(t) => val_type(t),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The (operand, result) types of binary instructions
+fn bin_type(instr: &str) -> Option<(ValType, ValType)> {
+    let (ty, op) = instr.split_once('.')?;
+    let ty = val_type(ty)?;
+    match op {
+        "add" | "sub" | "mul" | "div_s" => Some((ty, ty)),
+        "eq" | "ne" | "lt_s" | "le_s" | "gt_s" | "ge_s" => Some((ty, ValType::I32)),
+        _ => None,
+    }
+}
+
+// The (operand, result) types of conversions
+fn convert_type(instr: &str) -> Option<(ValType, ValType)> {
+    match instr {
+        "i64.extend_i32_s" => Some((ValType::I32, ValType::I64)),
+        "i32.wrap_i64" => Some((ValType::I64, ValType::I32)),
+        _ => None,
+    }
+}
+
+fn atom<'a, I>(body: &mut I, what: &str) -> Result<&'a str, ValidationError>
+where
+    I: Iterator<Item = &'a Sexp>,
+{
+    match body.next() {
+        Some(Sexp::Atom(t)) => Ok(t),
+        _ => Err(ValidationError(format!("expected {}", what))),
+    }
+}
+
+// Parse instructions until `else`, `end` or the end of the body,
+// returning the terminating keyword (if any)
+fn instrs<'a, I>(
+    body: &mut std::iter::Peekable<I>,
+) -> Result<(Vec<Instr>, Option<&'a str>), ValidationError>
+where
+    I: Iterator<Item = &'a Sexp>,
+{
+    let mut v = vec![];
+    while let Some(s) = body.next() {
+        let t = match s {
+            Sexp::Atom(t) => t.as_str(),
+            Sexp::List(_) => return err("unexpected folded instruction"),
+        };
+        v.push(match t {
+            "else" | "end" => return Ok((v, Some(t))),
+            "i32.const" => match atom(body, "i32 constant")?.parse() {
+                Ok(i) => Instr::Const(Val::I32(i)),
+                Err(_) => return err("invalid i32 constant"),
+            },
+            "i64.const" => match atom(body, "i64 constant")?.parse() {
+                Ok(i) => Instr::Const(Val::I64(i)),
+                Err(_) => return err("invalid i64 constant"),
+            },
+            "local.get" => Instr::LocalGet(atom(body, "local")?.to_string()),
+            "local.set" => Instr::LocalSet(atom(body, "local")?.to_string()),
+            "local.tee" => Instr::LocalTee(atom(body, "local")?.to_string()),
+            "select" => Instr::Select,
+            "unreachable" => Instr::Unreachable,
+            "if" => {
+                let ty = match body.peek() {
+                    Some(s @ Sexp::List(_)) => match result_type(s) {
+                        Some(ty) => {
+                            body.next();
+                            Some(ty)
+                        }
+                        None => return err("expected `(result <type>)` after `if`"),
+                    },
+                    _ => None,
+                };
+                let (then, t) = instrs(body)?;
+                let els = match t {
+                    Some("else") => {
+                        let (els, t) = instrs(body)?;
+                        if t != Some("end") {
+                            return err("expected `end`");
+                        }
+                        els
+                    }
+                    Some("end") => vec![],
+                    _ => return err("expected `end`"),
+                };
+                Instr::If(ty, then, els)
+            }
+            t if bin_type(t).is_some() => Instr::Bin(t.to_string()),
+            t if convert_type(t).is_some() => Instr::Convert(t.to_string()),
+            t => return Err(ValidationError(format!("unsupported instruction `{}`", t))),
+        });
+    }
+    Ok((v, None))
+}
+
+fn pop(stack: &mut Vec<ValType>, ty: ValType, instr: &str) -> Result<(), ValidationError> {
+    match stack.pop() {
+        Some(t) if t == ty => Ok(()),
+        Some(t) => Err(ValidationError(format!(
+            "`{}` expected {:?} operand, found {:?}",
+            instr, ty, t
+        ))),
+        None => Err(ValidationError(format!(
+            "`{}` is missing an operand",
+            instr
+        ))),
+    }
+}
+
+// Check that the block leaves exactly the result type(s) on the stack
+fn validate(
+    body: &[Instr],
+    locals: &HashMap<String, ValType>,
+    result: &[ValType],
+) -> Result<(), ValidationError> {
+    let mut stack = vec![];
+    let local = |name: &String| {
+        locals
+            .get(name)
+            .copied()
+            .ok_or_else(|| ValidationError(format!("unknown local `{}`", name)))
+    };
+    for i in body {
+        match i {
+            Instr::Const(Val::I32(_)) => stack.push(ValType::I32),
+            Instr::Const(Val::I64(_)) => stack.push(ValType::I64),
+            Instr::Bin(b) => {
+                let (operand, res) = bin_type(b).unwrap();
+                pop(&mut stack, operand, b)?;
+                pop(&mut stack, operand, b)?;
+                stack.push(res);
+            }
+            Instr::Convert(c) => {
+                let (operand, res) = convert_type(c).unwrap();
+                pop(&mut stack, operand, c)?;
+                stack.push(res);
+            }
+            Instr::LocalGet(l) => stack.push(local(l)?),
+            Instr::LocalSet(l) => pop(&mut stack, local(l)?, "local.set")?,
+            Instr::LocalTee(l) => {
+                pop(&mut stack, local(l)?, "local.tee")?;
+                stack.push(local(l)?);
+            }
+            Instr::Select => {
+                pop(&mut stack, ValType::I32, "select")?;
+                let ty = *stack
+                    .last()
+                    .ok_or_else(|| ValidationError("`select` is missing an operand".into()))?;
+                pop(&mut stack, ty, "select")?;
+                pop(&mut stack, ty, "select")?;
+                stack.push(ty);
+            }
+            // the rest of the block is never executed
+            Instr::Unreachable => return Ok(()),
+            Instr::If(ty, then, els) => {
+                pop(&mut stack, ValType::I32, "if")?;
+                let res: Vec<ValType> = ty.iter().copied().collect();
+                validate(then, locals, &res)?;
+                validate(els, locals, &res)?;
+                stack.extend(res);
+            }
+        }
+    }
+    if stack == result {
+        Ok(())
+    } else {
+        Err(ValidationError(format!(
+            "expected {:?} at end of block, found {:?}",
+            result, stack
+        )))
+    }
+}
+
+impl Module {
+    // Parse and validate a module
+    pub fn parse(s: &str) -> Result<Module, ValidationError> {
+        let mut tokens = tokenize(s).into_iter();
+        let module = match sexp(&mut tokens)? {
+            Sexp::List(l) if l.len() == 2 && is_atom(&l[0], "module") => l,
+            _ => return err("expected `(module (func ...))`"),
+        };
+        if tokens.next().is_some() {
+            return err("unexpected tokens after module");
+        }
+        let func = match &module[1] {
+            Sexp::List(l) if l.len() >= 3 && is_atom(&l[0], "func") => l,
+            _ => return err("expected `(func ...)`"),
+        };
+        match &func[1] {
+            Sexp::List(l)
+                if l.len() == 2 && is_atom(&l[0], "export") && is_atom(&l[1], "\"main\"") => {}
+            _ => return err("expected `(export \"main\")`"),
+        }
+        if result_type(&func[2]) != Some(ValType::I32) {
+            return err("expected `(result i32)`");
+        }
+        let mut body = func[3..].iter().peekable();
+        let mut locals = vec![];
+        while let Some(Sexp::List(l)) = body.peek() {
+            match &l[..] {
+                [Sexp::Atom(local), Sexp::Atom(name), Sexp::Atom(ty)] if local == "local" => {
+                    match val_type(ty) {
+                        Some(ty) => locals.push((name.clone(), ty)),
+                        None => return err("invalid local type"),
+                    }
+                }
+                _ => return err("expected `(local $name <type>)`"),
+            }
+            body.next();
+        }
+        let (body, t) = instrs(&mut body)?;
+        if let Some(t) = t {
+            return Err(ValidationError(format!("unexpected `{}`", t)));
+        }
+        validate(&body, &locals.iter().cloned().collect(), &[ValType::I32])?;
+        Ok(Module { locals, body })
+    }
+
+    // Run the exported function
+    pub fn run(&self) -> Result<i32, Trap> {
+        let mut stack = vec![];
+        let mut locals = self
+            .locals
+            .iter()
+            .map(|(name, ty)| {
+                let zero = match ty {
+                    ValType::I32 => Val::I32(0),
+                    ValType::I64 => Val::I64(0),
+                };
+                (name.clone(), zero)
+            })
+            .collect();
+        exec(&self.body, &mut stack, &mut locals)?;
+        Ok(stack.pop().unwrap().i32())
+    }
+}
+
+fn exec(
+    body: &[Instr],
+    stack: &mut Vec<Val>,
+    locals: &mut HashMap<String, Val>,
+) -> Result<(), Trap> {
+    for i in body {
+        match i {
+            Instr::Const(v) => stack.push(*v),
+            Instr::LocalGet(l) => stack.push(locals[l]),
+            Instr::LocalSet(l) => {
+                locals.insert(l.clone(), stack.pop().unwrap());
+            }
+            Instr::LocalTee(l) => {
+                locals.insert(l.clone(), *stack.last().unwrap());
+            }
+            Instr::Select => {
+                let c = stack.pop().unwrap().i32();
+                let r = stack.pop().unwrap();
+                let l = stack.pop().unwrap();
+                stack.push(if c != 0 { l } else { r });
+            }
+            Instr::Unreachable => return Err(Trap::Unreachable),
+            Instr::If(_, then, els) => {
+                let c = stack.pop().unwrap().i32();
+                exec(if c != 0 { then } else { els }, stack, locals)?;
+            }
+            Instr::Convert(c) => {
+                let v = stack.pop().unwrap();
+                stack.push(match c.as_str() {
+                    "i64.extend_i32_s" => Val::I64(v.i32() as i64),
+                    "i32.wrap_i64" => Val::I32(v.i64() as i32),
+                    _ => unreachable!(),
+                });
+            }
+            Instr::Bin(b) => {
+                let r = stack.pop().unwrap();
+                let l = stack.pop().unwrap();
+                stack.push(match (l, r) {
+                    (Val::I32(l), Val::I32(r)) => bin(b, l, r, Val::I32)?,
+                    (Val::I64(l), Val::I64(r)) => bin(b, l, r, Val::I64)?,
+                    _ => unreachable!(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+// Integer types supported by the interpreter
+trait Int: Copy + Ord + Default {
+    fn wrapping_add(self, r: Self) -> Self;
+    fn wrapping_sub(self, r: Self) -> Self;
+    fn wrapping_mul(self, r: Self) -> Self;
+    fn checked_div(self, r: Self) -> Option<Self>;
+}
+
+macro_rules! impl_int {
+    ($($t:ty),*) => {
+        $(impl Int for $t {
+            fn wrapping_add(self, r: Self) -> Self { <$t>::wrapping_add(self, r) }
+            fn wrapping_sub(self, r: Self) -> Self { <$t>::wrapping_sub(self, r) }
+            fn wrapping_mul(self, r: Self) -> Self { <$t>::wrapping_mul(self, r) }
+            fn checked_div(self, r: Self) -> Option<Self> { <$t>::checked_div(self, r) }
+        })*
+    };
+}
+
+impl_int!(i32, i64);
+
+fn bin<T: Int>(b: &str, l: T, r: T, val: fn(T) -> Val) -> Result<Val, Trap> {
+    let cmp = |c: bool| Val::I32(c as i32);
+    Ok(match b.split_once('.').unwrap().1 {
+        "add" => val(l.wrapping_add(r)),
+        "sub" => val(l.wrapping_sub(r)),
+        "mul" => val(l.wrapping_mul(r)),
+        "div_s" if r == T::default() => return Err(Trap::DivByZero),
+        "div_s" => val(l.checked_div(r).ok_or(Trap::Overflow)?),
+        "eq" => cmp(l == r),
+        "ne" => cmp(l != r),
+        "lt_s" => cmp(l < r),
+        "le_s" => cmp(l <= r),
+        "gt_s" => cmp(l > r),
+        "ge_s" => cmp(l >= r),
+        _ => unreachable!(),
+    })
+}
+
+#[test]
+fn wat_interp_if() {
+    let m = Module::parse(
+        r#"(module (func (export "main") (result i32)
+            i32.const 1 if (result i32) i32.const 2 else i32.const 3 end))"#,
+    )
+    .unwrap();
+    assert_eq!(m.run(), Ok(2));
+}
+
+#[test]
+fn wat_interp_locals() {
+    let m = Module::parse(
+        r#"(module (func (export "main") (result i32) (local $x i64)
+            i64.const 5 local.set $x
+            i64.const 7 local.get $x local.get $x i64.const 3 i64.gt_s select
+            i32.wrap_i64))"#,
+    )
+    .unwrap();
+    assert_eq!(m.run(), Ok(7));
+}
+
+#[test]
+fn wat_interp_unreachable() {
+    let m = Module::parse(
+        r#"(module (func (export "main") (result i32)
+            i32.const 1 if unreachable end i32.const 0))"#,
+    )
+    .unwrap();
+    assert_eq!(m.run(), Err(Trap::Unreachable));
+}
+
+#[test]
+fn wat_interp_invalid() {
+    assert!(Module::parse(r#"(module (func (export "main") (result i32) i32.add))"#).is_err());
+    assert!(Module::parse(
+        r#"(module (func (export "main") (result i32) i32.const 1 i32.const 2))"#
+    )
+    .is_err());
+    assert!(Module::parse(r#"(module (func (export "main") (result i32) i64.const 1))"#).is_err());
+    assert!(Module::parse(
+        r#"(module (func (export "main") (result i32) i32.const 1 i64.const 1 i64.add))"#
+    )
+    .is_err());
+    assert!(Module::parse(r#"(module (func (export "main") (result i32) local.get $x))"#).is_err());
+    assert!(Module::parse(r#"(module (func (export "main") (result i32) i32.const 1)"#).is_err());
+}
@@ -0,0 +1,273 @@
+// An x86-64 backend, emitting GNU `as` (AT&T syntax) assembly
+// for a `main` function printing the value of the expression.
+//
+// The generated code follows a simple stack machine discipline.
+// The value of each sub expression is computed into `%eax`,
+// left operands are saved on the stack while the right operand
+// is computed. Booleans are represented by 0 and 1.
+//
+// Arithmetic is computed exactly in 64 bits, and then fitted
+// into 32 bits according to the arithmetic mode.
+use crate::{
+    ast::{ArithMode, EvalErrorKind, Expr, Literal, Op},
+    typecheck::Type,
+};
+use std::fmt::Write;
+
+struct X86 {
+    code: String,
+    labels: usize,
+    mode: ArithMode,
+}
+
+impl X86 {
+    fn emit(&mut self, instr: &str) {
+        writeln!(self.code, "    {}", instr).unwrap();
+    }
+
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!(".L{}", self.labels)
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Lit(Literal::Int(i)) => self.emit(&format!("movl ${}, %eax", i)),
+            Expr::Lit(Literal::Bool(b)) => self.emit(&format!("movl ${}, %eax", *b as i32)),
+            // only casts between i32 and bool are supported,
+            // for which the representation is unchanged
+            Expr::Par(e) | Expr::Cast(e, ..) => self.expr(e),
+            // short circuit, the left value is the result if it decides the outcome
+            Expr::BinOp(op @ (Op::And | Op::Or), left, right, _) => {
+                let end = self.label();
+                self.expr(left);
+                self.emit("testl %eax, %eax");
+                self.emit(&format!(
+                    "{} {}",
+                    if *op == Op::And { "je" } else { "jne" },
+                    end
+                ));
+                self.expr(right);
+                writeln!(self.code, "{}:", end).unwrap();
+            }
+            Expr::BinOp(op, left, right, _) => {
+                self.expr(left);
+                self.emit("pushq %rax");
+                self.expr(right);
+                self.emit("movl %eax, %ecx");
+                self.emit("popq %rax");
+                self.op(*op);
+            }
+            // rejected by `check`
+            _ => unreachable!(),
+        }
+    }
+
+    // Apply op to %eax (left) and %ecx (right), result in %eax
+    fn op(&mut self, op: Op) {
+        let set = |cc: &str| format!("set{} %al", cc);
+        match op {
+            Op::Add | Op::Sub | Op::Mul | Op::Div => {
+                if op == Op::Div {
+                    self.emit("testl %ecx, %ecx");
+                    self.emit("je .Ldiv_zero");
+                }
+                self.emit("movslq %eax, %rax");
+                self.emit("movslq %ecx, %rcx");
+                match op {
+                    Op::Add => self.emit("addq %rcx, %rax"),
+                    Op::Sub => self.emit("subq %rcx, %rax"),
+                    Op::Mul => self.emit("imulq %rcx, %rax"),
+                    _ => {
+                        self.emit("cqto");
+                        self.emit("idivq %rcx");
+                    }
+                }
+                self.fit();
+            }
+            Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+                self.emit("cmpl %ecx, %eax");
+                self.emit(&set(match op {
+                    Op::Eq => "e",
+                    Op::Ne => "ne",
+                    Op::Lt => "l",
+                    Op::Le => "le",
+                    Op::Gt => "g",
+                    _ => "ge",
+                }));
+                self.emit("movzbl %al, %eax");
+            }
+            Op::And | Op::Or => unreachable!(),
+        }
+    }
+
+    // Fit the 64 bit result in %rax into %eax
+    fn fit(&mut self) {
+        match self.mode {
+            ArithMode::Checked => {
+                self.emit("movslq %eax, %rdx");
+                self.emit("cmpq %rax, %rdx");
+                self.emit("jne .Loverflow");
+            }
+            // the lower 32 bits are the wrapped result
+            ArithMode::Wrapping => {}
+            ArithMode::Saturating => {
+                self.emit("movq $2147483647, %rdx");
+                self.emit("cmpq %rdx, %rax");
+                self.emit("cmovg %rdx, %rax");
+                self.emit("movq $-2147483648, %rdx");
+                self.emit("cmpq %rdx, %rax");
+                self.emit("cmovl %rdx, %rax");
+            }
+        }
+    }
+
+    // Report an error and exit with the same code as a Rust panic
+    fn error(&mut self, label: &str, msg: &str) {
+        writeln!(self.code, "{}:", label).unwrap();
+        self.emit("andq $-16, %rsp");
+        self.emit(&format!("leaq {}_msg(%rip), %rdi", label));
+        self.emit("call puts@PLT");
+        self.emit("movl $101, %edi");
+        self.emit("call exit@PLT");
+        self.code.push_str("    .section .rodata\n");
+        writeln!(self.code, "{}_msg:", label).unwrap();
+        self.emit(&format!(".string {:?}", msg));
+        self.code.push_str("    .text\n");
+    }
+}
+
+// Compile the (climbed) expression into an assembly program.
+// The expression is type checked first.
+pub fn compile(e: &Expr, mode: ArithMode) -> syn::Result<String> {
+    let ty = super::check(e)?;
+    let mut x86 = X86 {
+        code: String::new(),
+        labels: 0,
+        mode,
+    };
+    x86.code.push_str("    .text\n    .globl main\nmain:\n");
+    x86.emit("pushq %rbp");
+    x86.emit("movq %rsp, %rbp");
+    x86.expr(e);
+    // the type is either Bool or Int (i32)
+    if ty == Type::Bool {
+        x86.emit("leaq .Lstr_true(%rip), %rdi");
+        x86.emit("leaq .Lstr_false(%rip), %rdx");
+        x86.emit("testl %eax, %eax");
+        x86.emit("cmovz %rdx, %rdi");
+        x86.emit("call puts@PLT");
+    } else {
+        x86.emit("movl %eax, %esi");
+        x86.emit("leaq .Lfmt_int(%rip), %rdi");
+        x86.emit("xorl %eax, %eax");
+        x86.emit("call printf@PLT");
+    }
+    x86.emit("xorl %eax, %eax");
+    x86.emit("popq %rbp");
+    x86.emit("ret");
+    x86.error(".Ldiv_zero", &EvalErrorKind::DivByZero.to_string());
+    x86.error(".Loverflow", &EvalErrorKind::Overflow.to_string());
+    x86.code.push_str(
+        r#"    .section .rodata
+.Lfmt_int:
+    .string "%d\n"
+.Lstr_true:
+    .string "true"
+.Lstr_false:
+    .string "false"
+    .section .note.GNU-stack,"",@progbits
+"#,
+    );
+    Ok(x86.code)
+}
+
+#[cfg(test)]
+fn compile_run_with(s: &str, mode: ArithMode) {
+    let e = match super::test_expr(s) {
+        Some(e) => e,
+        None => return,
+    };
+    let asm = compile(&e, mode).unwrap();
+    println!("{}", asm);
+    match super::cc_run(&asm, "s", &[]) {
+        Some(out) => match e.eval_with(mode) {
+            Ok(v) => assert_eq!(out, format!("{}\n", v)),
+            Err(err) => assert_eq!(out, format!("{}\n", err)),
+        },
+        None => println!("no C compiler found, skipping"),
+    }
+}
+
+#[cfg(test)]
+fn compile_run(s: &str) {
+    compile_run_with(s, ArithMode::Checked)
+}
+
+#[test]
+fn x86_arith() {
+    compile_run("2 - 3 * 4 - 5");
+    compile_run("(8 - 7) * -6 / 4");
+    compile_run("100 / (2 - 5) * 3");
+}
+
+#[test]
+fn x86_cmp() {
+    compile_run("1 + 2 < 4");
+    compile_run("3 >= 4 * 2");
+    compile_run("1 == 1 != (2 <= 1)");
+}
+
+#[test]
+fn x86_logic() {
+    compile_run("true && false || 3 > 2");
+    // the division is never evaluated
+    compile_run("false && 1 / 0 == 1");
+    compile_run("true || 1 / 0 == 1");
+}
+
+#[test]
+fn x86_errors() {
+    compile_run("1 / (2 - 2)");
+    compile_run("2147483647 + 1");
+}
+
+// the backends implement Int as i32, which may overflow
+#[test]
+fn x86_arith_modes() {
+    for mode in [
+        ArithMode::Checked,
+        ArithMode::Wrapping,
+        ArithMode::Saturating,
+    ] {
+        compile_run_with("2147483647 + 2 - 10", mode);
+        compile_run_with("-2147483648 - 1 + 1", mode);
+        compile_run_with("65536 * 65536 / 3", mode);
+        compile_run_with("-2147483648 / -1", mode);
+    }
+}
+
+#[test]
+fn x86_cast() {
+    compile_run("(true as i32 + 2) as i32");
+}
+
+#[test]
+fn x86_unsupported() {
+    let ts: proc_macro2::TokenStream = "1u8 as i32 + 2".parse().unwrap();
+    let e = crate::parse::climb(syn::parse2(ts).unwrap());
+    assert!(compile(&e, ArithMode::Checked).is_err());
+    #[cfg(feature = "bigint")]
+    {
+        let ts: proc_macro2::TokenStream = "2147483648 - 1".parse().unwrap();
+        let e = crate::parse::climb(syn::parse2(ts).unwrap());
+        assert!(compile(&e, ArithMode::Checked).is_err());
+    }
+}
+
+#[test]
+fn x86_type_error() {
+    let ts: proc_macro2::TokenStream = "1 + true".parse().unwrap();
+    let e = crate::parse::climb(syn::parse2(ts).unwrap());
+    assert!(compile(&e, ArithMode::Checked).is_err());
+}
@@ -0,0 +1,452 @@
+// An arbitrary precision integer, backing the Int type when the
+// `bigint` feature is enabled.
+//
+// The value is stored as a sign and a magnitude of base 2^32 limbs,
+// least significant limb first. The magnitude never has trailing
+// zero limbs, and zero is never negative, so the representation of
+// each value is unique (and the derived equality is correct).
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt,
+    ops::{Add, Mul, Neg, Sub},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BigInt {
+    neg: bool,
+    mag: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(neg: bool, mut mag: Vec<u32>) -> Self {
+        while mag.last() == Some(&0) {
+            mag.pop();
+        }
+        BigInt {
+            neg: neg && !mag.is_empty(),
+            mag,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.neg
+    }
+
+    // Division truncating towards zero (as for Rust integers),
+    // None on division by zero
+    pub fn checked_div(&self, r: &BigInt) -> Option<BigInt> {
+        if r.is_zero() {
+            return None;
+        }
+        let (q, _) = divrem_mag(&self.mag, &r.mag);
+        Some(BigInt::new(self.neg != r.neg, q))
+    }
+
+    // Remainder with the sign of the dividend, None on division by zero
+    pub fn checked_rem(&self, r: &BigInt) -> Option<BigInt> {
+        if r.is_zero() {
+            return None;
+        }
+        let (_, rem) = divrem_mag(&self.mag, &r.mag);
+        Some(BigInt::new(self.neg, rem))
+    }
+
+    // The lowest 128 bits of the two's complement representation,
+    // i.e., the value wrapped as for `as u128`
+    pub fn to_u128_wrapping(&self) -> u128 {
+        let low = self
+            .mag
+            .iter()
+            .take(4)
+            .enumerate()
+            .fold(0u128, |acc, (i, d)| acc | (*d as u128) << (32 * i));
+        if self.neg {
+            low.wrapping_neg()
+        } else {
+            low
+        }
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        if self.mag.len() > 4 {
+            return None;
+        }
+        let low = self.to_u128_wrapping() as i128;
+        // the sign of the wrapped value must agree with the sign
+        (self.is_zero() || (low < 0) == self.neg).then_some(low)
+    }
+
+    pub fn to_i32(&self) -> Option<i32> {
+        self.to_i128().and_then(|i| i32::try_from(i).ok())
+    }
+
+    // The nearest float (rounding may be off by one unit in the last place)
+    pub fn to_f64(&self) -> f64 {
+        let f = self
+            .mag
+            .iter()
+            .rev()
+            .fold(0.0, |acc, d| acc * 4294967296.0 + *d as f64);
+        if self.neg {
+            -f
+        } else {
+            f
+        }
+    }
+
+    // Parse digits in the given radix (2 to 36), `_` separators are allowed
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<BigInt, ParseBigIntError> {
+        let (neg, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let mut mag = vec![];
+        let mut digits = 0;
+        for c in s.chars().filter(|c| *c != '_') {
+            let d = c.to_digit(radix).ok_or(ParseBigIntError)?;
+            mul_add_small(&mut mag, radix, d);
+            digits += 1;
+        }
+        if digits == 0 {
+            return Err(ParseBigIntError);
+        }
+        Ok(BigInt::new(neg, mag))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ParseBigIntError;
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid digit in integer literal")
+    }
+}
+
+impl std::error::Error for ParseBigIntError {}
+
+// Parse an integer in the syntax of Rust integer literals (without suffix),
+// i.e., decimal, or hexadecimal, octal and binary with a `0x`, `0o` or `0b`
+// prefix, optionally negated
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (neg, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let (radix, digits) = match s.get(..2) {
+            Some("0x") => (16, &s[2..]),
+            Some("0o") => (8, &s[2..]),
+            Some("0b") => (2, &s[2..]),
+            _ => (10, s),
+        };
+        if digits.starts_with('-') {
+            return Err(ParseBigIntError);
+        }
+        let i = BigInt::from_str_radix(digits, radix)?;
+        Ok(if neg { -i } else { i })
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(i: i128) -> Self {
+        let u = i.unsigned_abs();
+        BigInt::new(i < 0, (0..4).map(|k| (u >> (32 * k)) as u32).collect())
+    }
+}
+
+impl From<i32> for BigInt {
+    fn from(i: i32) -> Self {
+        BigInt::from(i as i128)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.neg, self.mag)
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, r: &BigInt) -> BigInt {
+        if self.neg == r.neg {
+            return BigInt::new(self.neg, add_mag(&self.mag, &r.mag));
+        }
+        // different signs, subtract the smaller magnitude from the larger
+        match cmp_mag(&self.mag, &r.mag) {
+            Ordering::Less => BigInt::new(r.neg, sub_mag(&r.mag, &self.mag)),
+            _ => BigInt::new(self.neg, sub_mag(&self.mag, &r.mag)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, r: &BigInt) -> BigInt {
+        self + &-r.clone()
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, r: &BigInt) -> BigInt {
+        let mut mag = vec![0u32; self.mag.len() + r.mag.len()];
+        for (i, a) in self.mag.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in r.mag.iter().enumerate() {
+                let t = *a as u64 * *b as u64 + mag[i + j] as u64 + carry;
+                mag[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            mag[i + r.mag.len()] = carry as u32;
+        }
+        BigInt::new(self.neg != r.neg, mag)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return f.write_str("0");
+        }
+        // split into chunks of 9 decimal digits, least significant first
+        let mut mag = self.mag.clone();
+        let mut chunks = vec![];
+        while !mag.is_empty() {
+            chunks.push(div_small(&mut mag, 1_000_000_000));
+        }
+        if self.neg {
+            f.write_str("-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for c in chunks {
+            write!(f, "{:09}", c)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let t = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        res.push(t as u32);
+        carry = t >> 32;
+    }
+    res.push(carry as u32);
+    res
+}
+
+// a - b, requires a >= b
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, d) in a.iter().enumerate() {
+        let mut t = *d as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = (t < 0) as i64;
+        if t < 0 {
+            t += 1 << 32;
+        }
+        res.push(t as u32);
+    }
+    res
+}
+
+// mag = mag * m + a
+fn mul_add_small(mag: &mut Vec<u32>, m: u32, a: u32) {
+    let mut carry = a as u64;
+    for d in mag.iter_mut() {
+        let t = *d as u64 * m as u64 + carry;
+        *d = t as u32;
+        carry = t >> 32;
+    }
+    if carry != 0 {
+        mag.push(carry as u32);
+    }
+}
+
+// mag = mag / d, returning the remainder
+fn div_small(mag: &mut Vec<u32>, d: u32) -> u32 {
+    let mut rem = 0u64;
+    for x in mag.iter_mut().rev() {
+        let t = rem << 32 | *x as u64;
+        *x = (t / d as u64) as u32;
+        rem = t % d as u64;
+    }
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+    rem as u32
+}
+
+// Binary long division of magnitudes, returning (quotient, remainder)
+fn divrem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if b.len() == 1 {
+        let mut q = a.to_vec();
+        let r = div_small(&mut q, b[0]);
+        return (q, vec![r]);
+    }
+    let mut q = vec![0u32; a.len()];
+    let mut r: Vec<u32> = vec![];
+    for i in (0..a.len() * 32).rev() {
+        // r = r << 1 | bit i of a
+        let bit = a[i / 32] >> (i % 32) & 1;
+        mul_add_small(&mut r, 2, bit);
+        if cmp_mag(&r, b) != Ordering::Less {
+            r = sub_mag(&r, b);
+            while r.last() == Some(&0) {
+                r.pop();
+            }
+            q[i / 32] |= 1 << (i % 32);
+        }
+    }
+    (q, r)
+}
+
+#[cfg(test)]
+fn big(s: &str) -> BigInt {
+    s.parse().unwrap()
+}
+
+#[test]
+fn bigint_parse_display() {
+    for s in [
+        "0",
+        "1",
+        "-1",
+        "4294967296",
+        "-170141183460469231731687303715884105729",
+        "123456789012345678901234567890123456789012345678901234567890",
+    ] {
+        assert_eq!(big(s).to_string(), s);
+    }
+    assert_eq!(big("-0"), big("0"));
+    assert_eq!(big("1_000_000"), big("1000000"));
+    assert_eq!(big("0xff"), BigInt::from(255));
+    assert_eq!(big("-0o17"), BigInt::from(-15));
+    assert_eq!(big("0b1010_1010"), BigInt::from(170));
+    assert_eq!(
+        big("0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff").to_string(),
+        "22300745198530623141535718272648361505980415"
+    );
+    assert!("".parse::<BigInt>().is_err());
+    assert!("0x".parse::<BigInt>().is_err());
+    assert!("12a".parse::<BigInt>().is_err());
+    assert!("0b102".parse::<BigInt>().is_err());
+    assert!("--1".parse::<BigInt>().is_err());
+}
+
+#[test]
+fn bigint_arith() {
+    let a = big("340282366920938463463374607431768211456"); // 2^128
+    let b = big("-18446744073709551617"); // -(2^64 + 1)
+    assert_eq!(
+        (&a + &b).to_string(),
+        "340282366920938463444927863358058659839"
+    );
+    assert_eq!((&b + &a), (&a + &b));
+    assert_eq!(
+        (&b - &a).to_string(),
+        "-340282366920938463481821351505477763073"
+    );
+    assert_eq!(&a - &a, BigInt::default());
+    assert_eq!(
+        (&a * &b).to_string(),
+        "-6277101735386680764176071790128604879565730051895802724352"
+    );
+    assert_eq!(&(&a * &b) * &BigInt::default(), BigInt::default());
+    assert_eq!(
+        a.checked_div(&b).unwrap().to_string(),
+        "-18446744073709551615"
+    );
+    assert_eq!(a.checked_rem(&b).unwrap().to_string(), "1");
+    assert_eq!(
+        b.checked_div(&big("7")).unwrap(),
+        BigInt::from(-2635249153387078802i128)
+    );
+    assert_eq!(b.checked_rem(&big("7")).unwrap(), BigInt::from(-3));
+    assert_eq!(a.checked_div(&BigInt::default()), None);
+}
+
+// Agrees with i128 arithmetic where it does not overflow
+#[test]
+fn bigint_i128() {
+    let values = [
+        0,
+        1,
+        -1,
+        7,
+        -13,
+        i64::MAX as i128,
+        i64::MIN as i128,
+        1 << 100,
+        -(1 << 90) + 3,
+    ];
+    for l in values {
+        for r in values {
+            let (bl, br) = (BigInt::from(l), BigInt::from(r));
+            assert_eq!(bl.cmp(&br), l.cmp(&r));
+            assert_eq!((&bl + &br).to_i128(), l.checked_add(r));
+            assert_eq!((&bl - &br).to_i128(), l.checked_sub(r));
+            if let Some(m) = l.checked_mul(r) {
+                assert_eq!((&bl * &br).to_i128(), Some(m));
+            }
+            assert_eq!(
+                bl.checked_div(&br).and_then(|q| q.to_i128()),
+                l.checked_div(r)
+            );
+            assert_eq!(
+                bl.checked_rem(&br).and_then(|q| q.to_i128()),
+                l.checked_rem(r)
+            );
+        }
+    }
+    assert_eq!(BigInt::from(i128::MIN).to_i128(), Some(i128::MIN));
+    assert_eq!(
+        big("170141183460469231731687303715884105728").to_i128(),
+        None
+    );
+    assert_eq!(big("-4294967296").to_u128_wrapping() as i32, 0);
+    assert_eq!(big("2147483648").to_i32(), None);
+    assert_eq!(big("-2147483648").to_i32(), Some(i32::MIN));
+    assert_eq!(big("-0x1e").to_f64(), -30.0);
+}
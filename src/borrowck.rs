@@ -0,0 +1,481 @@
+// A simplified borrow checker, run after type checking.
+//
+// A borrow `&x` or `&mut x` creates a loan of `x`. A loan is live as long
+// as a value holding it may still be used, i.e., until the last use of the
+// variables holding it, or until the end of the statement for temporaries.
+// The checker rejects
+// - a `&mut` borrow of `x` while another loan of `x` is live,
+// - a `&` borrow of `x` while a `&mut` loan of `x` is live,
+// - assigning to `x` while a loan of `x` is live,
+// - using `x` while a `&mut` loan of `x` is live,
+// - loans outliving the variable borrowed, e.g., a block returning `&x`
+//   for a variable `x` declared in the block.
+// Errors point to the offending borrow (or use) and the conflicting borrow.
+//
+// The expression is checked in two passes, the first pass records
+// the last use of each variable, the second pass checks the loans.
+use crate::ast::{Expr, Program, Span, Stmt};
+
+struct Var {
+    name: String,
+    mutable: bool,
+    // the span of the declaration
+    span: Span,
+    // the loans the value may hold
+    loans: Vec<usize>,
+}
+
+struct Loan {
+    // the variable borrowed
+    var: usize,
+    mutable: bool,
+    span: Span,
+}
+
+#[derive(Default)]
+struct Checker {
+    // all variables declared, by id
+    vars: Vec<Var>,
+    // the ids of the variables in scope
+    scope: Vec<usize>,
+    loans: Vec<Loan>,
+    // the loans held by temporaries of the current statement
+    temps: Vec<usize>,
+    // increased for each expression visited
+    point: usize,
+    // the point of the last use of each variable, by id
+    last_use: Vec<usize>,
+    // the first pass only records the last uses
+    recording: bool,
+}
+
+// An error at span, with a note at the conflicting span
+fn conflict(span: Span, msg: String, other: Span, note: String) -> syn::Error {
+    let mut err = syn::Error::new(span.0, msg);
+    err.combine(syn::Error::new(other.0, note));
+    err
+}
+
+impl Checker {
+    fn lookup(&self, x: &str) -> Option<usize> {
+        self.scope
+            .iter()
+            .rev()
+            .copied()
+            .find(|v| self.vars[*v].name == x)
+    }
+
+    fn declare(&mut self, name: String, mutable: bool, span: Span, loans: Vec<usize>) {
+        self.scope.push(self.vars.len());
+        self.vars.push(Var {
+            name,
+            mutable,
+            span,
+            loans,
+        });
+        self.last_use
+            .resize(self.vars.len().max(self.last_use.len()), 0);
+    }
+
+    // The live loans of the variable
+    fn live(&self, var: usize) -> Vec<&Loan> {
+        let held = |l: usize| {
+            self.temps.contains(&l)
+                || self
+                    .scope
+                    .iter()
+                    .any(|v| self.vars[*v].loans.contains(&l) && self.last_use[*v] > self.point)
+        };
+        self.loans
+            .iter()
+            .enumerate()
+            .filter(|(l, loan)| loan.var == var && held(*l))
+            .map(|(_, loan)| loan)
+            .collect()
+    }
+
+    // The loans held by the value of the expression
+    fn expr(&mut self, e: &Expr) -> syn::Result<Vec<usize>> {
+        self.point += 1;
+        match e {
+            // the results of operators, casts and calls hold no references
+            Expr::Lit(_) => Ok(vec![]),
+            Expr::BinOp(_, l, r, _) | Expr::Index(l, r, _) => {
+                let loans = self.expr(l)?;
+                self.expr(r)?;
+                Ok(if let Expr::Index(..) = e {
+                    loans
+                } else {
+                    vec![]
+                })
+            }
+            Expr::Cast(e, ..) => {
+                self.expr(e)?;
+                Ok(vec![])
+            }
+            Expr::Call(_, args, _) => {
+                self.all(args)?;
+                Ok(vec![])
+            }
+            Expr::Par(e) | Expr::Repeat(e, _) | Expr::Field(e, ..) => self.expr(e),
+            Expr::Tuple(es) | Expr::Array(es, _) | Expr::Variant(_, _, es, _) => self.all(es),
+            Expr::Struct(_, fields, _) => {
+                let mut loans = vec![];
+                for (_, e) in fields {
+                    loans.extend(self.expr(e)?);
+                }
+                Ok(loans)
+            }
+            Expr::Var(x, span) => self.use_var(x, *span),
+            // the bindings of an arm hold the loans of the scrutinee
+            Expr::Match(e, arms, _) => {
+                let scrutinee = self.expr(e)?;
+                let mut loans = vec![];
+                for arm in arms {
+                    let len = self.scope.len();
+                    for x in arm.pat.bindings() {
+                        self.declare(x, false, arm.span, scrutinee.clone());
+                    }
+                    let l = self.expr(&arm.body)?;
+                    self.exit(len, &l, arm.span)?;
+                    loans.extend(l);
+                }
+                Ok(loans)
+            }
+            Expr::Block(stmts, e, span) => {
+                let len = self.scope.len();
+                for stmt in stmts {
+                    let temps = self.temps.len();
+                    match stmt {
+                        Stmt::Let(x, mutable, e, span) => {
+                            let loans = self.expr(e)?;
+                            self.declare(x.clone(), *mutable, *span, loans);
+                        }
+                        Stmt::Assign(place, e, span) => {
+                            let loans = self.expr(e)?;
+                            self.assign(place, loans, *span)?;
+                        }
+                        Stmt::Expr(e) => {
+                            self.expr(e)?;
+                        }
+                    }
+                    self.temps.truncate(temps);
+                }
+                let loans = self.expr(e)?;
+                self.exit(len, &loans, *span)?;
+                Ok(loans)
+            }
+            Expr::Ref(mutable, e, span) => self.borrow(e, *mutable, *span),
+            // the value referred to holds the loans of the variables referred to
+            Expr::Deref(e, _) => Ok(self
+                .expr(e)?
+                .into_iter()
+                .flat_map(|l| self.vars[self.loans[l].var].loans.clone())
+                .collect()),
+        }
+    }
+
+    fn all(&mut self, es: &[Expr]) -> syn::Result<Vec<usize>> {
+        let mut loans = vec![];
+        for e in es {
+            loans.extend(self.expr(e)?);
+        }
+        Ok(loans)
+    }
+
+    // Reading `x` is not allowed while `x` is mutably borrowed
+    fn use_var(&mut self, x: &str, span: Span) -> syn::Result<Vec<usize>> {
+        let v = match self.lookup(x) {
+            Some(v) => v,
+            // rejected by the type checker
+            None => return Ok(vec![]),
+        };
+        if self.recording {
+            self.last_use[v] = self.point;
+        } else if let Some(loan) = self.live(v).into_iter().find(|loan| loan.mutable) {
+            return Err(conflict(
+                span,
+                format!("cannot use `{}` because it was mutably borrowed", x),
+                loan.span,
+                format!("`{}` is borrowed here", x),
+            ));
+        }
+        Ok(self.vars[v].loans.clone())
+    }
+
+    fn borrow(&mut self, place: &Expr, mutable: bool, span: Span) -> syn::Result<Vec<usize>> {
+        let (x, v) = match place {
+            Expr::Par(e) => return self.borrow(e, mutable, span),
+            Expr::Var(x, _) => match self.lookup(x) {
+                Some(v) => (x, v),
+                None => return Ok(vec![]),
+            },
+            // a reborrow `&*r` holds the loans of `r`
+            Expr::Deref(r, _) => return self.expr(r),
+            // rejected by the type checker
+            _ => return self.expr(place),
+        };
+        if !self.recording {
+            let var = &self.vars[v];
+            if mutable && !var.mutable {
+                return Err(conflict(
+                    span,
+                    format!(
+                        "cannot borrow `{}` as mutable, as it is not declared as mutable",
+                        x
+                    ),
+                    var.span,
+                    format!("consider changing this to be mutable: `mut {}`", x),
+                ));
+            }
+            for loan in self.live(v) {
+                let (msg, note) = match (mutable, loan.mutable) {
+                    (true, true) => (
+                        "cannot borrow `{}` as mutable more than once at a time",
+                        "first mutable borrow occurs here",
+                    ),
+                    (true, false) => (
+                        "cannot borrow `{}` as mutable because it is also borrowed as immutable",
+                        "immutable borrow occurs here",
+                    ),
+                    (false, true) => (
+                        "cannot borrow `{}` as immutable because it is also borrowed as mutable",
+                        "mutable borrow occurs here",
+                    ),
+                    (false, false) => continue,
+                };
+                return Err(conflict(
+                    span,
+                    msg.replace("{}", x),
+                    loan.span,
+                    note.to_string(),
+                ));
+            }
+        }
+        self.loans.push(Loan {
+            var: v,
+            mutable,
+            span,
+        });
+        self.temps.push(self.loans.len() - 1);
+        Ok(vec![self.loans.len() - 1])
+    }
+
+    // Assigning to `x` requires `x` to be mutable and not borrowed,
+    // the value assigned replaces the loans held by `x`
+    fn assign(&mut self, place: &Expr, loans: Vec<usize>, span: Span) -> syn::Result<()> {
+        let (x, v) = match place {
+            Expr::Par(e) => return self.assign(e, loans, span),
+            Expr::Var(x, _) => match self.lookup(x) {
+                Some(v) => (x, v),
+                None => return Ok(()),
+            },
+            // `*r = e`, the variables referred to may hold the loans
+            Expr::Deref(r, _) => {
+                for l in self.expr(r)? {
+                    let v = self.loans[l].var;
+                    self.vars[v].loans.extend(loans.iter().copied());
+                }
+                return Ok(());
+            }
+            // rejected by the type checker
+            _ => return Ok(()),
+        };
+        if !self.recording {
+            let var = &self.vars[v];
+            if !var.mutable {
+                return Err(conflict(
+                    span,
+                    format!("cannot assign twice to immutable variable `{}`", x),
+                    var.span,
+                    format!("first assignment to `{}`", x),
+                ));
+            }
+            if let Some(loan) = self.live(v).first() {
+                return Err(conflict(
+                    span,
+                    format!("cannot assign to `{}` because it is borrowed", x),
+                    loan.span,
+                    format!("`{}` is borrowed here", x),
+                ));
+            }
+        }
+        self.vars[v].loans = loans;
+        Ok(())
+    }
+
+    // The variables declared since `len` go out of scope at `span`,
+    // neither the result nor a live loan may refer to them
+    fn exit(&mut self, len: usize, result: &[usize], span: Span) -> syn::Result<()> {
+        if !self.recording {
+            let local = &self.scope[len..];
+            for (l, loan) in self.loans.iter().enumerate() {
+                if !local.contains(&loan.var) {
+                    continue;
+                }
+                let x = &self.vars[loan.var].name;
+                let msg = if result.contains(&l) {
+                    format!("cannot return reference to local variable `{}`", x)
+                } else if self.scope[..len]
+                    .iter()
+                    .any(|v| self.vars[*v].loans.contains(&l) && self.last_use[*v] > self.point)
+                {
+                    format!("`{}` does not live long enough", x)
+                } else {
+                    continue;
+                };
+                return Err(conflict(
+                    loan.span,
+                    msg,
+                    span,
+                    format!("`{}` dropped here while still borrowed", x),
+                ));
+            }
+        }
+        self.scope.truncate(len);
+        Ok(())
+    }
+}
+
+impl Expr {
+    // Check the borrows of a type checked expression
+    pub fn borrow_check(&self) -> syn::Result<()> {
+        let mut first = Checker {
+            recording: true,
+            ..Default::default()
+        };
+        first.expr(self)?;
+        let mut checker = Checker {
+            last_use: first.last_use,
+            ..Default::default()
+        };
+        checker.expr(self).map(|_| ())
+    }
+}
+
+impl Program {
+    pub fn borrow_check(&self) -> syn::Result<()> {
+        self.expr.borrow_check()
+    }
+}
+
+#[cfg(test)]
+fn check(s: &str) -> syn::Result<()> {
+    let ts: proc_macro2::TokenStream = s.parse().unwrap();
+    let p: Program = syn::parse2(ts).unwrap();
+    p.type_check().unwrap();
+    p.borrow_check()
+}
+
+// The messages and start columns of the errors
+#[cfg(test)]
+fn errors(s: &str) -> Vec<(String, usize)> {
+    check(s)
+        .unwrap_err()
+        .into_iter()
+        .map(|e| (e.to_string(), e.span().start().column))
+        .collect()
+}
+
+#[test]
+fn borrowck_ok() {
+    assert!(check("{ let mut x = 1; let r = &mut x; *r = *r + 1; x }").is_ok());
+    // the first loan is dead after the last use of `r`
+    assert!(check("{ let mut x = 1; let r = &mut x; *r = 2; let s = &mut x; *s }").is_ok());
+    assert!(check("{ let x = 1; let r = &x; let s = &x; *r + *s + x }").is_ok());
+    assert!(check("{ let mut x = 1; x = 2; let y = { let r = &x; *r }; x = y; x }").is_ok());
+    assert!(
+        check("{ let mut x = (1, 2); let r = &mut x; let s = &mut *r; *s = (3, 4); x.0 }").is_ok()
+    );
+}
+
+#[test]
+fn borrowck_conflicts() {
+    let src = "{ let mut x = 1; let a = &mut x; let b = &mut x; *a + *b }";
+    assert_eq!(
+        errors(src),
+        vec![
+            (
+                "cannot borrow `x` as mutable more than once at a time".to_string(),
+                src.rfind("&mut x").unwrap()
+            ),
+            (
+                "first mutable borrow occurs here".to_string(),
+                src.find("&mut x").unwrap()
+            ),
+        ]
+    );
+    // temporaries are live until the end of the statement
+    let err = errors("{ let mut x = 1; (&mut x, &x); 0 }");
+    assert_eq!(
+        err[0].0,
+        "cannot borrow `x` as immutable because it is also borrowed as mutable"
+    );
+    let src = "{ let mut x = 1; let r = &x; x = 2; *r }";
+    assert_eq!(
+        errors(src),
+        vec![
+            (
+                "cannot assign to `x` because it is borrowed".to_string(),
+                src.find("= 2").unwrap()
+            ),
+            ("`x` is borrowed here".to_string(), src.find("&x").unwrap()),
+        ]
+    );
+    let err = errors("{ let mut x = 1; let r = &x; let m = &mut x; *r }");
+    assert_eq!(
+        err[0].0,
+        "cannot borrow `x` as mutable because it is also borrowed as immutable"
+    );
+    let err = errors("{ let mut x = 1; let r = &mut x; let y = x; *r }");
+    assert_eq!(err[0].0, "cannot use `x` because it was mutably borrowed");
+    // the loan flows from `r` to `s`
+    let err = errors("{ let mut x = 1; let r = &mut x; let s = r; x = 2; *s }");
+    assert_eq!(err[0].0, "cannot assign to `x` because it is borrowed");
+}
+
+#[test]
+fn borrowck_mutability() {
+    let src = "{ let x = 1; x = 2; x }";
+    assert_eq!(
+        errors(src),
+        vec![
+            (
+                "cannot assign twice to immutable variable `x`".to_string(),
+                src.find("= 2").unwrap()
+            ),
+            (
+                "first assignment to `x`".to_string(),
+                src.find("x =").unwrap()
+            ),
+        ]
+    );
+    let err = errors("{ let x = 1; let r = &mut x; *r }");
+    assert_eq!(
+        err[0].0,
+        "cannot borrow `x` as mutable, as it is not declared as mutable"
+    );
+}
+
+#[test]
+fn borrowck_locals() {
+    let src = "{ let y = { let x = 1; &x }; *y }";
+    assert_eq!(
+        errors(src),
+        vec![
+            (
+                "cannot return reference to local variable `x`".to_string(),
+                src.find("&x").unwrap()
+            ),
+            (
+                "`x` dropped here while still borrowed".to_string(),
+                src.find("{ let x").unwrap()
+            ),
+        ]
+    );
+    let err = errors("{ let a = 1; let mut r = &a; { let x = 2; r = &x; } *r }");
+    assert_eq!(err[0].0, "`x` does not live long enough");
+    let err = errors("match 1 { x => &x }");
+    assert_eq!(err[0].0, "cannot return reference to local variable `x`");
+    assert!(check("{ let a = 1; let mut r = &a; { let x = 2; r = &x; } 0 }").is_ok());
+}
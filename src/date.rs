@@ -0,0 +1,226 @@
+// Calendar dates, the values of `d"2024-02-29"` literals.
+//
+// A regex can check the shape `YYYY-MM-DD`, but not the calendar
+// (see `test7` in `examples/ex1_regex.rs`). Here the day is checked
+// against the length of the month, following the Gregorian leap year
+// rules (every 4th year, except centuries not divisible by 400).
+//
+// Dates are counted in days since 1970-01-01, so that adding days and
+// subtracting dates is plain integer arithmetic. Years range 1 to 9999.
+use std::{fmt, str::FromStr};
+
+// Ordered chronologically, by year, month and day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateError {
+    // not `YYYY-MM-DD`
+    Format,
+    Year(i32),
+    Month(u32),
+    // the day does not exist in the year and month
+    Day(i32, u32, u32),
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateError::Format => f.write_str("invalid date, expected `YYYY-MM-DD`"),
+            DateError::Year(y) => write!(f, "invalid year `{}`, expected 1 to 9999", y),
+            DateError::Month(m) => write!(f, "invalid month `{}`, expected 1 to 12", m),
+            DateError::Day(y, m, d) => write!(
+                f,
+                "invalid day `{}`, {} {} has {} days",
+                d,
+                MONTHS[*m as usize - 1],
+                y,
+                days_in_month(*y, *m)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DateError {}
+
+pub fn is_leap(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Result<Date, DateError> {
+        if !(1..=9999).contains(&year) {
+            return Err(DateError::Year(year));
+        }
+        if !(1..=12).contains(&month) {
+            return Err(DateError::Month(month));
+        }
+        if day < 1 || day > days_in_month(year, month) {
+            return Err(DateError::Day(year, month, day));
+        }
+        Ok(Date { year, month, day })
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    // The days since 1970-01-01, negative before
+    pub fn days(&self) -> i64 {
+        // years starting in March, so the leap day is the last day
+        let y = self.year as i64 - (self.month <= 2) as i64;
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let doy = (153 * ((self.month as i64 + 9) % 12) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    // The date the days after 1970-01-01, None outside the years 1 to 9999
+    pub fn from_days(days: i64) -> Option<Date> {
+        let z = days.checked_add(719_468)?;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + (month <= 2) as i64;
+        if !(1..=9999).contains(&year) {
+            return None;
+        }
+        Some(Date {
+            year: year as i32,
+            month,
+            day,
+        })
+    }
+
+    // The date n days later (earlier if negative)
+    pub fn add_days(&self, n: i64) -> Option<Date> {
+        Date::from_days(self.days().checked_add(n)?)
+    }
+}
+
+impl FromStr for Date {
+    type Err = DateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let b = s.as_bytes();
+        let shape = b.len() == 10
+            && b[4] == b'-'
+            && b[7] == b'-'
+            && b.iter()
+                .enumerate()
+                .all(|(i, c)| i == 4 || i == 7 || c.is_ascii_digit());
+        if !shape {
+            return Err(DateError::Format);
+        }
+        // the digits parse
+        Date::new(
+            s[..4].parse().unwrap(),
+            s[5..7].parse().unwrap(),
+            s[8..].parse().unwrap(),
+        )
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[test]
+fn date_parse() {
+    let d = |s: &str| s.parse::<Date>();
+    assert_eq!(d("2024-02-29").unwrap().to_string(), "2024-02-29");
+    assert_eq!(d("2000-02-29").unwrap().day(), 29);
+    assert_eq!(
+        d("2022-02-29").unwrap_err().to_string(),
+        "invalid day `29`, February 2022 has 28 days"
+    );
+    assert_eq!(d("1900-02-29"), Err(DateError::Day(1900, 2, 29)));
+    assert_eq!(d("2024-04-31"), Err(DateError::Day(2024, 4, 31)));
+    assert_eq!(d("2024-13-01"), Err(DateError::Month(13)));
+    assert_eq!(d("2024-00-10"), Err(DateError::Month(0)));
+    assert_eq!(d("0000-01-01"), Err(DateError::Year(0)));
+    for s in &[
+        "2024-2-29",
+        "2024/02/29",
+        "24-02-29",
+        "2024-02-29 ",
+        "+024-02-29",
+    ] {
+        assert_eq!(d(s), Err(DateError::Format), "{}", s);
+    }
+}
+
+#[test]
+fn date_days() {
+    let d = |s: &str| s.parse::<Date>().unwrap();
+    assert_eq!(d("1970-01-01").days(), 0);
+    assert_eq!(d("2024-03-01").days() - d("2024-02-28").days(), 2);
+    assert_eq!(d("2023-03-01").days() - d("2023-02-28").days(), 1);
+    assert_eq!(d("2000-01-01").days(), 10_957);
+    assert_eq!(d("1969-12-31").days(), -1);
+    assert_eq!(d("2024-01-31").add_days(30), Some(d("2024-03-01")));
+    assert_eq!(d("9999-12-31").add_days(1), None);
+    assert_eq!(d("0001-01-01").add_days(-1), None);
+    // day by day, against the month lengths, over more than
+    // a 400 year cycle
+    let mut date = d("1899-12-31");
+    let mut days = date.days();
+    while date.year() < 2301 {
+        let next = if date.day() < days_in_month(date.year(), date.month()) {
+            Date::new(date.year(), date.month(), date.day() + 1)
+        } else if date.month() < 12 {
+            Date::new(date.year(), date.month() + 1, 1)
+        } else {
+            Date::new(date.year() + 1, 1, 1)
+        }
+        .unwrap();
+        days += 1;
+        assert_eq!(next.days(), days, "{}", next);
+        assert_eq!(Date::from_days(days), Some(next));
+        assert!(date < next);
+        date = next;
+    }
+}
@@ -0,0 +1,474 @@
+// Big-step derivation trees.
+//
+// `Expr::derive` evaluates the expression (with the same semantics as
+// `Expr::eval_with`) and records the derivation, each node concludes
+// `<e, σ> → <v, σ'>` from the evaluation of the sub expressions, e.g.,
+//
+//   <2, σ> → <2, σ>   <3, σ> → <3, σ>
+//   ---------------------------------
+//        <2 - 3, σ> → <-1, σ>
+//
+// Statements conclude `<s, σ> → σ'`. The store σ is primed for each
+// change, i.e., when a variable is added, assigned or goes out of scope.
+//
+// `Derivation::to_latex` renders the tree as nested `\frac{premises}{conclusion}`,
+// in the notation of the gitlab math example in the README.
+use crate::ast::{ArithMode, EvalError, EvalErrorKind, Expr, Literal, Member, Op, Span, Stmt};
+use std::fmt;
+
+// A derivation tree, the conclusion follows from the premises
+#[derive(Debug, Clone, PartialEq)]
+pub struct Derivation<J> {
+    pub conclusion: J,
+    pub premises: Vec<Derivation<J>>,
+}
+
+// A judgement that can be typeset, or printed as plain text
+pub trait Judgement {
+    fn latex(&self) -> String;
+    fn unicode(&self) -> String;
+}
+
+impl<J: Judgement> Derivation<J> {
+    // Axioms (without premises) are rendered as the conclusion only
+    pub fn to_latex(&self) -> String {
+        if self.premises.is_empty() {
+            return self.conclusion.latex();
+        }
+        let premises: Vec<_> = self.premises.iter().map(|p| p.to_latex()).collect();
+        format!(
+            "\\frac{{{}}}{{{}}}",
+            premises.join(" \\quad "),
+            self.conclusion.latex()
+        )
+    }
+
+    // Premises side by side above a line, and the conclusion below it
+    pub fn to_unicode(&self) -> String {
+        self.lines().join("\n")
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let conclusion = self.conclusion.unicode();
+        if self.premises.is_empty() {
+            return vec![conclusion];
+        }
+        let blocks: Vec<_> = self.premises.iter().map(|p| p.lines()).collect();
+        let widths: Vec<_> = blocks
+            .iter()
+            .map(|b| b.iter().map(|l| width(l)).max().unwrap_or(0))
+            .collect();
+        let height = blocks.iter().map(|b| b.len()).max().unwrap_or(0);
+        let above = widths.iter().sum::<usize>() + 3 * (blocks.len() - 1);
+        let total = above.max(width(&conclusion));
+        let indent = |w| " ".repeat((total - w) / 2);
+        // premises are aligned at the bottom
+        let mut lines: Vec<String> = (0..height)
+            .map(|row| {
+                let cells: Vec<_> = blocks
+                    .iter()
+                    .zip(&widths)
+                    .map(|(b, w)| match (row + b.len()).checked_sub(height) {
+                        Some(i) => format!("{}{}", b[i], " ".repeat(w - width(&b[i]))),
+                        None => " ".repeat(*w),
+                    })
+                    .collect();
+                format!("{}{}", indent(above), cells.join("   "))
+                    .trim_end()
+                    .to_string()
+            })
+            .collect();
+        lines.push("─".repeat(total));
+        lines.push(format!("{}{}", indent(width(&conclusion)), conclusion));
+        lines
+    }
+}
+
+fn width(s: &str) -> usize {
+    s.chars().count()
+}
+
+// An expression or a statement
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Expr(Expr),
+    Stmt(Stmt),
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Expr(e) => write!(f, "{}", e),
+            Term::Stmt(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+// `<term, σ> → <value, σ'>`, or `<term, σ> → σ'` for statements,
+// the stores are given by the number of primes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eval {
+    pub term: Term,
+    pub value: Option<Literal>,
+    pub from: usize,
+    pub to: usize,
+}
+
+// Source code in math mode
+pub(crate) fn texttt(s: &str) -> String {
+    format!("\\texttt{{{}}}", escape(s))
+}
+
+// Escape the LaTeX special characters
+pub(crate) fn escape(s: &str) -> String {
+    let mut t = String::new();
+    for c in s.chars() {
+        match c {
+            '{' | '}' | '&' | '_' | '#' | '%' | '$' => {
+                t.push('\\');
+                t.push(c);
+            }
+            '^' => t.push_str("\\^{}"),
+            '~' => t.push_str("\\~{}"),
+            '\\' => t.push_str("\\textbackslash{}"),
+            c => t.push(c),
+        }
+    }
+    t
+}
+
+fn sigma(n: usize) -> String {
+    format!("\\sigma{}", "'".repeat(n))
+}
+
+fn sigma_unicode(n: usize) -> String {
+    format!("σ{}", "'".repeat(n))
+}
+
+impl Judgement for Eval {
+    fn latex(&self) -> String {
+        let lhs = format!("<{}, {}>", texttt(&self.term.to_string()), sigma(self.from));
+        match &self.value {
+            Some(v) => format!(
+                "{} \\rightarrow <{}, {}>",
+                lhs,
+                texttt(&v.to_string()),
+                sigma(self.to)
+            ),
+            None => format!("{} \\rightarrow {}", lhs, sigma(self.to)),
+        }
+    }
+
+    fn unicode(&self) -> String {
+        let lhs = format!("<{}, {}>", self.term, sigma_unicode(self.from));
+        match &self.value {
+            Some(v) => format!("{} → <{}, {}>", lhs, v, sigma_unicode(self.to)),
+            None => format!("{} → {}", lhs, sigma_unicode(self.to)),
+        }
+    }
+}
+
+impl Derivation<Eval> {
+    // The value concluded, None for statements
+    pub fn value(&self) -> Option<&Literal> {
+        self.conclusion.value.as_ref()
+    }
+}
+
+struct Deriver {
+    mode: ArithMode,
+    vars: Vec<(String, Literal)>,
+    // the number of changes to the store
+    store: usize,
+}
+
+impl Deriver {
+    fn expr(&mut self, e: &Expr) -> Result<Derivation<Eval>, EvalError> {
+        let from = self.store;
+        let mut premises = vec![];
+        let err = |kind, span: &Span| Err(EvalError::new(kind, *span));
+        // evaluate a premise
+        macro_rules! premise {
+            ($e:expr) => {{
+                let d = self.expr($e)?;
+                let v = d.value().unwrap().clone();
+                premises.push(d);
+                v
+            }};
+        }
+        let value = match e {
+            Expr::Lit(l) => l.clone(),
+            // && and || are short circuiting
+            Expr::BinOp(op, l, r, span) => {
+                let l = premise!(l);
+                match (op, &l) {
+                    (Op::And, Literal::Bool(false)) | (Op::Or, Literal::Bool(true)) => l,
+                    _ => {
+                        let r = premise!(r);
+                        match op.eval_with(self.mode, l, r) {
+                            Ok(v) => v,
+                            Err(kind) => return err(kind, span),
+                        }
+                    }
+                }
+            }
+            Expr::Par(e) => premise!(e),
+            Expr::Cast(e, ty, span) => match premise!(e).cast(ty) {
+                Some(v) => v,
+                None => return err(EvalErrorKind::InvalidCast, span),
+            },
+            Expr::Call(f, args, span) => {
+                let args = args
+                    .iter()
+                    .map(|a| Ok(premise!(a)))
+                    .collect::<Result<_, _>>()?;
+                match f.eval(args) {
+                    Ok(v) => v,
+                    Err(kind) => return err(kind, span),
+                }
+            }
+            Expr::Tuple(es) => Literal::Tuple(
+                es.iter()
+                    .map(|e| Ok(premise!(e)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Array(es, _) => Literal::Array(
+                es.iter()
+                    .map(|e| Ok(premise!(e)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Repeat(e, n) => Literal::Array(vec![premise!(e); *n]),
+            Expr::Index(a, i, span) => {
+                let a = premise!(a);
+                let i = premise!(i);
+                match (a, i.to_index()) {
+                    (Literal::Array(a), Some(i)) if i < a.len() => a[i].clone(),
+                    _ => return err(EvalErrorKind::OutOfBounds, span),
+                }
+            }
+            Expr::Field(t, m, span) => match (premise!(t), m) {
+                (Literal::Tuple(t), Member::Index(n)) if *n < t.len() => t[*n].clone(),
+                (Literal::Struct(_, fields), Member::Named(name)) => {
+                    match fields.into_iter().find(|(f, _)| f == name) {
+                        Some((_, v)) => v,
+                        None => return err(EvalErrorKind::TypeMismatch, span),
+                    }
+                }
+                _ => return err(EvalErrorKind::TypeMismatch, span),
+            },
+            Expr::Struct(name, fields, _) => {
+                let mut fields = fields
+                    .iter()
+                    .map(|(f, e)| Ok((f.clone(), premise!(e))))
+                    .collect::<Result<Vec<_>, _>>()?;
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Literal::Struct(name.clone(), fields)
+            }
+            Expr::Variant(en, v, args, _) => Literal::Enum(
+                en.clone(),
+                v.clone(),
+                args.iter()
+                    .map(|a| Ok(premise!(a)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Var(x, span) => match self.vars.iter().rev().find(|(y, _)| x == y) {
+                Some((_, v)) => v.clone(),
+                None => return err(EvalErrorKind::UnboundVar, span),
+            },
+            // the bindings of the first matching arm are added to the store
+            Expr::Match(s, arms, span) => {
+                let v = premise!(s);
+                let len = self.vars.len();
+                let arm = arms.iter().find(|arm| {
+                    self.vars.truncate(len);
+                    arm.pat.matches(&v, &mut self.vars)
+                });
+                match arm {
+                    Some(arm) => {
+                        self.changed(len);
+                        let v = premise!(&arm.body);
+                        self.exit(len);
+                        v
+                    }
+                    None => return err(EvalErrorKind::NoMatch, span),
+                }
+            }
+            Expr::Block(stmts, e, _) => {
+                let len = self.vars.len();
+                for stmt in stmts {
+                    premises.push(self.stmt(stmt)?);
+                }
+                let v = premise!(e);
+                self.exit(len);
+                v
+            }
+            Expr::Ref(_, place, _) => Literal::Ref(self.place(place, &mut premises)?),
+            Expr::Deref(..) => {
+                let i = self.place(e, &mut premises)?;
+                self.vars[i].1.clone()
+            }
+        };
+        Ok(Derivation {
+            conclusion: Eval {
+                term: Term::Expr(e.clone()),
+                value: Some(value),
+                from,
+                to: self.store,
+            },
+            premises,
+        })
+    }
+
+    fn stmt(&mut self, s: &Stmt) -> Result<Derivation<Eval>, EvalError> {
+        let from = self.store;
+        let mut premises = vec![];
+        match s {
+            Stmt::Let(x, _, e, _) => {
+                let d = self.expr(e)?;
+                self.vars.push((x.clone(), d.value().unwrap().clone()));
+                self.store += 1;
+                premises.push(d);
+            }
+            Stmt::Assign(place, e, _) => {
+                let d = self.expr(e)?;
+                let v = d.value().unwrap().clone();
+                premises.push(d);
+                let i = self.place(place, &mut premises)?;
+                self.vars[i].1 = v;
+                self.store += 1;
+            }
+            Stmt::Expr(e) => premises.push(self.expr(e)?),
+        }
+        Ok(Derivation {
+            conclusion: Eval {
+                term: Term::Stmt(s.clone()),
+                value: None,
+                from,
+                to: self.store,
+            },
+            premises,
+        })
+    }
+
+    // The index of the variable of a place expression, the
+    // evaluation of the reference of `*e` is a premise
+    fn place(
+        &mut self,
+        place: &Expr,
+        premises: &mut Vec<Derivation<Eval>>,
+    ) -> Result<usize, EvalError> {
+        match place {
+            Expr::Var(x, span) => self
+                .vars
+                .iter()
+                .rposition(|(y, _)| x == y)
+                .ok_or_else(|| EvalError::new(EvalErrorKind::UnboundVar, *span)),
+            Expr::Par(e) => self.place(e, premises),
+            Expr::Deref(e, span) => {
+                let d = self.expr(e)?;
+                let v = d.value().unwrap().clone();
+                premises.push(d);
+                match v {
+                    Literal::Ref(i) if i < self.vars.len() => Ok(i),
+                    Literal::Ref(_) => Err(EvalError::new(EvalErrorKind::Dangling, *span)),
+                    _ => Err(EvalError::new(EvalErrorKind::TypeMismatch, *span)),
+                }
+            }
+            // rejected by the type checker
+            _ => Err(EvalError::new(EvalErrorKind::TypeMismatch, Span::default())),
+        }
+    }
+
+    // Variables were added since `len`
+    fn changed(&mut self, len: usize) {
+        if self.vars.len() > len {
+            self.store += 1;
+        }
+    }
+
+    // The variables added since `len` go out of scope
+    fn exit(&mut self, len: usize) {
+        self.changed(len);
+        self.vars.truncate(len);
+    }
+}
+
+impl Expr {
+    // Evaluate the expression, recording the big-step derivation
+    pub fn derive(&self, mode: ArithMode) -> Result<Derivation<Eval>, EvalError> {
+        Deriver {
+            mode,
+            vars: vec![],
+            store: 0,
+        }
+        .expr(self)
+    }
+}
+
+#[cfg(test)]
+fn parse_expr(s: &str) -> Expr {
+    let ts: proc_macro2::TokenStream = s.parse().unwrap();
+    let p: crate::ast::Program = syn::parse2(ts).unwrap();
+    p.expr
+}
+
+#[test]
+fn derive_latex() {
+    let d = parse_expr("2 - 3").derive(Default::default()).unwrap();
+    assert_eq!(
+        d.to_latex(),
+        "\\frac{<\\texttt{2}, \\sigma> \\rightarrow <\\texttt{2}, \\sigma> \\quad \
+         <\\texttt{3}, \\sigma> \\rightarrow <\\texttt{3}, \\sigma>}\
+         {<\\texttt{2 - 3}, \\sigma> \\rightarrow <\\texttt{-1}, \\sigma>}"
+    );
+    assert_eq!(
+        d.to_unicode(),
+        "<2, σ> → <2, σ>   <3, σ> → <3, σ>\n\
+         ─────────────────────────────────\n      \
+         <2 - 3, σ> → <-1, σ>"
+    );
+    // nested, and short circuiting
+    let d = parse_expr("(2 - 3) * 4 < 0 || 1 / 0 == 1")
+        .derive(Default::default())
+        .unwrap();
+    assert_eq!(d.value(), Some(&Literal::Bool(true)));
+    assert_eq!(d.premises.len(), 1);
+    assert_eq!(d.to_latex().matches("\\frac").count(), 5);
+    // blocks change the store, source code is escaped
+    let d = parse_expr("{ let mut x = 1; x = x + 1; x }")
+        .derive(Default::default())
+        .unwrap();
+    let latex = d.to_latex();
+    assert!(latex.ends_with(
+        "{<\\texttt{\\{ let mut x = 1; x = x + 1; x \\}}, \\sigma> \
+         \\rightarrow <\\texttt{2}, \\sigma'''>}"
+    ));
+    assert!(latex.contains("<\\texttt{x = x + 1;}, \\sigma'> \\rightarrow \\sigma''"));
+}
+
+#[test]
+fn derive_agrees_with_eval() {
+    for s in &[
+        "8 - 7 - 6 * 5 - 4 * 3",
+        "[1, 2, 3][1] as u8 + 1u8",
+        "(1, 'a').1 == 'a' && len(\"abc\") == 3",
+        "enum E { A(i32), B } match E::A(2) { E::A(x) => x * 10, E::B => 0 }",
+        "struct P { x: i32, y: i32 } P { y: 1, x: 2 }",
+        "{ let mut a = [0; 2]; let r = &mut a; *r = [1, 2]; a[1] + { let a = 3; a } }",
+    ] {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        let p: crate::ast::Program = syn::parse2(ts).unwrap();
+        assert_eq!(
+            p.expr
+                .derive(Default::default())
+                .map(|d| d.value().cloned()),
+            p.eval_with(Default::default()).map(Some),
+            "{}",
+            s
+        );
+    }
+    let err = parse_expr("1 + 2 / (1 - 1)")
+        .derive(Default::default())
+        .unwrap_err();
+    assert_eq!(err.kind, EvalErrorKind::DivByZero);
+}
@@ -0,0 +1,326 @@
+// Random expressions, for property testing.
+//
+// `Generator::expr` produces random `Expr` trees of integer and Boolean
+// literals and binary operators, following the grammar (see `src/grammar.rs`):
+// operands binding weaker than the operator are parenthesized, so the tree
+// is exactly the one obtained by parsing (and climbing) its printed form.
+//
+// The depth of the trees, the mix of operators and whether the trees are
+// well typed are given by the `Config`. The generator is deterministic,
+// given the seed of its (splitmix64) random number generator.
+use crate::{
+    ast::{Expr, Literal, Op, Span},
+    typecheck::Type,
+};
+
+// A small, fast and deterministic random number generator (splitmix64)
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // A number in `0..n`
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    // True with a chance of `p` percent
+    pub fn percent(&mut self, p: u32) -> bool {
+        self.below(100) < p as u64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    // the maximal depth of the trees, literals have depth 0
+    pub depth: usize,
+    // the operators and their relative weights
+    pub ops: Vec<(Op, u32)>,
+    // only well typed trees, else operands may have any type
+    pub typed: bool,
+    // the chance (in percent) of a redundant parenthesis
+    pub parens: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            depth: 5,
+            ops: Op::ALL.iter().map(|op| (*op, 1)).collect(),
+            typed: true,
+            parens: 10,
+        }
+    }
+}
+
+pub struct Generator {
+    pub rng: Rng,
+    pub config: Config,
+}
+
+// The operand types of a typed operator with the given result type,
+// None if the operator does not produce the type
+fn operands(op: Op, ty: &Type, rng: &mut Rng) -> Option<Type> {
+    match (op, ty) {
+        (Op::Add | Op::Sub | Op::Mul | Op::Div, Type::Int) => Some(Type::Int),
+        (Op::Lt | Op::Le | Op::Gt | Op::Ge, Type::Bool) => Some(Type::Int),
+        (Op::Eq | Op::Ne, Type::Bool) if rng.percent(50) => Some(Type::Bool),
+        (Op::Eq | Op::Ne, Type::Bool) => Some(Type::Int),
+        (Op::And | Op::Or, Type::Bool) => Some(Type::Bool),
+        _ => None,
+    }
+}
+
+impl Generator {
+    pub fn new(seed: u64, config: Config) -> Generator {
+        Generator {
+            rng: Rng::new(seed),
+            config,
+        }
+    }
+
+    // A random expression, of type i32 or bool if typed
+    pub fn expr(&mut self) -> Expr {
+        let ty = if self.rng.percent(50) {
+            Type::Int
+        } else {
+            Type::Bool
+        };
+        self.expr_of(&ty)
+    }
+
+    // A random expression of the given type (if typed)
+    pub fn expr_of(&mut self, ty: &Type) -> Expr {
+        self.gen(ty, self.config.depth)
+    }
+
+    fn gen(&mut self, ty: &Type, depth: usize) -> Expr {
+        // the operators, and their operand types, producing the type
+        let mut ops = vec![];
+        for (op, w) in self.config.ops.clone() {
+            let operand = if self.config.typed {
+                operands(op, ty, &mut self.rng)
+            } else {
+                Some(ty.clone())
+            };
+            if let Some(operand) = operand {
+                ops.push((op, w, operand));
+            }
+        }
+        let total: u32 = ops.iter().map(|(_, w, _)| w).sum();
+        if depth == 0 || total == 0 || self.rng.percent(20) {
+            return self.literal(ty).into();
+        }
+        let mut pick = self.rng.below(total as u64) as u32;
+        let (op, operand) = ops
+            .into_iter()
+            .find_map(|(op, w, operand)| {
+                if pick < w {
+                    Some((op, operand))
+                } else {
+                    pick -= w;
+                    None
+                }
+            })
+            .unwrap();
+        let l = self.gen(&operand, depth - 1);
+        let r = self.gen(&operand, depth - 1);
+        self.binop(op, l, r)
+    }
+
+    fn literal(&mut self, ty: &Type) -> Literal {
+        let ty = match ty {
+            Type::Int | Type::Bool if self.config.typed => ty.clone(),
+            _ if self.rng.percent(50) => Type::Bool,
+            _ => Type::Int,
+        };
+        if ty == Type::Bool {
+            return Literal::Bool(self.rng.percent(50));
+        }
+        // mostly small numbers, and some edge cases for overflow
+        Literal::Int(match self.rng.below(10) {
+            0 => [i32::MIN, i32::MAX, -1, 0][self.rng.below(4) as usize],
+            1 => self.rng.next_u64() as i32,
+            _ => self.rng.below(20) as i32,
+        })
+    }
+
+    // The left operand needs parentheses if it binds weaker than
+    // the operator, the right operand if it does not bind harder
+    fn binop(&mut self, op: Op, l: Expr, r: Expr) -> Expr {
+        let p = op.priority();
+        let l = self.operand(l, p);
+        let r = self.operand(r, p + 1);
+        Expr::BinOp(op, Box::new(l), Box::new(r), Span::default())
+    }
+
+    fn operand(&mut self, e: Expr, priority: u8) -> Expr {
+        let weaker = matches!(&e, Expr::BinOp(op, ..) if op.priority() < priority);
+        // as `(-1)` is parsed as a literal a redundant parenthesis
+        // would not survive printing and parsing
+        let negative = matches!(&e, Expr::Lit(Literal::Int(i)) if *i < 0);
+        if weaker || !negative && self.rng.percent(self.config.parens) {
+            Expr::Par(Box::new(e))
+        } else {
+            e
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse_climb(s: &str) -> Expr {
+    let ts: proc_macro2::TokenStream = s.parse().unwrap();
+    crate::parse::climb(syn::parse2(ts).unwrap())
+}
+
+// The reference evaluator, using Rust's (checked) i32 arithmetic
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    Int(i32),
+    Bool(bool),
+}
+
+#[cfg(test)]
+fn reference(e: &Expr) -> Result<Value, crate::ast::EvalErrorKind> {
+    use crate::ast::EvalErrorKind::*;
+    use Value::*;
+    match e {
+        Expr::Lit(Literal::Int(i)) => Ok(Int(*i)),
+        Expr::Lit(Literal::Bool(b)) => Ok(Bool(*b)),
+        Expr::Par(e) => reference(e),
+        Expr::BinOp(op, l, r, _) => {
+            let l = reference(l)?;
+            match (op, l) {
+                (Op::And, Bool(false)) | (Op::Or, Bool(true)) => return Ok(l),
+                _ => {}
+            }
+            let r = reference(r)?;
+            Ok(match (op, l, r) {
+                (Op::Add, Int(l), Int(r)) => Int(l.checked_add(r).ok_or(Overflow)?),
+                (Op::Sub, Int(l), Int(r)) => Int(l.checked_sub(r).ok_or(Overflow)?),
+                (Op::Mul, Int(l), Int(r)) => Int(l.checked_mul(r).ok_or(Overflow)?),
+                (Op::Div, Int(_), Int(0)) => return Err(DivByZero),
+                (Op::Div, Int(l), Int(r)) => Int(l.checked_div(r).ok_or(Overflow)?),
+                (Op::Eq, l, r) => Bool(l == r),
+                (Op::Ne, l, r) => Bool(l != r),
+                (Op::Lt, Int(l), Int(r)) => Bool(l < r),
+                (Op::Le, Int(l), Int(r)) => Bool(l <= r),
+                (Op::Gt, Int(l), Int(r)) => Bool(l > r),
+                (Op::Ge, Int(l), Int(r)) => Bool(l >= r),
+                (Op::And | Op::Or, _, r) => r,
+                _ => unreachable!("ill typed {}", e),
+            })
+        }
+        _ => unreachable!("not generated {}", e),
+    }
+}
+
+// The sources of n typed and n untyped generated expressions, for
+// the differential tests
+#[cfg(test)]
+pub(crate) fn generated_sources(seed: u64, n: usize) -> Vec<String> {
+    let mut sources = vec![];
+    for typed in &[true, false] {
+        let config = Config {
+            typed: *typed,
+            ..Default::default()
+        };
+        let mut g = Generator::new(seed, config);
+        sources.extend((0..n).map(|_| g.expr().to_string()));
+    }
+    sources
+}
+
+#[test]
+fn generate_round_trip() {
+    for typed in &[true, false] {
+        let config = Config {
+            typed: *typed,
+            ..Default::default()
+        };
+        let mut g = Generator::new(1, config);
+        for _ in 0..500 {
+            let e = g.expr();
+            let s = e.to_string();
+            assert_eq!(parse_climb(&s), e, "{}", s);
+        }
+    }
+}
+
+#[test]
+fn generate_well_typed() {
+    let mut g = Generator::new(2, Config::default());
+    for _ in 0..500 {
+        let e = g.expr();
+        let ty = e.type_check().unwrap();
+        assert!(ty == Type::Int || ty == Type::Bool, "{}", e);
+    }
+    assert_eq!(g.expr_of(&Type::Bool).type_check().unwrap(), Type::Bool);
+}
+
+#[test]
+fn generate_eval_reference() {
+    use crate::ast::EvalErrorKind;
+    let mut g = Generator::new(3, Config::default());
+    for _ in 0..1000 {
+        let e = g.expr();
+        let s = e.to_string();
+        let v = parse_climb(&s).eval_with(Default::default());
+        match reference(&e) {
+            Ok(Value::Int(i)) => assert_eq!(v, Ok(Literal::Int(i)), "{}", s),
+            Ok(Value::Bool(b)) => assert_eq!(v, Ok(Literal::Bool(b)), "{}", s),
+            Err(kind) => {
+                // with the bigint feature Int does not overflow
+                if cfg!(feature = "bigint") && kind == EvalErrorKind::Overflow {
+                    continue;
+                }
+                assert_eq!(v.map_err(|err| err.kind), Err(kind), "{}", s)
+            }
+        }
+    }
+}
+
+#[test]
+fn generate_config() {
+    // only additions, at most 2^3 literals (and the right operands
+    // are parenthesized)
+    let config = Config {
+        depth: 3,
+        ops: vec![(Op::Add, 1)],
+        typed: true,
+        parens: 0,
+    };
+    let mut g = Generator::new(4, config);
+    for _ in 0..100 {
+        let e = g.expr_of(&Type::Int);
+        let s = e.to_string();
+        assert!(s.split(" + ").count() <= 8, "{}", s);
+        assert!(!s.contains(&['*', '/', '<', '&'][..]), "{}", s);
+    }
+    // no operators producing bool, just literals
+    assert!(matches!(
+        g.expr_of(&Type::Bool),
+        Expr::Lit(Literal::Bool(_))
+    ));
+    // depth 0
+    g.config.depth = 0;
+    assert!(matches!(g.expr(), Expr::Lit(_)));
+    // the same seed gives the same expressions
+    let mut a = Generator::new(5, Config::default());
+    let mut b = Generator::new(5, Config::default());
+    for _ in 0..10 {
+        assert_eq!(a.expr(), b.expr());
+    }
+}
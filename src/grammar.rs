@@ -0,0 +1,618 @@
+// A declarative description of the grammar accepted by `src/parse.rs`.
+//
+// The operator precedence levels are generated from `Op::priority`, the
+// functions from `Builtin::ALL` and the type names from `Type::PRIMITIVES`,
+// so the documentation follows the implementation. The grammar is rendered
+// as (ISO style) EBNF text by `Grammar::to_ebnf`, and as railroad diagrams
+// by `Grammar::to_svg`.
+//
+// The binary operators are given as repetitions (`Add = Mul , { "+" , Mul }`),
+// the parser builds the left associative tree by precedence climbing.
+use crate::{
+    ast::{Builtin, Op},
+    typecheck::Type,
+};
+use std::fmt::{self, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    // a token, e.g., `"+"`
+    Terminal(String),
+    // a reference to a rule
+    NonTerminal(String),
+    // tokens described in words, `? integer literal ?`
+    Special(String),
+    Seq(Vec<Node>),
+    // the first alternative is drawn on the main track
+    Choice(Vec<Node>),
+    // `[ n ]`
+    Optional(Box<Node>),
+    // `{ n }`, zero or more
+    Repeat(Box<Node>),
+}
+
+use Node::*;
+
+fn t(s: &str) -> Node {
+    Terminal(s.to_string())
+}
+
+fn n(s: &str) -> Node {
+    NonTerminal(s.to_string())
+}
+
+fn opt(n: Node) -> Node {
+    Optional(Box::new(n))
+}
+
+fn rep(n: Node) -> Node {
+    Repeat(Box::new(n))
+}
+
+// `[ n , { "," , n } , [ "," ] ]`, a comma separated list
+// with an optional trailing comma, as `parse_terminated`
+fn terminated(n: Node) -> Node {
+    opt(Seq(vec![n.clone(), rep(Seq(vec![t(","), n])), opt(t(","))]))
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminal(s) if s.contains('"') => write!(f, "'{}'", s),
+            Terminal(s) => write!(f, "\"{}\"", s),
+            NonTerminal(s) => f.write_str(s),
+            Special(s) => write!(f, "? {} ?", s),
+            Seq(ns) => {
+                let ns: Vec<_> = ns
+                    .iter()
+                    .map(|n| match n {
+                        Choice(_) => format!("( {} )", n),
+                        n => n.to_string(),
+                    })
+                    .collect();
+                f.write_str(&ns.join(" , "))
+            }
+            Choice(ns) => {
+                let ns: Vec<_> = ns.iter().map(|n| n.to_string()).collect();
+                f.write_str(&ns.join(" | "))
+            }
+            Optional(n) => write!(f, "[ {} ]", n),
+            Repeat(n) => write!(f, "{{ {} }}", n),
+        }
+    }
+}
+
+// The rules, the first is the start symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grammar {
+    pub rules: Vec<(String, Node)>,
+}
+
+// The rule name of an operator precedence level
+fn level(priority: u8, lowest: u8) -> String {
+    if priority == lowest {
+        "Expr".to_string()
+    } else {
+        format!("Expr{}", priority)
+    }
+}
+
+impl Grammar {
+    // The grammar of programs, declarations followed by an expression
+    pub fn expr() -> Grammar {
+        let mut rules = vec![];
+        let mut rule = |name: &str, body: Node| rules.push((name.to_string(), body));
+        rule(
+            "Program",
+            Seq(vec![rep(Choice(vec![n("Struct"), n("Enum")])), n("Expr")]),
+        );
+
+        // a level per priority, binding harder than the previous
+        let mut priorities: Vec<u8> = Op::ALL.iter().map(|op| op.priority()).collect();
+        priorities.sort_unstable();
+        priorities.dedup();
+        let lowest = priorities[0];
+        for (i, p) in priorities.iter().enumerate() {
+            let next = match priorities.get(i + 1) {
+                Some(q) => n(&level(*q, lowest)),
+                None => n("Cast"),
+            };
+            let ops: Vec<_> = Op::ALL
+                .iter()
+                .filter(|op| op.priority() == *p)
+                .map(|op| t(&op.to_string()))
+                .collect();
+            let op = if ops.len() == 1 {
+                ops[0].clone()
+            } else {
+                Choice(ops)
+            };
+            rule(
+                &level(*p, lowest),
+                Seq(vec![next.clone(), rep(Seq(vec![op, next]))]),
+            );
+        }
+        rule(
+            "Cast",
+            Seq(vec![n("Unary"), rep(Seq(vec![t("as"), n("Type")]))]),
+        );
+        rule(
+            "Unary",
+            Choice(vec![
+                Seq(vec![t("*"), n("Unary")]),
+                Seq(vec![t("&"), opt(t("mut")), n("Unary")]),
+                n("Postfix"),
+            ]),
+        );
+        rule(
+            "Postfix",
+            Seq(vec![
+                n("Primary"),
+                rep(Choice(vec![
+                    Seq(vec![t("["), n("Expr"), t("]")]),
+                    Seq(vec![t("."), Choice(vec![n("Ident"), n("Integer")])]),
+                ])),
+            ]),
+        );
+        rule(
+            "Primary",
+            Choice(vec![
+                n("Literal"),
+                Seq(vec![
+                    t("("),
+                    opt(Seq(vec![n("Expr"), opt(Seq(vec![t(","), n("Exprs")]))])),
+                    t(")"),
+                ]),
+                Seq(vec![
+                    t("["),
+                    opt(Seq(vec![
+                        n("Expr"),
+                        Choice(vec![
+                            Seq(vec![t(";"), n("Integer")]),
+                            opt(Seq(vec![t(","), n("Exprs")])),
+                        ]),
+                    ])),
+                    t("]"),
+                ]),
+                n("Match"),
+                n("Block"),
+                Seq(vec![
+                    n("Ident"),
+                    t("::"),
+                    n("Ident"),
+                    opt(Seq(vec![t("("), n("Exprs"), t(")")])),
+                ]),
+                Seq(vec![
+                    n("Ident"),
+                    t("{"),
+                    terminated(Seq(vec![n("Ident"), t(":"), n("Expr")])),
+                    t("}"),
+                ]),
+                Seq(vec![n("Function"), t("("), n("Exprs"), t(")")]),
+                n("Ident"),
+            ]),
+        );
+        rule("Exprs", terminated(n("Expr")));
+        rule(
+            "Match",
+            Seq(vec![
+                t("match"),
+                n("Expr"),
+                t("{"),
+                terminated(n("Arm")),
+                t("}"),
+            ]),
+        );
+        rule("Arm", Seq(vec![n("Pattern"), t("=>"), n("Expr")]));
+        rule(
+            "Pattern",
+            Choice(vec![
+                t("_"),
+                Seq(vec![
+                    n("Ident"),
+                    t("::"),
+                    n("Ident"),
+                    opt(Seq(vec![t("("), terminated(n("Pattern")), t(")")])),
+                ]),
+                n("Ident"),
+                n("Literal"),
+            ]),
+        );
+        rule(
+            "Block",
+            Seq(vec![t("{"), rep(n("Stmt")), opt(n("Expr")), t("}")]),
+        );
+        // as in Rust the `;` is optional after blocks and `match`
+        rule(
+            "Stmt",
+            Choice(vec![
+                Seq(vec![
+                    t("let"),
+                    opt(t("mut")),
+                    n("Ident"),
+                    t("="),
+                    n("Expr"),
+                    t(";"),
+                ]),
+                Seq(vec![Choice(vec![n("Block"), n("Match")]), opt(t(";"))]),
+                Seq(vec![n("Expr"), opt(Seq(vec![t("="), n("Expr")])), t(";")]),
+            ]),
+        );
+        rule(
+            "Struct",
+            Seq(vec![
+                t("struct"),
+                n("Ident"),
+                t("{"),
+                terminated(Seq(vec![n("Ident"), t(":"), n("DeclType")])),
+                t("}"),
+            ]),
+        );
+        rule(
+            "Enum",
+            Seq(vec![
+                t("enum"),
+                n("Ident"),
+                t("{"),
+                terminated(Seq(vec![
+                    n("Ident"),
+                    opt(Seq(vec![t("("), terminated(n("DeclType")), t(")")])),
+                ])),
+                t("}"),
+            ]),
+        );
+        rule(
+            "DeclType",
+            Choice(vec![
+                n("Type"),
+                n("Ident"),
+                Seq(vec![t("("), terminated(n("DeclType")), t(")")]),
+                Seq(vec![t("["), n("DeclType"), t(";"), n("Integer"), t("]")]),
+            ]),
+        );
+        rule(
+            "Type",
+            Choice(
+                Type::PRIMITIVES
+                    .iter()
+                    .map(|ty| t(&ty.to_string()))
+                    .collect(),
+            ),
+        );
+        rule(
+            "Function",
+            Choice(Builtin::ALL.iter().map(|f| t(&f.to_string())).collect()),
+        );
+        rule(
+            "Literal",
+            Choice(vec![
+                n("Integer"),
+                n("Float"),
+                t("true"),
+                t("false"),
+                n("Char"),
+                n("String"),
+                n("Date"),
+            ]),
+        );
+        rule(
+            "Integer",
+            Special("integer literal, with an optional type suffix".to_string()),
+        );
+        rule(
+            "Float",
+            Special("float literal, with an optional type suffix".to_string()),
+        );
+        rule("Char", Special("character literal".to_string()));
+        rule("String", Special("string literal".to_string()));
+        rule(
+            "Date",
+            Seq(vec![
+                t("d"),
+                Special("\"YYYY-MM-DD\", a valid date".to_string()),
+            ]),
+        );
+        rule("Ident", Special("identifier".to_string()));
+        Grammar { rules }
+    }
+
+    // A rule per line, `name = body ;`
+    pub fn to_ebnf(&self) -> String {
+        let width = self.rules.iter().map(|(r, _)| r.len()).max().unwrap_or(0);
+        let mut s = String::new();
+        for (name, body) in &self.rules {
+            writeln!(s, "{:width$} = {} ;", name, body, width = width).unwrap();
+        }
+        s
+    }
+
+    // A railroad diagram per rule, below each other
+    pub fn to_svg(&self) -> String {
+        let mut body = String::new();
+        let mut y = 0;
+        let mut width = 0;
+        for (name, node) in &self.rules {
+            let l = node.layout();
+            writeln!(
+                body,
+                "<text class=\"rule\" x=\"10\" y=\"{}\">{}</text>",
+                y + 20,
+                xml(name)
+            )
+            .unwrap();
+            // the track, with a bar at the start and at the end
+            let track = y + 40 + l.up;
+            let end = 30 + l.w;
+            writeln!(
+                body,
+                "<path d=\"M 10 {0} v 20 M 10 {1} h 20 M {2} {1} h 20 M {3} {0} v 20\"/>",
+                track - 10,
+                track,
+                end,
+                end + 20
+            )
+            .unwrap();
+            node.draw(30, track, &mut body);
+            y = track + l.down + 20;
+            width = width.max(end + 30);
+        }
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\">\n\
+             <style>\
+             path, rect {{ fill: none; stroke: black; stroke-width: 1.5 }} \
+             text {{ font: 14px monospace; text-anchor: middle }} \
+             text.rule {{ font-weight: bold; text-anchor: start }} \
+             text.special {{ font-style: italic }}\
+             </style>\n{b}</svg>\n",
+            w = width,
+            h = y,
+            b = body
+        )
+    }
+}
+
+// Escape the XML special characters
+fn xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// The width of the diagram, and the height above and below the track
+struct Layout {
+    w: i32,
+    up: i32,
+    down: i32,
+}
+
+// The horizontal space around alternatives, loops and between
+// items, and the vertical space between alternatives
+const CURVE: i32 = 20;
+const GAP: i32 = 10;
+const BOX: i32 = 11;
+
+impl Node {
+    fn layout(&self) -> Layout {
+        match self {
+            Terminal(s) | NonTerminal(s) | Special(s) => Layout {
+                w: 8 * s.chars().count() as i32 + 20,
+                up: BOX,
+                down: BOX,
+            },
+            Seq(ns) => {
+                let ls: Vec<_> = ns.iter().map(|n| n.layout()).collect();
+                Layout {
+                    w: ls.iter().map(|l| l.w).sum::<i32>() + GAP * (ls.len().max(1) as i32 - 1),
+                    up: ls.iter().map(|l| l.up).max().unwrap_or(0),
+                    down: ls.iter().map(|l| l.down).max().unwrap_or(0),
+                }
+            }
+            Choice(ns) => {
+                let ls: Vec<_> = ns.iter().map(|n| n.layout()).collect();
+                let tracks = alternatives(&ls);
+                let last = ls.last().map_or(0, |l| l.down);
+                Layout {
+                    w: ls.iter().map(|l| l.w).max().unwrap_or(0) + 2 * CURVE,
+                    up: ls.first().map_or(0, |l| l.up),
+                    down: tracks.last().copied().unwrap_or(0) + last,
+                }
+            }
+            Optional(n) => Choice(vec![Seq(vec![]), (**n).clone()]).layout(),
+            // the loop back is drawn above the track
+            Repeat(n) => {
+                let l = n.layout();
+                Layout {
+                    w: l.w + 2 * CURVE,
+                    up: (l.down + GAP).max(CURVE) + l.up,
+                    down: 0,
+                }
+            }
+        }
+    }
+
+    // Draw the diagram from `x` with the track at `y`
+    fn draw(&self, x: i32, y: i32, out: &mut String) {
+        let l = self.layout();
+        match self {
+            Terminal(s) | NonTerminal(s) | Special(s) => {
+                let (rx, class) = match self {
+                    Terminal(_) => (BOX, ""),
+                    Special(_) => (0, " class=\"special\""),
+                    _ => (0, ""),
+                };
+                writeln!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/>\
+                     <text{} x=\"{}\" y=\"{}\">{}</text>",
+                    x,
+                    y - BOX,
+                    l.w,
+                    2 * BOX,
+                    rx,
+                    class,
+                    x + l.w / 2,
+                    y + 5,
+                    xml(s)
+                )
+                .unwrap();
+            }
+            Seq(ns) => {
+                let mut x = x;
+                for (i, n) in ns.iter().enumerate() {
+                    if i > 0 {
+                        line(out, x, y, GAP);
+                        x += GAP;
+                    }
+                    n.draw(x, y, out);
+                    x += n.layout().w;
+                }
+            }
+            Choice(ns) => {
+                let ls: Vec<_> = ns.iter().map(|n| n.layout()).collect();
+                let inner = l.w - 2 * CURVE;
+                let (left, right) = (x + CURVE, x + CURVE + inner);
+                for ((n, nl), dy) in ns.iter().zip(&ls).zip(alternatives(&ls)) {
+                    let yi = y + dy;
+                    if dy == 0 {
+                        line(out, x, y, CURVE);
+                        line(out, right, y, CURVE);
+                    } else {
+                        writeln!(
+                            out,
+                            "<path d=\"M {x} {y} q {h} 0 {h} {h} V {v} q 0 {h} {h} {h} \
+                             M {r} {yi} q {h} 0 {h} -{h} V {w} q 0 -{h} {h} -{h}\"/>",
+                            x = x,
+                            y = y,
+                            h = CURVE / 2,
+                            v = yi - CURVE / 2,
+                            r = right,
+                            yi = yi,
+                            w = y + CURVE / 2
+                        )
+                        .unwrap();
+                    }
+                    n.draw(left, yi, out);
+                    line(out, left + nl.w, yi, inner - nl.w);
+                }
+            }
+            Optional(n) => Choice(vec![Seq(vec![]), (**n).clone()]).draw(x, y, out),
+            Repeat(n) => {
+                let nl = n.layout();
+                let yl = y - (nl.down + GAP).max(CURVE);
+                line(out, x, y, l.w);
+                writeln!(
+                    out,
+                    "<path d=\"M {x} {y} q {h} 0 {h} -{h} V {v} q 0 -{h} {h} -{h} \
+                     M {r} {yl} q {h} 0 {h} {h} V {w} q 0 {h} {h} {h}\"/>",
+                    x = x,
+                    y = y,
+                    h = CURVE / 2,
+                    v = yl + CURVE / 2,
+                    r = x + CURVE + nl.w,
+                    yl = yl,
+                    w = y - CURVE / 2
+                )
+                .unwrap();
+                n.draw(x + CURVE, yl, out);
+            }
+        }
+    }
+}
+
+// The offsets of the alternative tracks below the first
+fn alternatives(ls: &[Layout]) -> Vec<i32> {
+    let mut dy = 0;
+    let mut tracks = vec![];
+    for (i, l) in ls.iter().enumerate() {
+        if i > 0 {
+            dy = (dy + ls[i - 1].down + GAP + l.up).max(dy + CURVE);
+        }
+        tracks.push(dy);
+    }
+    tracks
+}
+
+fn line(out: &mut String, x: i32, y: i32, w: i32) {
+    if w > 0 {
+        writeln!(out, "<path d=\"M {} {} h {}\"/>", x, y, w).unwrap();
+    }
+}
+
+#[test]
+fn grammar_ebnf() {
+    let ebnf = Grammar::expr().to_ebnf();
+    println!("{}", ebnf);
+    let lines: Vec<_> = ebnf
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    for rule in &[
+        "Program = { Struct | Enum } , Expr ;",
+        "Expr = Expr1 , { \"||\" , Expr1 } ;",
+        "Expr1 = Expr2 , { \"&&\" , Expr2 } ;",
+        "Expr2 = Expr3 , { ( \"==\" | \"!=\" | \"<=\" | \">=\" | \"<\" | \">\" ) , Expr3 } ;",
+        "Expr3 = Expr4 , { ( \"+\" | \"-\" ) , Expr4 } ;",
+        "Expr4 = Cast , { ( \"*\" | \"/\" ) , Cast } ;",
+        "Cast = Unary , { \"as\" , Type } ;",
+        "Exprs = [ Expr , { \",\" , Expr } , [ \",\" ] ] ;",
+        "Function = \"len\" | \"upper\" | \"substr\" ;",
+        "Ident = ? identifier ? ;",
+        "Date = \"d\" , ? \"YYYY-MM-DD\", a valid date ? ;",
+    ] {
+        assert!(lines.contains(&rule.to_string()), "{}", rule);
+    }
+}
+
+#[test]
+fn grammar_complete() {
+    let g = Grammar::expr();
+    // each referenced rule is defined exactly once
+    fn refs<'a>(n: &'a Node, out: &mut Vec<&'a String>) {
+        match n {
+            NonTerminal(s) => out.push(s),
+            Seq(ns) | Choice(ns) => ns.iter().for_each(|n| refs(n, out)),
+            Optional(n) | Repeat(n) => refs(n, out),
+            _ => {}
+        }
+    }
+    let mut used = vec![];
+    for (name, body) in &g.rules {
+        assert_eq!(g.rules.iter().filter(|(r, _)| r == name).count(), 1);
+        refs(body, &mut used);
+    }
+    for r in &used {
+        assert!(g.rules.iter().any(|(name, _)| name == *r), "{}", r);
+    }
+    // and all rules but the start symbol are used
+    for (name, _) in &g.rules[1..] {
+        assert!(used.contains(&name), "{}", name);
+    }
+    // the type names are accepted by the parser
+    for ty in &Type::PRIMITIVES {
+        assert_eq!(Type::from_name(&ty.to_string()).as_ref(), Some(ty));
+    }
+    for f in &Builtin::ALL {
+        assert_eq!(Builtin::from_name(&f.to_string()), Some(*f));
+    }
+}
+
+#[test]
+fn grammar_svg() {
+    let g = Grammar::expr();
+    let svg = g.to_svg();
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("class=\"rule\"").count(), g.rules.len());
+    assert!(svg.contains(">&amp;&amp;</text>"));
+    assert_eq!(svg.matches("<text").count(), svg.matches("</text>").count());
+    // a terminal is a rounded box on the track
+    let mut s = String::new();
+    t("+").draw(0, 50, &mut s);
+    assert_eq!(
+        s,
+        "<rect x=\"0\" y=\"39\" width=\"28\" height=\"22\" rx=\"11\"/>\
+         <text x=\"14\" y=\"55\">+</text>\n"
+    );
+}
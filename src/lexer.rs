@@ -0,0 +1,504 @@
+// A standalone lexer, independent of `proc_macro2`.
+//
+// `proc_macro2` lexes Rust, so `'unterminated` or `#` give errors far
+// from their cause, and `<>` is split into Rust tokens. This
+// lexer gives tokens with their byte offsets and line/column, and
+// errors at the offending character. The tokens are parsed by
+// `src/lexer_parse.rs`, the `syn` parsers (`src/parse.rs`) remain.
+use crate::ast::Comment;
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+// A position in the source, lines and columns (in chars) count from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    // identifiers and keywords
+    Ident(String),
+    // the digits (without `_`) and the suffix, e.g., `1_000u8`
+    Int(String, String),
+    Float(String, String),
+    // the value, escapes resolved
+    Char(char),
+    Str(String),
+    // operators and delimiters, e.g., `+`, `<>` or `{`
+    Punct(&'static str),
+    // the text of a `///` or `/** */` doc comment
+    Doc(String),
+    // a plain comment, only given by `lex_comments`
+    Comment(Comment),
+    // the end of the source
+    Eof,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Ident(x) => write!(f, "`{}`", x),
+            TokenKind::Int(n, s) | TokenKind::Float(n, s) => write!(f, "`{}{}`", n, s),
+            TokenKind::Char(c) => write!(f, "`{:?}`", c),
+            TokenKind::Str(s) => write!(f, "`{:?}`", s),
+            TokenKind::Punct(p) => write!(f, "`{}`", p),
+            TokenKind::Doc(_) => f.write_str("a doc comment"),
+            TokenKind::Comment(_) => f.write_str("a comment"),
+            TokenKind::Eof => f.write_str("end of input"),
+        }
+    }
+}
+
+// A token, from `pos` to the byte offset `end`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: Pos,
+    pub end: usize,
+}
+
+// A lexing (or parsing) error, at a position in the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub msg: String,
+    pub pos: Pos,
+}
+
+impl Error {
+    pub fn new(msg: impl Into<String>, pos: Pos) -> Self {
+        Error {
+            msg: msg.into(),
+            pos,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.pos, self.msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+// The punctuation, longer before shorter (a token is as long as possible)
+pub(crate) const PUNCTS: &[&str] = &[
+    "<>", "==", "!=", "<=", ">=", "&&", "||", "=>", "::", "+", "-", "*", "/", "<", ">", "=", "&",
+    "!", ";", ":", ",", ".", "(", ")", "[", "]", "{", "}",
+];
+
+struct Lexer<'a> {
+    src: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    col: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn pos(&mut self) -> Pos {
+        let offset = self.chars.peek().map_or(self.src.len(), |(i, _)| *i);
+        Pos {
+            offset,
+            line: self.line,
+            col: self.col,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let (_, c) = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+
+    // The chars while `f` holds
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek().filter(|c| f(*c)) {
+            s.push(c);
+            self.bump();
+        }
+        s
+    }
+
+    fn ident(&mut self) -> String {
+        self.take_while(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    // Digits and `_`, the `_` are dropped
+    fn digits(&mut self) -> String {
+        self.take_while(|c| c.is_ascii_digit() || c == '_')
+            .replace('_', "")
+    }
+
+    // An integer or float, after a `.` only integers (`t.0.1` is
+    // nested tuple indexing)
+    fn number(&mut self, field: bool) -> TokenKind {
+        // `0x`, `0o` or `0b` (the digits are checked by the parser)
+        let rest = &self.src[self.pos().offset..];
+        if !field && ["0x", "0o", "0b"].iter().any(|p| rest.starts_with(p)) {
+            let mut n: String = (0..2).filter_map(|_| self.bump()).collect();
+            n += &self
+                .take_while(|c| c.is_ascii_hexdigit() || c == '_')
+                .replace('_', "");
+            return TokenKind::Int(n, self.ident());
+        }
+        let mut n = self.digits();
+        let mut float = false;
+        let rest = &self.src[self.pos().offset..];
+        let mut ahead = rest.chars();
+        if !field && ahead.next() == Some('.') && ahead.next().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+            n.push('.');
+            n += &self.digits();
+            float = true;
+        }
+        let rest = &self.src[self.pos().offset..];
+        let mut ahead = rest.chars();
+        if !field && matches!(ahead.next(), Some('e' | 'E')) {
+            let sign = matches!(ahead.clone().next(), Some('+' | '-'));
+            if ahead.nth(sign as usize).is_some_and(|c| c.is_ascii_digit()) {
+                n.push(self.bump().unwrap());
+                if sign {
+                    n.push(self.bump().unwrap());
+                }
+                n += &self.digits();
+                float = true;
+            }
+        }
+        let suffix = self.ident();
+        if float {
+            TokenKind::Float(n, suffix)
+        } else {
+            TokenKind::Int(n, suffix)
+        }
+    }
+
+    // An escape `\..`, in a char or string literal
+    fn escape(&mut self, start: Pos) -> Result<char, Error> {
+        let pos = self.pos();
+        self.bump();
+        let c = self
+            .bump()
+            .ok_or_else(|| Error::new("unterminated literal", start))?;
+        Ok(match c {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' | '\'' | '"' => c,
+            'x' => {
+                let hex: String = (0..2).filter_map(|_| self.bump()).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(b) if b < 0x80 => b as char,
+                    _ => return Err(Error::new("invalid `\\x` escape", pos)),
+                }
+            }
+            'u' => {
+                let hex = if self.peek() == Some('{') {
+                    self.bump();
+                    let hex = self.take_while(|c| c.is_ascii_hexdigit());
+                    self.bump().filter(|c| *c == '}').map(|_| hex)
+                } else {
+                    None
+                };
+                match hex.and_then(|hex| u32::from_str_radix(&hex, 16).ok()) {
+                    Some(u) => {
+                        std::char::from_u32(u).ok_or_else(|| Error::new("invalid unicode", pos))?
+                    }
+                    None => return Err(Error::new("invalid `\\u{..}` escape", pos)),
+                }
+            }
+            c => return Err(Error::new(format!("unknown escape `\\{}`", c), pos)),
+        })
+    }
+
+    fn char_lit(&mut self, start: Pos) -> Result<TokenKind, Error> {
+        self.bump();
+        let c = match self.peek() {
+            Some('\\') => self.escape(start)?,
+            Some(c) if c != '\'' && c != '\n' => {
+                self.bump();
+                c
+            }
+            _ => return Err(Error::new("empty or unterminated character literal", start)),
+        };
+        if self.peek() != Some('\'') {
+            return Err(Error::new("unterminated character literal", start));
+        }
+        self.bump();
+        Ok(TokenKind::Char(c))
+    }
+
+    fn str_lit(&mut self, start: Pos) -> Result<TokenKind, Error> {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    return Ok(TokenKind::Str(s));
+                }
+                // a `\` at the end of a line skips the following whitespace
+                Some('\\') if self.src[self.pos().offset + 1..].starts_with('\n') => {
+                    self.bump();
+                    self.take_while(char::is_whitespace);
+                }
+                Some('\\') => s.push(self.escape(start)?),
+                Some(c) => {
+                    self.bump();
+                    s.push(c);
+                }
+                None => return Err(Error::new("unterminated string literal", start)),
+            }
+        }
+    }
+
+    // A `//` or `/* */` comment, block comments nest as in Rust. Doc
+    // comments are `///` but not `////`, and `/**` but not `/***` or
+    // `/**/`
+    fn comment(&mut self, start: Pos) -> Result<TokenKind, Error> {
+        let rest = &self.src[start.offset..];
+        if rest.starts_with("//") {
+            let doc = rest.starts_with("///") && !rest.starts_with("////");
+            self.bump();
+            self.bump();
+            let text = self.take_while(|c| c != '\n');
+            return Ok(if doc {
+                TokenKind::Doc(text[1..].to_string())
+            } else {
+                TokenKind::Comment(Comment::Line(text))
+            });
+        }
+        let doc = rest.starts_with("/**") && !rest.starts_with("/***") && !rest.starts_with("/**/");
+        self.bump();
+        self.bump();
+        // the text after the `/*`
+        let mut text = String::new();
+        let mut depth = 1;
+        loop {
+            let rest = &self.src[self.pos().offset..];
+            if rest.is_empty() {
+                return Err(Error::new("unterminated block comment", start));
+            } else if rest.starts_with("/*") {
+                depth += 1;
+            } else if rest.starts_with("*/") {
+                depth -= 1;
+                if depth == 0 {
+                    self.bump();
+                    self.bump();
+                    return Ok(if doc {
+                        TokenKind::Doc(text[1..].to_string())
+                    } else {
+                        TokenKind::Comment(Comment::Block(text))
+                    });
+                }
+            }
+            let n = if rest.starts_with("/*") || rest.starts_with("*/") {
+                2
+            } else {
+                1
+            };
+            for _ in 0..n {
+                text.push(self.bump().unwrap());
+            }
+        }
+    }
+
+    fn punct(&mut self, start: Pos) -> Result<TokenKind, Error> {
+        let rest = &self.src[start.offset..];
+        match PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+            Some(p) => {
+                for _ in 0..p.len() {
+                    self.bump();
+                }
+                Ok(TokenKind::Punct(p))
+            }
+            None => Err(Error::new(
+                format!("unexpected character `{}`", self.peek().unwrap()),
+                start,
+            )),
+        }
+    }
+}
+
+// The tokens of the source, ending with `Eof`, plain comments are skipped
+pub fn lex(src: &str) -> Result<Vec<Token>, Error> {
+    Ok(lex_comments(src)?.0)
+}
+
+// The tokens, and separately the plain comments as `TokenKind::Comment`
+pub fn lex_comments(src: &str) -> Result<(Vec<Token>, Vec<Token>), Error> {
+    let mut lexer = Lexer {
+        src,
+        chars: src.char_indices().peekable(),
+        line: 1,
+        col: 1,
+    };
+    let mut tokens: Vec<Token> = vec![];
+    let mut comments = vec![];
+    loop {
+        lexer.take_while(char::is_whitespace);
+        let pos = lexer.pos();
+        let comment = ["//", "/*"]
+            .iter()
+            .any(|p| src[pos.offset..].starts_with(p));
+        let kind = match lexer.peek() {
+            None => TokenKind::Eof,
+            Some(_) if comment => lexer.comment(pos)?,
+            Some(c) if c.is_alphabetic() || c == '_' => TokenKind::Ident(lexer.ident()),
+            Some(c) if c.is_ascii_digit() => {
+                let field = matches!(tokens.last(), Some(t) if t.kind == TokenKind::Punct("."));
+                lexer.number(field)
+            }
+            Some('\'') => lexer.char_lit(pos)?,
+            Some('"') => lexer.str_lit(pos)?,
+            Some(_) => lexer.punct(pos)?,
+        };
+        let end = lexer.pos().offset;
+        let eof = kind == TokenKind::Eof;
+        match kind {
+            TokenKind::Comment(_) => comments.push(Token { kind, pos, end }),
+            _ => tokens.push(Token { kind, pos, end }),
+        }
+        if eof {
+            return Ok((tokens, comments));
+        }
+    }
+}
+
+#[cfg(test)]
+fn kinds(src: &str) -> Vec<TokenKind> {
+    lex(src).unwrap().into_iter().map(|t| t.kind).collect()
+}
+
+#[test]
+fn lex_tokens() {
+    use TokenKind::*;
+    let int = |n: &str, s: &str| Int(n.to_string(), s.to_string());
+    let float = |n: &str, s: &str| Float(n.to_string(), s.to_string());
+    assert_eq!(
+        kinds("x1 + 1_000u8 ** 2.5e-3f32 <> 'a' && \"b\\n\""),
+        vec![
+            Ident("x1".to_string()),
+            Punct("+"),
+            int("1000", "u8"),
+            Punct("*"),
+            Punct("*"),
+            float("2.5e-3", "f32"),
+            Punct("<>"),
+            Char('a'),
+            Punct("&&"),
+            Str("b\n".to_string()),
+            Eof
+        ]
+    );
+    // nested tuple indexing, not a float
+    assert_eq!(
+        kinds("t.0.1 1.0 1e3 2.max 0xff_u8"),
+        vec![
+            Ident("t".to_string()),
+            Punct("."),
+            int("0", ""),
+            Punct("."),
+            int("1", ""),
+            float("1.0", ""),
+            float("1e3", ""),
+            int("2", ""),
+            Punct("."),
+            Ident("max".to_string()),
+            int("0xff", "u8"),
+            Eof
+        ]
+    );
+    assert_eq!(
+        kinds("'\\'' '\\u{e9}' \"\\x41\\\n   b\""),
+        vec![Char('\''), Char('é'), Str("Ab".to_string()), Eof]
+    );
+}
+
+#[test]
+fn lex_comment_tokens() {
+    use TokenKind::*;
+    let doc = |s: &str| Doc(s.to_string());
+    assert_eq!(
+        kinds("// a\n/// b\n//// c\n1 /* d /* e */ f */ + /** g */ 2 /***/ /**/ //"),
+        vec![
+            doc(" b"),
+            Int("1".to_string(), String::new()),
+            Punct("+"),
+            doc(" g "),
+            Int("2".to_string(), String::new()),
+            Eof
+        ]
+    );
+    // the plain comments, separately
+    let (tokens, comments) = lex_comments("// a\n1 /* b /* c */ */ //// d").unwrap();
+    assert_eq!(tokens.len(), 2);
+    let comments: Vec<_> = comments
+        .into_iter()
+        .map(|t| (t.kind, t.pos.offset))
+        .collect();
+    assert_eq!(
+        comments,
+        vec![
+            (Comment(crate::ast::Comment::Line(" a".to_string())), 0),
+            (
+                Comment(crate::ast::Comment::Block(" b /* c */ ".to_string())),
+                7
+            ),
+            (Comment(crate::ast::Comment::Line("// d".to_string())), 23),
+        ]
+    );
+    let tokens = lex("/* a\n b */ x /// y\n").unwrap();
+    assert_eq!((tokens[0].pos.line, tokens[0].pos.col), (2, 7));
+    assert_eq!((tokens[1].pos.line, tokens[1].pos.col), (2, 9));
+    assert_eq!(tokens[1].end, 18);
+}
+
+#[test]
+fn lex_positions() {
+    let tokens = lex("{\n  let é = 1;\n  é <= 2\n}").unwrap();
+    let at = |i: usize| {
+        let t: &Token = &tokens[i];
+        (t.pos.line, t.pos.col, t.pos.offset, t.end)
+    };
+    // `let`, `é` (two bytes, one column), `<=` and the end
+    assert_eq!(at(1), (2, 3, 4, 7));
+    assert_eq!(at(2), (2, 7, 8, 10));
+    assert_eq!(at(7), (3, 5, 21, 23));
+    let end = Pos {
+        offset: 27,
+        line: 4,
+        col: 2,
+    };
+    assert_eq!(tokens.last().unwrap().pos, end);
+}
+
+#[test]
+fn lex_errors() {
+    let err = |src: &str| lex(src).unwrap_err().to_string();
+    assert_eq!(err("'unterminated"), "1:1: unterminated character literal");
+    assert_eq!(err("1 +\n  # 2"), "2:3: unexpected character `#`");
+    assert_eq!(err("x = \"abc"), "1:5: unterminated string literal");
+    assert_eq!(err("''"), "1:1: empty or unterminated character literal");
+    assert_eq!(err("'\\q'"), "1:2: unknown escape `\\q`");
+    assert_eq!(err("\"\\u{110000}\""), "1:2: invalid unicode");
+    assert_eq!(err("1 /* a /* b */"), "1:3: unterminated block comment");
+}
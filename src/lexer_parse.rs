@@ -0,0 +1,857 @@
+// A parser over the tokens of `src/lexer.rs`.
+//
+// The same language as the `syn` parsers in `src/parse.rs`, giving the
+// same (correctly associated) trees, plus `<>` as an alternative to
+// `!=`. Binary operators are parsed as by the Pratt parser
+// (`src/pratt.rs`). Errors are at the line and column of the offending
+// token. The trees have default spans, as there are no `proc_macro2`
+// spans for the tokens.
+use crate::{
+    ast::{
+        Arm, Builtin, Comment, Documented, EnumDecl, Expr, ExprFile, Item, Literal, Member, Op,
+        Pattern, Program, Span, Stmt, StructDecl, MAX_REPEAT,
+    },
+    lexer::{lex, lex_comments, Error, Pos, Token, TokenKind},
+    typecheck::Type,
+};
+use std::{convert::TryFrom, str::FromStr};
+
+type Result<T> = std::result::Result<T, Error>;
+
+// Not identifiers
+const KEYWORDS: &[&str] = &[
+    "as", "enum", "false", "let", "match", "mut", "struct", "true", "_",
+];
+
+pub struct Parser<'a> {
+    tokens: &'a [Token],
+    next: usize,
+}
+
+// Parse a program from source
+pub fn program(src: &str) -> Result<Program> {
+    let tokens = lex(src)?;
+    let mut p = Parser::new(&tokens);
+    let program = p.program()?;
+    p.end()?;
+    Ok(program)
+}
+
+// Parse a file of documented expressions from source, keeping the
+// plain comments
+pub fn file(src: &str) -> Result<ExprFile> {
+    let (tokens, comments) = lex_comments(src)?;
+    Parser::new(&tokens).file(&comments)
+}
+
+// The file API, e.g., `src.parse::<ExprFile>()`, keeping the doc and the
+// plain comments
+impl FromStr for ExprFile {
+    type Err = Error;
+
+    fn from_str(src: &str) -> Result<Self> {
+        file(src)
+    }
+}
+
+// Parse an expression from source
+pub fn expr(src: &str) -> Result<Expr> {
+    let tokens = lex(src)?;
+    let mut p = Parser::new(&tokens);
+    let e = p.expr()?;
+    p.end()?;
+    Ok(e)
+}
+
+// Past the tokens, when they do not end with `Eof`
+static EOF: Token = Token {
+    kind: TokenKind::Eof,
+    pos: Pos {
+        offset: 0,
+        line: 1,
+        col: 1,
+    },
+    end: 0,
+};
+
+impl<'a> Parser<'a> {
+    // The tokens should end with `Eof`, as given by `lex`, for the
+    // position of end of input errors
+    pub fn new(tokens: &'a [Token]) -> Self {
+        Parser { tokens, next: 0 }
+    }
+
+    fn peek(&self) -> &'a Token {
+        self.tokens.get(self.next).unwrap_or(&EOF)
+    }
+
+    fn peek2(&self) -> &'a TokenKind {
+        &self.tokens.get(self.next + 1).unwrap_or(&EOF).kind
+    }
+
+    fn bump(&mut self) -> &'a Token {
+        let t = self.peek();
+        if t.kind != TokenKind::Eof {
+            self.next += 1;
+        }
+        t
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T> {
+        let t = self.peek();
+        Err(Error::new(
+            format!("expected {}, found {}", expected, t.kind),
+            t.pos,
+        ))
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Punct(q) if *q == p)
+    }
+
+    fn is_keyword(&self, k: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Ident(x) if x == k)
+    }
+
+    // Consume the punctuation if next
+    fn eat(&mut self, p: &str) -> bool {
+        let found = self.is_punct(p);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    fn expect(&mut self, p: &str) -> Result<()> {
+        if self.eat(p) {
+            Ok(())
+        } else {
+            self.error(&format!("`{}`", p))
+        }
+    }
+
+    fn eat_keyword(&mut self, k: &str) -> bool {
+        let found = self.is_keyword(k);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    fn is_ident(&self) -> bool {
+        matches!(&self.peek().kind, TokenKind::Ident(x) if !KEYWORDS.contains(&x.as_str()))
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match &self.peek().kind {
+            TokenKind::Ident(x) if !KEYWORDS.contains(&x.as_str()) => {
+                self.bump();
+                Ok(x.clone())
+            }
+            _ => self.error("an identifier"),
+        }
+    }
+
+    // All tokens consumed
+    pub fn end(&self) -> Result<()> {
+        match self.peek().kind {
+            TokenKind::Eof => Ok(()),
+            _ => self.error("an operator or end of input"),
+        }
+    }
+
+    // `f` separated by `,` (optionally trailing) up to the `close`
+    fn list<T>(
+        &mut self,
+        close: &str,
+        mut f: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let mut items = vec![];
+        while !self.eat(close) {
+            items.push(f(self)?);
+            if !self.is_punct(close) {
+                self.expect(",")?;
+            }
+        }
+        Ok(items)
+    }
+
+    // The items followed by an expression
+    pub fn program(&mut self) -> Result<Program> {
+        let mut items = vec![];
+        loop {
+            if self.is_keyword("struct") {
+                items.push(Item::Struct(self.struct_decl()?));
+            } else if self.is_keyword("enum") {
+                items.push(Item::Enum(self.enum_decl()?));
+            } else {
+                break;
+            }
+        }
+        let expr = self.expr()?;
+        Ok(Program { items, expr })
+    }
+
+    // Expressions separated by `;` (optionally trailing) up to the end,
+    // each after its doc comments. The plain comments (as given by
+    // `lex_comments`) go with the expression they are before or within,
+    // or after on the line of the `;`.
+    pub fn file(&mut self, comments: &[Token]) -> Result<ExprFile> {
+        let mut comments = comments.iter().peekable();
+        // the next comments at positions satisfying `f`
+        let mut comments_while = |f: &dyn Fn(Pos) -> bool| -> Vec<(Pos, Comment)> {
+            std::iter::from_fn(|| match comments.peek() {
+                Some(Token {
+                    kind: TokenKind::Comment(c),
+                    pos,
+                    ..
+                }) if f(*pos) => {
+                    comments.next();
+                    Some((*pos, c.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+        };
+        let without_pos = |comments: Vec<(Pos, Comment)>| comments.into_iter().map(|(_, c)| c);
+        let all = self.tokens;
+        let mut exprs = vec![];
+        while self.peek().kind != TokenKind::Eof {
+            let start = self.peek().pos.offset;
+            let before = without_pos(comments_while(&|pos| pos.offset < start)).collect();
+            let mut docs = vec![];
+            while let TokenKind::Doc(doc) = &self.peek().kind {
+                docs.push(doc.clone());
+                self.bump();
+            }
+            let first = self.next;
+            let expr = self.expr()?;
+            let tokens = &all[first..self.next];
+            // the comments up to the `;`, or the end of the last token
+            let (end, bound) = if self.is_punct(";") {
+                let end = self.bump();
+                (end, end.pos.offset)
+            } else {
+                self.end()?;
+                let end = &tokens[tokens.len() - 1];
+                (end, end.end)
+            };
+            let inside = comments_while(&|pos| pos.offset < bound)
+                .into_iter()
+                .map(|(pos, c)| {
+                    let n = tokens.iter().take_while(|t| t.pos.offset < pos.offset);
+                    (n.count(), c)
+                })
+                .collect();
+            let line = end.pos.line;
+            let trailing = without_pos(comments_while(&|pos| pos.line == line)).collect();
+            exprs.push(Documented {
+                before,
+                docs,
+                expr,
+                inside,
+                trailing,
+            });
+        }
+        Ok(ExprFile {
+            exprs,
+            comments: without_pos(comments_while(&|_| true)).collect(),
+        })
+    }
+
+    fn struct_decl(&mut self) -> Result<StructDecl> {
+        self.bump();
+        let name = self.ident()?;
+        self.expect("{")?;
+        let fields = self.list("}", |p| {
+            let f = p.ident()?;
+            p.expect(":")?;
+            Ok((f, p.decl_type()?))
+        })?;
+        Ok(StructDecl {
+            name,
+            fields,
+            span: Span::default(),
+        })
+    }
+
+    fn enum_decl(&mut self) -> Result<EnumDecl> {
+        self.bump();
+        let name = self.ident()?;
+        self.expect("{")?;
+        let variants = self.list("}", |p| {
+            let v = p.ident()?;
+            let mut tys = vec![];
+            if p.eat("(") {
+                tys = p.list(")", Parser::decl_type)?;
+            }
+            Ok((v, tys))
+        })?;
+        Ok(EnumDecl {
+            name,
+            variants,
+            span: Span::default(),
+        })
+    }
+
+    // A built in type
+    fn ty(&mut self) -> Result<Type> {
+        let t = self.peek();
+        match &t.kind {
+            TokenKind::Ident(x) => {
+                self.bump();
+                Type::from_name(x).ok_or_else(|| Error::new(format!("unknown type `{}`", x), t.pos))
+            }
+            _ => self.error("a type"),
+        }
+    }
+
+    // A type in a declaration, as `parse_decl_type`
+    fn decl_type(&mut self) -> Result<Type> {
+        if self.eat("(") {
+            Ok(Type::Tuple(self.list(")", Parser::decl_type)?))
+        } else if self.eat("[") {
+            let t = self.decl_type()?;
+            self.expect(";")?;
+            let n = self.length()?;
+            self.expect("]")?;
+            Ok(Type::Array(Box::new(t), n))
+        } else {
+            let name = self.ident()?;
+            Ok(Type::from_name(&name).unwrap_or(Type::Struct(name)))
+        }
+    }
+
+    // An unsuffixed integer, e.g., an array length
+    fn length(&mut self) -> Result<usize> {
+        let t = self.peek();
+        match &t.kind {
+            TokenKind::Int(n, s) if s.is_empty() => {
+                self.bump();
+                n.parse().map_err(|_| Error::new("number too large", t.pos))
+            }
+            _ => self.error("a length"),
+        }
+    }
+
+    // `d` followed by a string, a date literal
+    fn date_lit(&self) -> bool {
+        self.is_keyword("d") && matches!(self.peek2(), TokenKind::Str(_))
+    }
+
+    // A literal, `-` is part of number literals
+    fn literal(&mut self) -> Result<Literal> {
+        if self.date_lit() {
+            self.bump();
+            let t = self.bump();
+            return match &t.kind {
+                TokenKind::Str(s) => s
+                    .parse()
+                    .map(Literal::Date)
+                    .map_err(|err| Error::new(err.to_string(), t.pos)),
+                _ => unreachable!(),
+            };
+        }
+        let neg =
+            self.is_punct("-") && matches!(self.peek2(), TokenKind::Int(..) | TokenKind::Float(..));
+        if neg {
+            self.bump();
+        }
+        let t = self.peek();
+        let sign = if neg { "-" } else { "" };
+        let l = match &t.kind {
+            TokenKind::Int(n, s) => int(&format!("{}{}", sign, n), s),
+            TokenKind::Float(n, s) => float(&format!("{}{}", sign, n), s),
+            TokenKind::Char(c) => Ok(Literal::Char(*c)),
+            TokenKind::Str(s) => Ok(Literal::Str(s.clone())),
+            TokenKind::Ident(x) if x == "true" => Ok(Literal::Bool(true)),
+            TokenKind::Ident(x) if x == "false" => Ok(Literal::Bool(false)),
+            _ => return self.error("an expression"),
+        };
+        self.bump();
+        l.map_err(|msg| Error::new(msg, t.pos))
+    }
+
+    // `_`, a literal, a binding `x`, or `Enum::Variant(p, ...)`
+    fn pattern(&mut self) -> Result<Pattern> {
+        if self.eat_keyword("_") {
+            Ok(Pattern::Wild)
+        } else if self.is_ident() && *self.peek2() == TokenKind::Punct("::") {
+            let e = self.ident()?;
+            self.bump();
+            let v = self.ident()?;
+            let mut ps = vec![];
+            if self.eat("(") {
+                ps = self.list(")", Parser::pattern)?;
+            }
+            Ok(Pattern::Variant(e, v, ps))
+        } else if self.is_ident() && !self.date_lit() {
+            Ok(Pattern::Bind(self.ident()?))
+        } else {
+            Ok(Pattern::Lit(self.literal()?))
+        }
+    }
+
+    // An expression, correctly associated
+    pub fn expr(&mut self) -> Result<Expr> {
+        self.expr_min(0)
+    }
+
+    // An expression of operators with priority `min` or higher
+    fn expr_min(&mut self, min: u8) -> Result<Expr> {
+        let mut left = self.operand()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Punct(p) => match op(p) {
+                    Some(op) if op.priority() >= min => op,
+                    _ => return Ok(left),
+                },
+                _ => return Ok(left),
+            };
+            self.bump();
+            let right = self.expr_min(op.priority() + 1)?;
+            left = Expr::BinOp(op, Box::new(left), Box::new(right), Span::default());
+        }
+    }
+
+    // A unary expression followed by casts
+    fn operand(&mut self) -> Result<Expr> {
+        let mut left = self.unary()?;
+        while self.eat_keyword("as") {
+            let ty = self.ty()?;
+            left = Expr::Cast(Box::new(left), ty, Span::default());
+        }
+        Ok(left)
+    }
+
+    // `*e`, `&e` or `&mut e`
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("*") {
+            Ok(Expr::Deref(Box::new(self.unary()?), Span::default()))
+        } else if self.eat("&&") {
+            let mutable = self.eat_keyword("mut");
+            let e = Expr::Ref(mutable, Box::new(self.unary()?), Span::default());
+            Ok(Expr::Ref(false, Box::new(e), Span::default()))
+        } else if self.eat("&") {
+            let mutable = self.eat_keyword("mut");
+            Ok(Expr::Ref(mutable, Box::new(self.unary()?), Span::default()))
+        } else {
+            self.postfix()
+        }
+    }
+
+    // A primary expression followed by indexing and field access
+    fn postfix(&mut self) -> Result<Expr> {
+        let mut left = self.primary()?;
+        loop {
+            if self.eat("[") {
+                let i = self.expr()?;
+                self.expect("]")?;
+                left = Expr::Index(Box::new(left), Box::new(i), Span::default());
+            } else if self.eat(".") {
+                let t = self.peek();
+                let member = match &t.kind {
+                    TokenKind::Int(n, s) if s.is_empty() => Member::Index(
+                        n.parse()
+                            .map_err(|_| Error::new("number too large", t.pos))?,
+                    ),
+                    TokenKind::Ident(_) if self.is_ident() => Member::Named(self.ident()?),
+                    _ => return self.error("a field"),
+                };
+                if let Member::Index(_) = member {
+                    self.bump();
+                }
+                left = Expr::Field(Box::new(left), member, Span::default());
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        if self.eat("(") {
+            // `(e)` is parenthesized, `()`, `(e,)` and `(e1, e2, ...)` are tuples
+            if self.eat(")") {
+                return Ok(Expr::Tuple(vec![]));
+            }
+            let e = self.expr()?;
+            if self.eat(")") {
+                return Ok(Expr::Par(Box::new(e)));
+            }
+            self.expect(",")?;
+            let rest = self.list(")", Parser::expr)?;
+            Ok(Expr::Tuple(std::iter::once(e).chain(rest).collect()))
+        } else if self.eat("[") {
+            // `[e1, e2, ...]` or `[e; n]`
+            if self.eat("]") {
+                return Ok(Expr::Array(vec![], Span::default()));
+            }
+            let e = self.expr()?;
+            if self.eat(";") {
+                let pos = self.peek().pos;
+                let n = self.length()?;
+                if n > MAX_REPEAT {
+                    let msg = format!("array length `{}` exceeds the limit {}", n, MAX_REPEAT);
+                    return Err(Error::new(msg, pos));
+                }
+                self.expect("]")?;
+                return Ok(Expr::Repeat(Box::new(e), n));
+            }
+            let mut es = vec![e];
+            if !self.eat("]") {
+                self.expect(",")?;
+                es.extend(self.list("]", Parser::expr)?);
+            }
+            Ok(Expr::Array(es, Span::default()))
+        } else if self.is_keyword("match") {
+            self.match_expr()
+        } else if self.is_punct("{") {
+            self.block()
+        } else if self.is_ident() && !self.date_lit() {
+            let t = self.peek();
+            let x = self.ident()?;
+            match self.peek().kind {
+                TokenKind::Punct("::") => {
+                    // a variant `Enum::Variant(e, ...)` or `Enum::Variant`
+                    self.bump();
+                    let v = self.ident()?;
+                    let mut args = vec![];
+                    if self.eat("(") {
+                        args = self.list(")", Parser::expr)?;
+                    }
+                    Ok(Expr::Variant(x, v, args, Span::default()))
+                }
+                TokenKind::Punct("{") if self.struct_lit() => {
+                    // a struct literal `Name { field: e, ... }`
+                    self.bump();
+                    let fields = self.list("}", |p| {
+                        let f = p.ident()?;
+                        p.expect(":")?;
+                        Ok((f, p.expr()?))
+                    })?;
+                    Ok(Expr::Struct(x, fields, Span::default()))
+                }
+                TokenKind::Punct("(") => {
+                    // a call `f(e, ...)`
+                    let f = Builtin::from_name(&x)
+                        .ok_or_else(|| Error::new(format!("unknown function `{}`", x), t.pos))?;
+                    self.bump();
+                    Ok(Expr::Call(
+                        f,
+                        self.list(")", Parser::expr)?,
+                        Span::default(),
+                    ))
+                }
+                // a variable bound by a pattern
+                _ => Ok(Expr::Var(x, Span::default())),
+            }
+        } else {
+            Ok(self.literal()?.into())
+        }
+    }
+
+    // Whether the `{` after a name starts a struct literal rather than the
+    // arms of a `match`, i.e., `{}` or `{ field:`
+    fn struct_lit(&self) -> bool {
+        let kind = |i: usize| self.tokens.get(self.next + i).map(|t| &t.kind);
+        match kind(1) {
+            Some(TokenKind::Punct("}")) => true,
+            Some(TokenKind::Ident(_)) => kind(2) == Some(&TokenKind::Punct(":")),
+            _ => false,
+        }
+    }
+
+    // `match e { pat => e, ... }`
+    fn match_expr(&mut self) -> Result<Expr> {
+        self.bump();
+        let e = self.expr()?;
+        self.expect("{")?;
+        let arms = self.list("}", |p| {
+            let pat = p.pattern()?;
+            p.expect("=>")?;
+            let body = p.expr()?;
+            Ok(Arm {
+                pat,
+                body,
+                span: Span::default(),
+            })
+        })?;
+        Ok(Expr::Match(Box::new(e), arms, Span::default()))
+    }
+
+    // `{ stmt; ... e }`, as `parse_block`
+    fn block(&mut self) -> Result<Expr> {
+        self.expect("{")?;
+        let mut stmts = vec![];
+        let block = |stmts, e| Ok(Expr::Block(stmts, Box::new(e), Span::default()));
+        loop {
+            if self.eat("}") {
+                return block(stmts, Expr::Tuple(vec![]));
+            }
+            if self.eat_keyword("let") {
+                let mutable = self.eat_keyword("mut");
+                let x = self.ident()?;
+                self.expect("=")?;
+                let e = self.expr()?;
+                self.expect(";")?;
+                stmts.push(Stmt::Let(x, mutable, e, Span::default()));
+                continue;
+            }
+            if self.is_punct("{") || self.is_keyword("match") {
+                let e = if self.is_keyword("match") {
+                    self.match_expr()?
+                } else {
+                    self.block()?
+                };
+                if self.eat("}") {
+                    return block(stmts, e);
+                }
+                self.eat(";");
+                stmts.push(Stmt::Expr(e));
+                continue;
+            }
+            let e = self.expr()?;
+            if self.eat("=") {
+                let rhs = self.expr()?;
+                self.expect(";")?;
+                stmts.push(Stmt::Assign(e, rhs, Span::default()));
+            } else if self.eat(";") {
+                stmts.push(Stmt::Expr(e));
+            } else if self.eat("}") {
+                return block(stmts, e);
+            } else {
+                return self.error("`;`");
+            }
+        }
+    }
+}
+
+// The binary operator, `<>` is `!=`
+fn op(p: &str) -> Option<Op> {
+    Some(match p {
+        "+" => Op::Add,
+        "-" => Op::Sub,
+        "*" => Op::Mul,
+        "/" => Op::Div,
+        "==" => Op::Eq,
+        "!=" | "<>" => Op::Ne,
+        "<=" => Op::Le,
+        ">=" => Op::Ge,
+        "<" => Op::Lt,
+        ">" => Op::Gt,
+        "&&" => Op::And,
+        "||" => Op::Or,
+        _ => return None,
+    })
+}
+
+// An integer literal, the type is given by the suffix
+fn int(n: &str, suffix: &str) -> std::result::Result<Literal, String> {
+    // decimal, or with a `0x`, `0o` or `0b` prefix, optionally negated
+    fn parse<T: TryFrom<i128> + TryFrom<u128>>(n: &str) -> std::result::Result<T, String> {
+        let range = || "integer literal is out of range".to_string();
+        let (neg, n) = match n.strip_prefix('-') {
+            Some(n) => (true, n),
+            None => (false, n),
+        };
+        let (radix, digits) = match n.get(..2) {
+            Some("0x") => (16, &n[2..]),
+            Some("0o") => (8, &n[2..]),
+            Some("0b") => (2, &n[2..]),
+            _ => (10, n),
+        };
+        let mag = u128::from_str_radix(digits, radix).map_err(|err| match err.kind() {
+            std::num::IntErrorKind::PosOverflow => range(),
+            _ => format!("invalid digits `{}` for base {}", digits, radix),
+        })?;
+        if neg {
+            let i = 0i128.checked_sub_unsigned(mag).ok_or_else(range)?;
+            T::try_from(i).map_err(|_| range())
+        } else {
+            T::try_from(mag).map_err(|_| range())
+        }
+    }
+    Ok(match suffix {
+        // unbounded
+        #[cfg(feature = "bigint")]
+        "" => n
+            .parse::<crate::bigint::BigInt>()
+            .map_err(|_| format!("invalid integer literal `{}`", n))?
+            .into(),
+        #[cfg(not(feature = "bigint"))]
+        "" => Literal::Int(parse(n)?),
+        "i32" => Literal::Int(parse(n)?),
+        "i8" => Literal::I8(parse(n)?),
+        "i16" => Literal::I16(parse(n)?),
+        "i64" => Literal::I64(parse(n)?),
+        "i128" => Literal::I128(parse(n)?),
+        "u8" => Literal::U8(parse(n)?),
+        "u16" => Literal::U16(parse(n)?),
+        "u32" => Literal::U32(parse(n)?),
+        "u64" => Literal::U64(parse(n)?),
+        "u128" => Literal::U128(parse(n)?),
+        "f32" | "f64" => float(n, suffix)?,
+        s => return Err(format!("invalid suffix `{}` for integer literal", s)),
+    })
+}
+
+fn float(n: &str, suffix: &str) -> std::result::Result<Literal, String> {
+    let invalid = |_| "invalid float literal".to_string();
+    Ok(match suffix {
+        "" | "f64" => Literal::F64(n.parse().map_err(invalid)?),
+        "f32" => Literal::F32(n.parse().map_err(invalid)?),
+        s => return Err(format!("invalid suffix `{}` for float literal", s)),
+    })
+}
+
+#[test]
+fn lexer_parse_corpus() {
+    use crate::pratt::Engine;
+    let derefs = [
+        "{ let x = 1; let r = &x; let rr = &r; **rr }",
+        "{ let x = 2; let r = &x; 3**r }",
+        "{ let x = 2; let r = &x; let rr = &r; 1 + 3**r as i32**&r - **rr * 2 }",
+    ];
+    let sources = crate::pratt::corpus()
+        .into_iter()
+        .chain(derefs.iter().map(|s| s.to_string()));
+    for s in sources {
+        let c = Engine::Climb.parse_str(&s).unwrap();
+        let p = program(&s).unwrap();
+        assert_eq!(p.expr, c.expr, "{}", s);
+        assert_eq!(p.items, c.items, "{}", s);
+    }
+    for s in &["1 +", "(1 - )", "{ let x = 1 x }", "5 % 2"] {
+        assert!(program(s).is_err(), "{}", s);
+    }
+}
+
+#[test]
+fn lexer_parse_non_rust() {
+    // `<>` is `!=`
+    let e = expr("1 <> 2 && (3 <> 3) == false").unwrap();
+    assert_eq!(e.to_string(), "1 != 2 && (3 != 3) == false");
+    assert_eq!(e.eval(), Literal::Bool(true));
+    // nested tuple fields, and `-` in literals
+    let e = expr("((1, -2.5), 3).0.1 - -1.0").unwrap();
+    assert_eq!(e.eval(), Literal::F64(-1.5));
+    assert_eq!(expr("0xff_u8 - 0b1010u8").unwrap().eval(), Literal::U8(245));
+    assert_eq!(expr("-0x80i8").unwrap().eval(), Literal::I8(i8::MIN));
+    let e = expr(r#"d"2024-02-28" + 1 - d"2024-01-01""#).unwrap();
+    assert_eq!(e.eval(), Literal::Int(59));
+    // `**` is two `*`
+    let e = expr("{ let x = 2; let r = &x; 3**r * 2 }").unwrap();
+    assert_eq!(e.to_string(), "{ let x = 2; let r = &x; 3 * *r * 2 }");
+    assert_eq!(e.eval(), Literal::Int(12));
+}
+
+#[test]
+fn lexer_parse_file() {
+    use crate::ast::Comment;
+    let src = "// the sum\n/// The total\n1 + /* 2 + */ 3; // three\n\
+               /** Block\n docs */\n{ let x = 1; x };\n/* the end */\n";
+    let f: ExprFile = src.parse().unwrap();
+    let line = |s: &str| Comment::Line(s.to_string());
+    let block = |s: &str| Comment::Block(s.to_string());
+    assert_eq!(f.exprs[0].before, vec![line(" the sum")]);
+    assert_eq!(f.exprs[0].docs, vec![" The total"]);
+    assert_eq!(f.exprs[0].inside, vec![(2, block(" 2 + "))]);
+    assert_eq!(f.exprs[0].trailing, vec![line(" three")]);
+    assert!(f.exprs[1].before.is_empty());
+    assert_eq!(f.exprs[1].docs, vec![" Block\n docs "]);
+    assert_eq!(f.comments, vec![block(" the end ")]);
+    // the comments are printed where they were
+    assert_eq!(f.to_string(), src);
+    // within and after, before the `;` and without it
+    let src = "/* a */ [1, // b\n2] /* c */; /* d */ // e\n(3 /* f */) // g\n// h\n";
+    let f = file(src).unwrap();
+    assert_eq!(f.exprs[0].before, vec![block(" a ")]);
+    assert_eq!(f.exprs[0].inside, vec![(3, line(" b")), (5, block(" c "))]);
+    assert_eq!(f.exprs[0].trailing, vec![block(" d "), line(" e")]);
+    assert_eq!(f.exprs[1].inside, vec![(2, block(" f "))]);
+    assert_eq!(f.exprs[1].trailing, vec![line(" g")]);
+    assert_eq!(f.comments, vec![line(" h")]);
+    assert_eq!(
+        f.to_string(),
+        "/* a */\n[1, // b\n2] /* c */; /* d */ // e\n(3 /* f */); // g\n// h\n"
+    );
+    // after the doc comments, within
+    for src in ["/// d\n// x\n1;\n", "/// d\n/* x */ 1 /* y */;\n"] {
+        let f = file(src).unwrap();
+        assert_eq!(f.exprs[0].inside[0].0, 0);
+        assert_eq!(f.to_string(), src);
+    }
+    // printed back, the same file
+    let g = file(&f.to_string()).unwrap();
+    assert_eq!(g, f);
+    assert_eq!(g.to_string(), f.to_string());
+    // as by `syn`, without the plain comments
+    let mut h: ExprFile = syn::parse_str(src).unwrap();
+    assert!(h.exprs.iter().all(|e| e.inside.is_empty()));
+    assert!(h.comments.is_empty());
+    h.exprs.iter_mut().zip(&f.exprs).for_each(|(h, f)| {
+        h.before = f.before.clone();
+        h.inside = f.inside.clone();
+        h.trailing = f.trailing.clone();
+    });
+    h.comments = f.comments.clone();
+    assert_eq!(h, f);
+    assert!(file("").unwrap().exprs.is_empty());
+    let err = |src: &str| file(src).unwrap_err().to_string();
+    assert_eq!(
+        err("1 + 2 /// no\n"),
+        "1:7: expected an operator or end of input, found a doc comment"
+    );
+    assert_eq!(
+        err("{\n  /// no\n  1 }"),
+        "2:3: expected an expression, found a doc comment"
+    );
+    assert_eq!(
+        err("1;\n/// dangling"),
+        "2:13: expected an expression, found end of input"
+    );
+}
+
+#[test]
+fn lexer_parse_errors() {
+    let err = |src: &str| program(src).unwrap_err().to_string();
+    assert_eq!(
+        err("1 +"),
+        "1:4: expected an expression, found end of input"
+    );
+    assert_eq!(
+        err("{\n  let x = 1\n  x\n}"),
+        "3:3: expected `;`, found `x`"
+    );
+    assert_eq!(
+        err("1 2"),
+        "1:3: expected an operator or end of input, found `2`"
+    );
+    assert_eq!(err("(1, 2"), "1:6: expected `,`, found end of input");
+    assert_eq!(err("1 as usize"), "1:6: unknown type `usize`");
+    assert_eq!(err("foo(1)"), "1:1: unknown function `foo`");
+    assert_eq!(err("let = 1"), "1:1: expected an expression, found `let`");
+    assert_eq!(err("300u8"), "1:1: integer literal is out of range");
+    assert_eq!(err("0b102u8"), "1:1: invalid digits `102` for base 2");
+    assert_eq!(err("1 + 'x"), "1:5: unterminated character literal");
+    assert_eq!(
+        err("[0;\n 100000000000]"),
+        "2:2: array length `100000000000` exceeds the limit 65536"
+    );
+    assert_eq!(
+        err("d\"2024-02-28\" +\n  d\"2023-02-29\""),
+        "2:4: invalid day `29`, February 2023 has 28 days"
+    );
+    // tokens without `Eof` end as if they had one
+    let err = Parser::new(&[]).expr().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1:1: expected an expression, found end of input"
+    );
+    let tokens = lex("1 +").unwrap();
+    let err = Parser::new(&tokens[..2]).expr().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1:1: expected an expression, found end of input"
+    );
+}
@@ -1,2 +1,22 @@
 pub mod ast;
+pub mod backend;
+pub mod bigint;
+pub mod borrowck;
+pub mod date;
+pub mod derive;
+pub mod generate;
+pub mod grammar;
+pub mod lexer;
+pub mod lexer_parse;
 pub mod parse;
+pub mod pratt;
+pub mod regex_lexer;
+pub mod rpn;
+pub mod sos;
+pub mod syn_expr;
+pub mod typecheck;
+pub mod typing;
+
+// Whether `Int` is unbounded (the `bigint` feature), dependent crates
+// (e.g., `expr_macro`) may not enable the feature themselves
+pub const BIGINT: bool = cfg!(feature = "bigint");
@@ -1,8 +1,14 @@
-use crate::ast::{
-    Expr::{self, *},
-    Literal, Op,
+use crate::{
+    ast::{
+        Arm, Builtin, Documented, EnumDecl,
+        Expr::{self, *},
+        ExprFile, Literal, Member, Op, Pattern, Program, Span, Stmt, StructDecl, MAX_REPEAT,
+    },
+    pratt::Engine,
+    typecheck::Type,
 };
 
+use std::convert::TryFrom;
 use syn::{
     parse::{Parse, ParseStream},
     Result, Token,
@@ -10,14 +16,84 @@ use syn::{
 
 impl Parse for Literal {
     fn parse(input: ParseStream) -> Result<Self> {
+        if date_lit(input) {
+            // `d"2024-02-29"`, checked against the calendar
+            let _: syn::Ident = input.parse()?;
+            let s: syn::LitStr = input.parse()?;
+            let d = s
+                .value()
+                .parse()
+                .map_err(|err| syn::Error::new(s.span(), err))?;
+            return Ok(Literal::Date(d));
+        }
         // Use the "built in" syn parser for literals
         let l: syn::Lit = input.parse()?;
+        Literal::try_from(l)
+    }
+}
+
+// `d` followed by a string, a date literal
+fn date_lit(input: ParseStream) -> bool {
+    input.peek(syn::Ident)
+        && input.peek2(syn::LitStr)
+        && matches!(input.fork().parse::<syn::Ident>(), Ok(d) if d == "d")
+}
+
+impl TryFrom<syn::Lit> for Literal {
+    type Error = syn::Error;
 
+    fn try_from(l: syn::Lit) -> Result<Self> {
         Ok(match l {
-            syn::Lit::Int(l) => Literal::Int(l.base10_parse().unwrap()),
+            // the integer type is given by the suffix, i32 by default
+            syn::Lit::Int(l) => match l.suffix() {
+                // unbounded, parsed from the source digits
+                #[cfg(feature = "bigint")]
+                "" => {
+                    let digits = l.to_string();
+                    let i: crate::bigint::BigInt = digits
+                        .parse()
+                        .map_err(|err| syn::Error::new(l.span(), err))?;
+                    i.into()
+                }
+                #[cfg(not(feature = "bigint"))]
+                "" => Literal::Int(l.base10_parse()?),
+                "i32" => Literal::Int(l.base10_parse()?),
+                "i8" => Literal::I8(l.base10_parse()?),
+                "i16" => Literal::I16(l.base10_parse()?),
+                "i64" => Literal::I64(l.base10_parse()?),
+                "i128" => Literal::I128(l.base10_parse()?),
+                "u8" => Literal::U8(l.base10_parse()?),
+                "u16" => Literal::U16(l.base10_parse()?),
+                "u32" => Literal::U32(l.base10_parse()?),
+                "u64" => Literal::U64(l.base10_parse()?),
+                "u128" => Literal::U128(l.base10_parse()?),
+                // e.g., `1f32`, depending on the lexer this is an integer
+                // literal with a float suffix
+                "f32" => Literal::F32(l.base10_parse()?),
+                "f64" => Literal::F64(l.base10_parse()?),
+                s => {
+                    return Err(syn::Error::new(
+                        l.span(),
+                        format!("invalid suffix `{}` for integer literal", s),
+                    ))
+                }
+            },
+            // the float type is given by the suffix, f64 by default
+            syn::Lit::Float(l) => match l.suffix() {
+                "" | "f64" => Literal::F64(l.base10_parse()?),
+                "f32" => Literal::F32(l.base10_parse()?),
+                s => {
+                    return Err(syn::Error::new(
+                        l.span(),
+                        format!("invalid suffix `{}` for float literal", s),
+                    ))
+                }
+            },
             syn::Lit::Bool(b) => Literal::Bool(b.value),
-            // for now only Int and Bool are covered
-            _ => unimplemented!(),
+            syn::Lit::Char(c) => Literal::Char(c.value()),
+            syn::Lit::Str(s) => Literal::Str(s.value()),
+            // byte strings etc.
+            l => return Err(syn::Error::new(l.span(), "unsupported literal")),
         })
     }
 }
@@ -36,6 +112,107 @@ fn parse_lit_bool_false() {
     assert_eq!(l, Literal::Bool(false));
 }
 
+#[test]
+fn parse_lit_suffix() {
+    let ts: proc_macro2::TokenStream = "5u8".parse().unwrap();
+    let l: Literal = syn::parse2(ts).unwrap();
+    assert_eq!(l, Literal::U8(5));
+    let ts: proc_macro2::TokenStream = "-170141183460469231731687303715884105728i128"
+        .parse()
+        .unwrap();
+    let l: Literal = syn::parse2(ts).unwrap();
+    assert_eq!(l, Literal::I128(i128::MIN));
+}
+
+#[test]
+fn parse_lit_out_of_range() {
+    // does not fit the default i32 (unless it is unbounded)
+    let ts: proc_macro2::TokenStream = "3_000_000_000".parse().unwrap();
+    #[cfg(not(feature = "bigint"))]
+    assert!(syn::parse2::<Literal>(ts).is_err());
+    #[cfg(feature = "bigint")]
+    assert_eq!(
+        syn::parse2::<Literal>(ts).unwrap().to_string(),
+        "3000000000"
+    );
+    let ts: proc_macro2::TokenStream = "3_000_000_000u32".parse().unwrap();
+    let l: Literal = syn::parse2(ts).unwrap();
+    assert_eq!(l, Literal::U32(3_000_000_000));
+    let ts: proc_macro2::TokenStream = "256u8".parse().unwrap();
+    assert!(syn::parse2::<Literal>(ts).is_err());
+    let ts: proc_macro2::TokenStream = "1u7".parse().unwrap();
+    assert!(syn::parse2::<Literal>(ts).is_err());
+}
+
+#[test]
+fn parse_lit_float() {
+    let ts: proc_macro2::TokenStream = "1.5".parse().unwrap();
+    let l: Literal = syn::parse2(ts).unwrap();
+    assert_eq!(l, Literal::F64(1.5));
+    let ts: proc_macro2::TokenStream = "-2.5e-3f32".parse().unwrap();
+    let l: Literal = syn::parse2(ts).unwrap();
+    assert_eq!(l, Literal::F32(-2.5e-3));
+    let ts: proc_macro2::TokenStream = "1f32".parse().unwrap();
+    let l: Literal = syn::parse2(ts).unwrap();
+    assert_eq!(l, Literal::F32(1.0));
+}
+
+// Printing a float literal and parsing it back gives the exact same value
+#[test]
+fn float_display_round_trip() {
+    let values = [
+        0.1,
+        -0.0,
+        1.0,
+        1.0 / 3.0,
+        1e300,
+        f64::MIN_POSITIVE,
+        5e-324,
+        f64::MAX,
+        123456789.125,
+    ];
+    for v in values.iter() {
+        // large values are infinite as f32
+        for l in [Literal::F64(*v), Literal::F32((*v as f32).min(f32::MAX))] {
+            let ts: proc_macro2::TokenStream = l.to_string().parse().unwrap();
+            let back: Literal = syn::parse2(ts).unwrap();
+            println!("{} {:?}", l, back);
+            assert_eq!(back, l);
+            assert_eq!(back.to_string(), l.to_string());
+        }
+    }
+}
+
+#[test]
+fn float_display_non_finite() {
+    for l in [
+        Literal::F64(f64::INFINITY),
+        Literal::F64(f64::NEG_INFINITY),
+        Literal::F32(f32::INFINITY),
+    ] {
+        let ts: proc_macro2::TokenStream = l.to_string().parse().unwrap();
+        let e = climb(syn::parse2(ts).unwrap());
+        assert_eq!(e.eval(), l);
+    }
+    let ts: proc_macro2::TokenStream = Literal::F64(f64::NAN).to_string().parse().unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    assert!(matches!(e.eval(), Literal::F64(v) if v.is_nan()));
+}
+
+#[test]
+fn parse_lit_str() {
+    let ts: proc_macro2::TokenStream = r#""a\"b\n""#.parse().unwrap();
+    let l: Literal = syn::parse2(ts).unwrap();
+    assert_eq!(l, Literal::from("a\"b\n"));
+    // printing and parsing back gives the same string
+    let ts: proc_macro2::TokenStream = l.to_string().parse().unwrap();
+    assert_eq!(syn::parse2::<Literal>(ts).unwrap(), l);
+    let ts: proc_macro2::TokenStream = r"'\''".parse().unwrap();
+    assert_eq!(syn::parse2::<Literal>(ts).unwrap(), Literal::Char('\''));
+    let ts: proc_macro2::TokenStream = r#"b"bytes""#.parse().unwrap();
+    assert!(syn::parse2::<Literal>(ts).is_err());
+}
+
 #[test]
 fn parse_lit_fail() {
     let ts: proc_macro2::TokenStream = "a".parse().unwrap();
@@ -56,6 +233,34 @@ impl Parse for Op {
         } else if input.peek(Token![*]) {
             let _: Token![*] = input.parse()?;
             Ok(Op::Mul)
+        } else if input.peek(Token![/]) {
+            let _: Token![/] = input.parse()?;
+            Ok(Op::Div)
+        } else if input.peek(Token![==]) {
+            let _: Token![==] = input.parse()?;
+            Ok(Op::Eq)
+        } else if input.peek(Token![!=]) {
+            let _: Token![!=] = input.parse()?;
+            Ok(Op::Ne)
+        } else if input.peek(Token![<=]) {
+            // check `<=` before `<`, as peek only looks at the first character
+            let _: Token![<=] = input.parse()?;
+            Ok(Op::Le)
+        } else if input.peek(Token![>=]) {
+            let _: Token![>=] = input.parse()?;
+            Ok(Op::Ge)
+        } else if input.peek(Token![<]) {
+            let _: Token![<] = input.parse()?;
+            Ok(Op::Lt)
+        } else if input.peek(Token![>]) {
+            let _: Token![>] = input.parse()?;
+            Ok(Op::Gt)
+        } else if input.peek(Token![&&]) {
+            let _: Token![&&] = input.parse()?;
+            Ok(Op::And)
+        } else if input.peek(Token![||]) {
+            let _: Token![||] = input.parse()?;
+            Ok(Op::Or)
         } else {
             // to explicitly create an error at the current position
             input.step(|cursor| Err(cursor.error("expected operator")))
@@ -87,26 +292,225 @@ fn parse_op_fail() {
     assert!(err.is_err());
 }
 
+impl Parse for Builtin {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let f: syn::Ident = input.parse()?;
+        Builtin::from_name(&f.to_string())
+            .ok_or_else(|| syn::Error::new(f.span(), format!("unknown function `{}`", f)))
+    }
+}
+
+impl Parse for Type {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ty: syn::Ident = input.parse()?;
+        Type::from_name(&ty.to_string())
+            .ok_or_else(|| syn::Error::new(ty.span(), format!("unknown type `{}`", ty)))
+    }
+}
+
+#[test]
+fn parse_type() {
+    let ts: proc_macro2::TokenStream = "u16".parse().unwrap();
+    assert_eq!(syn::parse2::<Type>(ts).unwrap(), Type::U16);
+    let ts: proc_macro2::TokenStream = "usize".parse().unwrap();
+    assert!(syn::parse2::<Type>(ts).is_err());
+}
+
+// A type in a declaration, besides the built in types these
+// are struct names, tuples `(T, ...)` and arrays `[T; n]`
+fn parse_decl_type(input: ParseStream) -> Result<Type> {
+    if input.peek(syn::token::Paren) {
+        let content;
+        let _ = syn::parenthesized!(content in input);
+        let ts = content.parse_terminated::<_, Token![,]>(parse_decl_type)?;
+        Ok(Type::Tuple(ts.into_iter().collect()))
+    } else if input.peek(syn::token::Bracket) {
+        let content;
+        let _ = syn::bracketed!(content in input);
+        let t = parse_decl_type(&content)?;
+        let _: Token![;] = content.parse()?;
+        let n: syn::LitInt = content.parse()?;
+        Ok(Type::Array(Box::new(t), n.base10_parse()?))
+    } else {
+        let ty: syn::Ident = input.parse()?;
+        let name = ty.to_string();
+        Ok(Type::from_name(&name).unwrap_or(Type::Struct(name)))
+    }
+}
+
+impl Parse for StructDecl {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let _: Token![struct] = input.parse()?;
+        let name: syn::Ident = input.parse()?;
+        let content;
+        let _ = syn::braced!(content in input);
+        let fields = content.parse_terminated::<_, Token![,]>(|input| {
+            let f: syn::Ident = input.parse()?;
+            let _: Token![:] = input.parse()?;
+            Ok((f.to_string(), parse_decl_type(input)?))
+        })?;
+        Ok(StructDecl {
+            name: name.to_string(),
+            fields: fields.into_iter().collect(),
+            span: name.span().into(),
+        })
+    }
+}
+
+impl Parse for EnumDecl {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let _: Token![enum] = input.parse()?;
+        let name: syn::Ident = input.parse()?;
+        let content;
+        let _ = syn::braced!(content in input);
+        let variants = content.parse_terminated::<_, Token![,]>(|input| {
+            let v: syn::Ident = input.parse()?;
+            let mut tys = vec![];
+            if input.peek(syn::token::Paren) {
+                let content;
+                let _ = syn::parenthesized!(content in input);
+                tys = content
+                    .parse_terminated::<_, Token![,]>(parse_decl_type)?
+                    .into_iter()
+                    .collect();
+            }
+            Ok((v.to_string(), tys))
+        })?;
+        Ok(EnumDecl {
+            name: name.to_string(),
+            variants: variants.into_iter().collect(),
+            span: name.span().into(),
+        })
+    }
+}
+
+// `_`, a literal, a binding `x`, or `Enum::Variant(p, ...)`
+impl Parse for Pattern {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Token![_]) {
+            let _: Token![_] = input.parse()?;
+            Ok(Pattern::Wild)
+        } else if input.peek(syn::Ident) && input.peek2(Token![::]) {
+            let e: syn::Ident = input.parse()?;
+            let _: Token![::] = input.parse()?;
+            let v: syn::Ident = input.parse()?;
+            let mut ps = vec![];
+            if input.peek(syn::token::Paren) {
+                let content;
+                let _ = syn::parenthesized!(content in input);
+                ps = content
+                    .parse_terminated::<Pattern, Token![,]>(Pattern::parse)?
+                    .into_iter()
+                    .collect();
+            }
+            Ok(Pattern::Variant(e.to_string(), v.to_string(), ps))
+        } else if input.peek(syn::Ident) && !date_lit(input) {
+            let x: syn::Ident = input.parse()?;
+            Ok(Pattern::Bind(x.to_string()))
+        } else {
+            Ok(Pattern::Lit(input.parse()?))
+        }
+    }
+}
+
+// `pat => e`
+impl Parse for Arm {
+    fn parse(input: ParseStream) -> Result<Self> {
+        parse_arm::<RightAssoc>(input)
+    }
+}
+
+fn parse_arm<P: ExprParser>(input: ParseStream) -> Result<Arm> {
+    let span = input.span().into();
+    let pat = input.parse()?;
+    let _: Token![=>] = input.parse()?;
+    let body = P::expr(input)?;
+    Ok(Arm { pat, body, span })
+}
+
+// The items followed by an expression, which is climbed
+impl Parse for Program {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Engine::Climb.program(input)
+    }
+}
+
+// The (climbed) expressions separated by `;`, with their doc comments
+// only, as `proc_macro2` drops the plain comments (and gives the doc
+// comments as `#[doc = "..."]` attributes). Files are parsed by
+// `ExprFile::from_str` (`src/lexer_parse.rs`), keeping all comments.
+impl Parse for ExprFile {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut exprs = vec![];
+        while !input.is_empty() {
+            let mut docs = vec![];
+            for attr in input.call(syn::Attribute::parse_outer)? {
+                match attr.parse_meta()? {
+                    syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        lit: syn::Lit::Str(s),
+                        ..
+                    }) if path.is_ident("doc") => docs.push(s.value()),
+                    _ => return Err(syn::Error::new_spanned(attr, "expected a doc comment")),
+                }
+            }
+            let expr = Engine::Climb.expr(input)?;
+            exprs.push(Documented {
+                before: vec![],
+                docs,
+                expr,
+                inside: vec![],
+                trailing: vec![],
+            });
+            if !input.is_empty() {
+                let _: Token![;] = input.parse()?;
+            }
+        }
+        Ok(ExprFile {
+            exprs,
+            comments: vec![],
+        })
+    }
+}
+
+// The parser of the sub expressions (e.g., in parentheses and blocks),
+// `Expr::parse` giving right associative trees to be climbed, or the
+// Pratt parser (see `src/pratt.rs`)
+pub(crate) trait ExprParser {
+    fn expr(input: ParseStream) -> Result<Expr>;
+}
+
+pub(crate) struct RightAssoc;
+
+impl ExprParser for RightAssoc {
+    fn expr(input: ParseStream) -> Result<Expr> {
+        Expr::parse(input)
+    }
+}
+
+// An operand of a binary operator, a unary expression followed by casts
+pub(crate) fn parse_operand<P: ExprParser>(input: ParseStream) -> Result<Expr> {
+    let mut left = parse_unary::<P>(input)?;
+    // `as` binds harder than any binary operator
+    while input.peek(Token![as]) {
+        let as_token: Token![as] = input.parse()?;
+        let ty: Type = input.parse()?;
+        left = Cast(Box::new(left), ty, as_token.span.into());
+    }
+    Ok(left)
+}
+
 // Render a "right associative" AST
 impl Parse for Expr {
     // Use a custom parser for expressions
     fn parse(input: ParseStream) -> Result<Self> {
-        // check if we have a left `(Expr)`
-        let left = if input.peek(syn::token::Paren) {
-            let content;
-            let _ = syn::parenthesized!(content in input);
-            let e: Expr = content.parse()?;
-            Expr::Par(Box::new(e))
-        } else {
-            // else we require a left literal
-            let left: Literal = input.parse()?;
-            left.into()
-        };
+        let left = parse_operand::<RightAssoc>(input)?;
         // now check if right is an Op Expr
+        let span = input.span();
         match input.parse::<Op>() {
             Ok(op) => {
                 let right: Expr = input.parse()?;
-                Ok(Expr::bin_op(op, left, right))
+                Ok(BinOp(op, Box::new(left), Box::new(right), span.into()))
             }
             // no op, just return the left, no error
             Err(_) => Ok(left),
@@ -114,6 +518,330 @@ impl Parse for Expr {
     }
 }
 
+// The `n` of `[e; n]`, at most `MAX_REPEAT`
+pub(crate) fn repeat_len(n: &syn::LitInt) -> Result<usize> {
+    match n.base10_parse()? {
+        len if len > MAX_REPEAT => Err(syn::Error::new(
+            n.span(),
+            format!("array length `{}` exceeds the limit {}", len, MAX_REPEAT),
+        )),
+        len => Ok(len),
+    }
+}
+
+// `*e`, `&e` or `&mut e`, unary operators bind harder than `as`
+// but not as hard as indexing and field access
+fn parse_unary<P: ExprParser>(input: ParseStream) -> Result<Expr> {
+    if input.peek(Token![*]) {
+        let star: Token![*] = input.parse()?;
+        Ok(Deref(
+            Box::new(parse_unary::<P>(input)?),
+            star.spans[0].into(),
+        ))
+    } else if input.peek(Token![&&]) {
+        // `&&e` is lexed as a single token
+        let and: Token![&&] = input.parse()?;
+        let mutable = input.parse::<Option<Token![mut]>>()?.is_some();
+        let e = Ref(
+            mutable,
+            Box::new(parse_unary::<P>(input)?),
+            and.spans[1].into(),
+        );
+        Ok(Ref(false, Box::new(e), and.spans[0].into()))
+    } else if input.peek(Token![&]) {
+        let and: Token![&] = input.parse()?;
+        let mutable = input.parse::<Option<Token![mut]>>()?.is_some();
+        Ok(Ref(
+            mutable,
+            Box::new(parse_unary::<P>(input)?),
+            and.spans[0].into(),
+        ))
+    } else {
+        parse_postfix::<P>(input)
+    }
+}
+
+// A primary expression followed by indexing and field access
+fn parse_postfix<P: ExprParser>(input: ParseStream) -> Result<Expr> {
+    // check if we have a left `(Expr)`
+    let mut left = if input.peek(syn::token::Paren) {
+        let content;
+        let _ = syn::parenthesized!(content in input);
+        // `(e)` is parenthesized, `()`, `(e,)` and `(e1, e2, ...)` are tuples
+        if content.is_empty() {
+            Tuple(vec![])
+        } else {
+            let e = P::expr(&content)?;
+            if content.is_empty() {
+                Expr::Par(Box::new(e))
+            } else {
+                let _: Token![,] = content.parse()?;
+                let rest = content.parse_terminated::<Expr, Token![,]>(P::expr)?;
+                Tuple(std::iter::once(e).chain(rest).collect())
+            }
+        }
+    } else if input.peek(syn::token::Bracket) {
+        // `[e1, e2, ...]` or `[e; n]`
+        let content;
+        let bracket = syn::bracketed!(content in input);
+        let span = bracket.span.into();
+        if content.is_empty() {
+            Array(vec![], span)
+        } else {
+            let e = P::expr(&content)?;
+            if content.peek(Token![;]) {
+                let _: Token![;] = content.parse()?;
+                let n: syn::LitInt = content.parse()?;
+                Repeat(Box::new(e), repeat_len(&n)?)
+            } else if content.is_empty() {
+                Array(vec![e], span)
+            } else {
+                let _: Token![,] = content.parse()?;
+                let rest = content.parse_terminated::<Expr, Token![,]>(P::expr)?;
+                Array(std::iter::once(e).chain(rest).collect(), span)
+            }
+        }
+    } else if input.peek(Token![match]) {
+        parse_match::<P>(input)?
+    } else if input.peek(syn::Ident) && input.peek2(Token![::]) {
+        // a variant `Enum::Variant(e, ...)` or `Enum::Variant`
+        let e: syn::Ident = input.parse()?;
+        let _: Token![::] = input.parse()?;
+        let v: syn::Ident = input.parse()?;
+        let mut args = vec![];
+        if input.peek(syn::token::Paren) {
+            let content;
+            let _ = syn::parenthesized!(content in input);
+            args = content
+                .parse_terminated::<Expr, Token![,]>(P::expr)?
+                .into_iter()
+                .collect();
+        }
+        Variant(e.to_string(), v.to_string(), args, v.span().into())
+    } else if input.peek(syn::token::Brace) {
+        parse_block::<P>(input)?
+    } else if input.peek(syn::Ident) && input.peek2(syn::token::Brace) && struct_lit(input) {
+        // a struct literal `Name { field: e, ... }`
+        let name: syn::Ident = input.parse()?;
+        let content;
+        let _ = syn::braced!(content in input);
+        let fields = content.parse_terminated::<_, Token![,]>(|input| {
+            let f: syn::Ident = input.parse()?;
+            let _: Token![:] = input.parse()?;
+            let e = P::expr(input)?;
+            Ok((f.to_string(), e))
+        })?;
+        Struct(
+            name.to_string(),
+            fields.into_iter().collect(),
+            name.span().into(),
+        )
+    } else if input.peek(syn::Ident) && input.peek2(syn::token::Paren) {
+        // a call `f(e, ...)`
+        let span = input.span();
+        let f: Builtin = input.parse()?;
+        let content;
+        let _ = syn::parenthesized!(content in input);
+        let args = content.parse_terminated::<Expr, Token![,]>(P::expr)?;
+        Call(f, args.into_iter().collect(), span.into())
+    } else if input.peek(syn::Ident) && !date_lit(input) {
+        // a variable bound by a pattern
+        let x: syn::Ident = input.parse()?;
+        Var(x.to_string(), x.span().into())
+    } else {
+        // else we require a left literal
+        let left: Literal = input.parse()?;
+        left.into()
+    };
+    // indexing and field access bind harder than `as`
+    loop {
+        if input.peek(syn::token::Bracket) {
+            let content;
+            let _ = syn::bracketed!(content in input);
+            let span = content.span();
+            let i = P::expr(&content)?;
+            left = Index(Box::new(left), Box::new(i), span.into());
+        } else if input.peek(Token![.]) {
+            let _: Token![.] = input.parse()?;
+            if input.peek(syn::Ident) {
+                let f: syn::Ident = input.parse()?;
+                left = Field(
+                    Box::new(left),
+                    Member::Named(f.to_string()),
+                    f.span().into(),
+                );
+                continue;
+            }
+            for (n, span) in parse_fields(input)? {
+                left = Field(Box::new(left), Member::Index(n), span.into());
+            }
+        } else {
+            break;
+        }
+    }
+    Ok(left)
+}
+
+// `match e { pat => e, ... }`
+fn parse_match<P: ExprParser>(input: ParseStream) -> Result<Expr> {
+    let match_token: Token![match] = input.parse()?;
+    let e = P::expr(input)?;
+    let content;
+    let _ = syn::braced!(content in input);
+    let arms = content.parse_terminated::<Arm, Token![,]>(parse_arm::<P>)?;
+    Ok(Match(
+        Box::new(e),
+        arms.into_iter().collect(),
+        match_token.span.into(),
+    ))
+}
+
+// `{ stmt; ... e }`, where a statement is `let x = e;`,
+// `let mut x = e;`, `place = e;` or `e;`, as in Rust the `;` is
+// optional after blocks and `match` expressions
+fn parse_block<P: ExprParser>(input: ParseStream) -> Result<Expr> {
+    let content;
+    let brace = syn::braced!(content in input);
+    let mut stmts = vec![];
+    loop {
+        if content.is_empty() {
+            return Ok(Block(stmts, Box::new(Tuple(vec![])), brace.span.into()));
+        }
+        if content.peek(Token![let]) {
+            let _: Token![let] = content.parse()?;
+            let mutable = content.parse::<Option<Token![mut]>>()?.is_some();
+            let x: syn::Ident = content.parse()?;
+            let _: Token![=] = content.parse()?;
+            let e = P::expr(&content)?;
+            let _: Token![;] = content.parse()?;
+            stmts.push(Stmt::Let(x.to_string(), mutable, e, x.span().into()));
+            continue;
+        }
+        if content.peek(syn::token::Brace) || content.peek(Token![match]) {
+            let e = if content.peek(Token![match]) {
+                parse_match::<P>(&content)?
+            } else {
+                parse_block::<P>(&content)?
+            };
+            if content.is_empty() {
+                return Ok(Block(stmts, Box::new(e), brace.span.into()));
+            }
+            let _: Option<Token![;]> = content.parse()?;
+            stmts.push(Stmt::Expr(e));
+            continue;
+        }
+        let e = P::expr(&content)?;
+        if content.peek(Token![=]) {
+            let eq: Token![=] = content.parse()?;
+            let rhs = P::expr(&content)?;
+            let _: Token![;] = content.parse()?;
+            stmts.push(Stmt::Assign(e, rhs, eq.spans[0].into()));
+        } else if content.peek(Token![;]) {
+            let _: Token![;] = content.parse()?;
+            stmts.push(Stmt::Expr(e));
+        } else if content.is_empty() {
+            return Ok(Block(stmts, Box::new(e), brace.span.into()));
+        } else {
+            return Err(content.error("expected `;`"));
+        }
+    }
+}
+
+// Whether `Name { .. }` is a struct literal rather than a variable followed
+// by the arms of a `match`, i.e., the braces are empty or start with `field:`
+fn struct_lit(input: ParseStream) -> bool {
+    let fields = |input: ParseStream| -> Result<bool> {
+        let _: syn::Ident = input.parse()?;
+        let content;
+        let _ = syn::braced!(content in input);
+        Ok(content.is_empty()
+            || content.peek(syn::Ident) && content.peek2(Token![:]) && !content.peek2(Token![::]))
+    };
+    fields(&input.fork()).unwrap_or(false)
+}
+
+// The field(s) after a `.`, a tuple index `0`, or `0.1` as nested
+// tuple indices are lexed as a float
+fn parse_fields(input: ParseStream) -> Result<Vec<(usize, proc_macro2::Span)>> {
+    let lit: syn::Lit = input.parse()?;
+    let (digits, suffix) = match &lit {
+        syn::Lit::Int(n) => (n.base10_digits().to_string(), n.suffix()),
+        syn::Lit::Float(f) => (f.base10_digits().to_string(), f.suffix()),
+        _ => ("".to_string(), ""),
+    };
+    let fields: Option<Vec<_>> = digits
+        .split('.')
+        .map(|n| n.parse().ok().map(|n| (n, lit.span())))
+        .collect();
+    match fields {
+        Some(fields) if suffix.is_empty() && fields.len() <= 2 => Ok(fields),
+        _ => Err(syn::Error::new(lit.span(), "expected tuple field")),
+    }
+}
+
+// The sources of the tests below, both engines must agree on them
+#[cfg(test)]
+pub(crate) const TEST_SOURCES: &[&str] = &[
+    "2 - 4 - 5",
+    "(2 - 4) - 5",
+    "2 * 4 - 5",
+    "(2 * 4) - 5",
+    "(2 * 4) - ",
+    "(2 - 3 - 4) * (5 - 6 * 7)",
+    "1 + 2 / 0",
+    "1 + 2 * 3 < 4 * 5 - 6 && 2 != 3 || 1 >= 2",
+    "1 == 2 || 3 - 4 * 5 <= 6 / 2 - 9 && true",
+    "2147483647 + 2 - 10",
+    "-2147483648 / -1",
+    "0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff * 0b11 + 0o7",
+    "123456789012345678901234567890 / 1000000000000000000000 - 123456777",
+    "99999999999999999999 > 99999999999999999998 * 1",
+    "-99999999999999999999 < 2",
+    "4294967297 as u8 as i32 + 4294967295 as i32",
+    "(-1 - 4294967296) as u64 == 18446744069414584319u64",
+    "100000000000000000000 as f64",
+    "-340282366920938463463374607431768211456 * 3",
+    "300 as u8 as i32 - (2 - 3) as u8 as i32 * 2",
+    "200u8 + 100u8",
+    "0u64 - 1u64",
+    "0.1 + 0.2 * 3.0 - 1.0 / 4.0",
+    "1.5f32 * 3f32",
+    "0.0 / 0.0 < 1.0 || 0.0 / 0.0 >= 1.0",
+    "(0.0 / 0.0) as i64",
+    "2.9 as i32 + 7 as f64 as i32",
+    r#""ab" + upper("cd" + "e") + "f""#,
+    r#"substr("hello world", 2 * 3, len("abc") + 2)"#,
+    r#""abc" < "abd" && "b" > "abc""#,
+    "'a' as u8 + 1u8",
+    "98u8 as char",
+    "lower(1)",
+    r#"d"2025-01-01" - d"2024-01-01""#,
+    r#"d"2024-02-29" < d"2024-03-01" && d"2024-02-29" == d"2024-03-01" - 1"#,
+    r#"struct E { at: Date } match (E { at: d"2024-02-28" }).at + 1 { d"2024-02-29" => 1, _ => 0 }"#,
+    r#"e"2024-02-29""#,
+    "(1, 2).0u8",
+    "((1, (2, 3)),).0.1.0",
+    "[1, 2 * 3 - 4,]",
+    "[0; 100000000000]",
+    "[[0; 2]; 3][2][1u8 as i32]",
+    "[(1, 2), (3, 4)][1].0 - 1",
+    "(1, [2, 3]) == (1, [2, 3])",
+    "struct Point { x: i32, y: i32 } \
+     struct Line { from: Point, to: Point, tag: (bool, [char; 2]) } \
+     Point { x: 1 - 2 - 3, y: 2, }.x",
+    "struct P { x: i32, y: i32 } struct L { a: P, b: P } \
+     L { a: P { x: 1, y: 2 }, b: P { y: 3, x: 4 } }.b.x",
+    "enum O { S(i32), N } enum E { A(O, bool), B } \
+     match E::A(O::S(2), true) { E::A(O::S(x), true) => x * 10, E::A(O::S(x), _) => x, _ => 0 }",
+    "enum O { S(i32), N } \
+     match O::S(1) { O::S(x) => match O::S(x + 1) { O::S(x) => x, O::N => 0 } + x, O::N => 0 }",
+    "match 1 + 1 { 1 => 'a', 2 => 'b', _ => 'c' }",
+    "{ let x = 3u8; let r = &x; *r as i32 * 2 }",
+    "{ let x = 1; let y = { let x = x + 1; x * 10 }; { x + y } }",
+    "{ let mut a = 1; let mut b = 2; let r = &mut a; *r = 3; let s = &mut b; *s = *s + a; (a, b) }",
+    "{ let x = 1 x }",
+];
+
 #[test]
 fn test_expr_right() {
     let ts: proc_macro2::TokenStream = "2 - 4 - 5".parse().unwrap();
@@ -184,45 +912,91 @@ fn test_expr_fail() {
 // In order to implement the algorithm for our Expr
 // we first want to turn the right associated graph to
 // to a flat vector of elements ExprItems.
+// Operands are the non binary parts of the expression,
+// i.e., literals, calls, tuples, arrays and parenthesized expressions
+// (with indexing, field access and casts).
 #[derive(Debug)]
 enum ExprItems {
-    Op(Op),
-    Lit(Literal),
+    Op(Op, Span),
+    Operand(Expr),
 }
 
 impl ExprItems {
-    fn get_op(&self) -> Op {
+    fn get_op(&self) -> (Op, Span) {
         match self {
-            ExprItems::Op(op) => *op,
+            ExprItems::Op(op, span) => (*op, *span),
             _ => panic!(),
         }
     }
-    fn get_lit(&self) -> Literal {
+    fn get_operand(&self) -> Expr {
         match self {
-            ExprItems::Lit(lit) => *lit,
+            ExprItems::Operand(e) => e.clone(),
             _ => panic!(),
         }
     }
 }
 
+// Climbs the sub expressions of an operand
+fn climb_operand(e: Expr) -> Expr {
+    match e {
+        Par(e) => Par(Box::new(climb(*e))),
+        Cast(e, ty, span) => Cast(Box::new(climb_operand(*e)), ty, span),
+        Call(f, args, span) => Call(f, args.into_iter().map(climb).collect(), span),
+        Tuple(es) => Tuple(es.into_iter().map(climb).collect()),
+        Array(es, span) => Array(es.into_iter().map(climb).collect(), span),
+        Repeat(e, n) => Repeat(Box::new(climb(*e)), n),
+        Index(a, i, span) => Index(Box::new(climb_operand(*a)), Box::new(climb(*i)), span),
+        Field(t, m, span) => Field(Box::new(climb_operand(*t)), m, span),
+        Struct(name, fields, span) => Struct(
+            name,
+            fields.into_iter().map(|(f, e)| (f, climb(e))).collect(),
+            span,
+        ),
+        Variant(e, v, args, span) => Variant(e, v, args.into_iter().map(climb).collect(), span),
+        Match(e, arms, span) => Match(
+            Box::new(climb(*e)),
+            arms.into_iter()
+                .map(|arm| Arm {
+                    body: climb(arm.body),
+                    ..arm
+                })
+                .collect(),
+            span,
+        ),
+        Block(stmts, e, span) => Block(
+            stmts
+                .into_iter()
+                .map(|stmt| match stmt {
+                    Stmt::Let(x, mutable, e, span) => Stmt::Let(x, mutable, climb(e), span),
+                    Stmt::Assign(place, e, span) => Stmt::Assign(climb(place), climb(e), span),
+                    Stmt::Expr(e) => Stmt::Expr(climb(e)),
+                })
+                .collect(),
+            Box::new(climb(*e)),
+            span,
+        ),
+        Ref(mutable, e, span) => Ref(mutable, Box::new(climb_operand(*e)), span),
+        Deref(e, span) => Deref(Box::new(climb_operand(*e)), span),
+        _ => e,
+    }
+}
+
 // Flattens an Expr into a vector of ExprItems
 fn to_vec(e: Expr) -> Vec<ExprItems> {
     match e {
-        Lit(l) => vec![ExprItems::Lit(l)],
-        BinOp(op, l, r) => {
+        BinOp(op, l, r, span) => {
             let mut r = to_vec(*r);
             match *l {
-                Lit(l) => {
-                    r.push(ExprItems::Op(op));
-                    r.push(ExprItems::Lit(l));
+                // should never occur due to the Expr structure
+                BinOp(..) => unreachable!(),
+                l => {
+                    r.push(ExprItems::Op(op, span));
+                    r.push(ExprItems::Operand(climb_operand(l)));
                     r
                 }
-                // should never occur due to the Expr structure
-                _ => unreachable!(),
             }
         }
-        // not yet implemented
-        Par(_) => unimplemented!(),
+        e => vec![ExprItems::Operand(climb_operand(e))],
     }
 }
 
@@ -237,7 +1011,7 @@ fn peek_precedence<F>(scanner: &mut Scanner, f: F) -> bool
 where
     F: Fn(u8) -> bool,
 {
-    if let Some(ExprItems::Op(op)) = scanner.peek().clone() {
+    if let Some(ExprItems::Op(op, _)) = scanner.peek() {
         f(op.priority())
     } else {
         false
@@ -248,21 +1022,23 @@ where
 fn climb_rec(mut lhs: Expr, min_precedence: u8, scanner: &mut Scanner) -> Expr {
     while peek_precedence(scanner, |op_precedence| op_precedence >= min_precedence) {
         // op := lookahead
-        let op: Op = scanner.next().unwrap().get_op();
+        let (op, span) = scanner.next().unwrap().get_op();
         // advance to next token
         // rhs := parse_primary ()
-        let mut rhs: Expr = scanner.next().unwrap().get_lit().into();
+        let mut rhs: Expr = scanner.next().unwrap().get_operand();
         // while lookahead is a binary operator whose precedence is greater
         //                  than op's, or a right-associative operator
         //                  whose precedence is equal to op's
         while peek_precedence(scanner, |op_precedence| op_precedence > op.priority()) {
-            // rhs := parse_expression_1 (rhs, min_precedence + 1)
-            rhs = climb_rec(rhs, min_precedence + 1, scanner);
+            // rhs := parse_expression_1 (rhs, precedence of op + 1)
+            // (the wikipedia pseudo code above uses min_precedence + 1,
+            // which only works for two precedence levels)
+            rhs = climb_rec(rhs, op.priority() + 1, scanner);
             // lookahead := peek next token
             // scanner will be updated since we passed it recursively
         }
         // lhs := the result of applying op with operands lhs and rhs
-        lhs = BinOp(op, Box::new(lhs), Box::new(rhs));
+        lhs = BinOp(op, Box::new(lhs), Box::new(rhs), span);
     }
     lhs
 }
@@ -273,8 +1049,8 @@ pub fn climb(e: Expr) -> Expr {
     let v: Vec<ExprItems> = to_vec(e);
     // turn the vector into a Scanner
     let mut scanner = v.iter().rev().peekable();
-    // take the first literal
-    let lhs: Expr = scanner.next().unwrap().get_lit().into();
+    // take the first operand
+    let lhs: Expr = scanner.next().unwrap().get_operand();
     // now call the climber
     climb_rec(lhs, 0, &mut scanner)
 }
@@ -322,3 +1098,543 @@ fn climb_test4() {
     println!("evaluation {:?}", e.eval());
     assert_eq!(e.eval(), Literal::Int(8 - 7 - 6 * 5 - 4 * 3));
 }
+
+#[test]
+fn climb_div() {
+    let ts: proc_macro2::TokenStream = "8 / 2 / 3".parse().unwrap();
+    let e: Expr = syn::parse2(ts).unwrap();
+    let e = climb(e);
+    println!("e {:?}", e);
+    assert_eq!(e.eval(), Literal::Int(8 / 2 / 3));
+}
+
+#[test]
+fn climb_and_or() {
+    let ts: proc_macro2::TokenStream = "true && false || false".parse().unwrap();
+    let e: Expr = syn::parse2(ts).unwrap();
+    let e = climb(e);
+    println!("e {:?}", e);
+    assert_eq!(e.eval(), Literal::Bool(false));
+}
+
+#[test]
+fn climb_par() {
+    let ts: proc_macro2::TokenStream = "(2 - 3 - 4) * (5 - 6 * 7)".parse().unwrap();
+    let e: Expr = syn::parse2(ts).unwrap();
+    let e = climb(e);
+    println!("e {:?}", e);
+    assert_eq!(e.eval(), Literal::Int((2 - 3 - 4) * (5 - 6 * 7)));
+}
+
+#[test]
+fn eval_div_by_zero() {
+    let ts: proc_macro2::TokenStream = "1 + 2 / 0".parse().unwrap();
+    let e: Expr = syn::parse2(ts).unwrap();
+    let err = climb(e).try_eval().unwrap_err();
+    assert_eq!(err.kind, crate::ast::EvalErrorKind::DivByZero);
+}
+
+#[test]
+fn climb_cmp() {
+    let ts: proc_macro2::TokenStream = "1 + 2 * 3 < 4 * 5 - 6 && 2 != 3 || 1 >= 2".parse().unwrap();
+    let e: Expr = syn::parse2(ts).unwrap();
+    let e = climb(e);
+    println!("e {:?}", e);
+    #[allow(clippy::nonminimal_bool)]
+    let expected = 1 + 2 * 3 < 4 * 5 - 6 && 2 != 3 || 1 >= 2;
+    assert_eq!(e.eval(), Literal::Bool(expected));
+}
+
+#[test]
+fn climb_precedence_levels() {
+    let ts: proc_macro2::TokenStream = "1 == 2 || 3 - 4 * 5 <= 6 / 2 - 9 && true".parse().unwrap();
+    let e: Expr = syn::parse2(ts).unwrap();
+    let e = climb(e);
+    println!("e {:?}", e);
+    assert_eq!(e.eval(), Literal::Bool(3 - 4 * 5 <= 6 / 2 - 9));
+}
+
+// Int overflow (not possible with the bigint feature)
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn eval_arith_modes() {
+    use crate::ast::{ArithMode, EvalErrorKind};
+    let ts: proc_macro2::TokenStream = "2147483647 + 2 - 10".parse().unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    let err = e.eval_with(ArithMode::Checked).unwrap_err();
+    assert_eq!(err.kind, EvalErrorKind::Overflow);
+    assert_eq!(
+        e.eval_with(ArithMode::Wrapping),
+        Ok(Literal::Int(i32::MAX.wrapping_add(2).wrapping_sub(10)))
+    );
+    assert_eq!(
+        e.eval_with(ArithMode::Saturating),
+        Ok(Literal::Int(i32::MAX - 10))
+    );
+}
+
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn eval_arith_modes_div() {
+    use crate::ast::ArithMode;
+    let ts: proc_macro2::TokenStream = "-2147483648 / -1".parse().unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    assert!(e.eval_with(ArithMode::Checked).is_err());
+    assert_eq!(e.eval_with(ArithMode::Wrapping), Ok(Literal::Int(i32::MIN)));
+    assert_eq!(
+        e.eval_with(ArithMode::Saturating),
+        Ok(Literal::Int(i32::MAX))
+    );
+}
+
+#[cfg(feature = "bigint")]
+#[test]
+fn eval_bigint() {
+    use crate::ast::{ArithMode, EvalErrorKind};
+    let eval = |s: &str| {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        climb(syn::parse2(ts).unwrap()).eval_with(ArithMode::Checked)
+    };
+    let ok = |s: &str| eval(s).unwrap().to_string();
+    assert_eq!(ok("2147483647 + 2 - 10"), "2147483639");
+    assert_eq!(ok("-2147483648 / -1"), "2147483648");
+    assert_eq!(
+        ok("0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff * 0b11 + 0o7"),
+        "66902235595591869424607154817945084517941252"
+    );
+    assert_eq!(
+        ok("123456789012345678901234567890 / 1000000000000000000000 - 123456777"),
+        "12"
+    );
+    // results that fit are plain i32
+    assert_eq!(
+        eval("4294967296 * 4294967296 / 4294967296 / 4294967296"),
+        Ok(Literal::Int(1))
+    );
+    assert_eq!(
+        ok("99999999999999999999 > 99999999999999999998 * 1"),
+        "true"
+    );
+    assert_eq!(ok("-99999999999999999999 < 2"), "true");
+    assert_eq!(ok("4294967297 as u8 as i32 + 4294967295 as i32"), "0");
+    assert_eq!(
+        ok("(-1 - 4294967296) as u64 == 18446744069414584319u64"),
+        "true"
+    );
+    assert_eq!(ok("100000000000000000000 as f64"), "1e20");
+    assert_eq!(
+        eval("100000000000000000000 / (1 - 1)").unwrap_err().kind,
+        EvalErrorKind::DivByZero
+    );
+    // printing and parsing back gives the same value
+    let l = eval("-340282366920938463463374607431768211456 * 3").unwrap();
+    let ts: proc_macro2::TokenStream = l.to_string().parse().unwrap();
+    assert_eq!(syn::parse2::<Literal>(ts).unwrap(), l);
+}
+
+#[test]
+fn climb_cast() {
+    let ts: proc_macro2::TokenStream = "300 as u8 as i32 - (2 - 3) as u8 as i32 * 2"
+        .parse()
+        .unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    println!("e {:?}", e);
+    #[allow(clippy::unnecessary_cast)]
+    let expected = 300i32 as u8 as i32 - (2 - 3) as u8 as i32 * 2;
+    assert_eq!(e.eval(), Literal::Int(expected));
+}
+
+#[test]
+fn eval_widths() {
+    use crate::ast::{ArithMode, EvalErrorKind};
+    let ts: proc_macro2::TokenStream = "200u8 + 100u8".parse().unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    assert_eq!(e.try_eval().unwrap_err().kind, EvalErrorKind::Overflow);
+    assert_eq!(e.eval_with(ArithMode::Wrapping), Ok(Literal::U8(44)));
+    assert_eq!(e.eval_with(ArithMode::Saturating), Ok(Literal::U8(255)));
+    let ts: proc_macro2::TokenStream = "0u64 - 1u64".parse().unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    assert_eq!(e.eval_with(ArithMode::Wrapping), Ok(Literal::U64(u64::MAX)));
+    let ts: proc_macro2::TokenStream = "-1i8 as u128 / 3u128 > 3_000_000_000u128".parse().unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    assert_eq!(e.eval(), Literal::Bool(true));
+}
+
+#[test]
+fn eval_float() {
+    let ts: proc_macro2::TokenStream = "0.1 + 0.2 * 3.0 - 1.0 / 4.0".parse().unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    assert_eq!(e.eval(), Literal::F64(0.1 + 0.2 * 3.0 - 1.0 / 4.0));
+    let ts: proc_macro2::TokenStream = "1.5f32 * 3f32".parse().unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    assert_eq!(e.eval(), Literal::F32(4.5));
+}
+
+#[test]
+fn eval_float_ieee() {
+    let eval = |s: &str| {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        climb(syn::parse2(ts).unwrap()).eval()
+    };
+    assert_eq!(eval("1.0 / 0.0"), Literal::F64(f64::INFINITY));
+    assert_eq!(eval("-1.0 / 0.0 < -1e308"), Literal::Bool(true));
+    // NaN is not equal to itself, and all comparisons are false
+    assert_eq!(eval("0.0 / 0.0 == 0.0 / 0.0"), Literal::Bool(false));
+    assert_eq!(eval("0.0 / 0.0 != 0.0 / 0.0"), Literal::Bool(true));
+    assert_eq!(
+        eval("0.0 / 0.0 < 1.0 || 0.0 / 0.0 >= 1.0"),
+        Literal::Bool(false)
+    );
+    assert_eq!(eval("1e39 as f32"), Literal::F32(f32::INFINITY));
+    // float to integer casts saturate, NaN is cast to 0
+    assert_eq!(eval("1e10 as i32"), Literal::Int(i32::MAX));
+    assert_eq!(eval("-1.5 as u8"), Literal::U8(0));
+    assert_eq!(eval("(0.0 / 0.0) as i64"), Literal::I64(0));
+    assert_eq!(eval("2.9 as i32 + 7 as f64 as i32"), Literal::Int(9));
+}
+
+#[test]
+fn eval_str() {
+    use crate::ast::EvalErrorKind;
+    let eval = |s: &str| {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        climb(syn::parse2(ts).unwrap()).try_eval()
+    };
+    assert_eq!(
+        eval(r#""ab" + upper("cd" + "e") + "f""#),
+        Ok(Literal::from("abCDEf"))
+    );
+    assert_eq!(eval(r#"len("åäö") * 2 + 1"#), Ok(Literal::Int(7)));
+    assert_eq!(
+        eval(r#"substr("hello world", 2 * 3, len("abc") + 2)"#),
+        Ok(Literal::from("world"))
+    );
+    assert_eq!(eval(r#"substr("åäö", 1, 1)"#), Ok(Literal::from("ä")));
+    assert_eq!(
+        eval(r#""abc" < "abd" && "b" > "abc""#),
+        Ok(Literal::Bool(true))
+    );
+    assert_eq!(eval(r#""a" + "b" == "ab""#), Ok(Literal::Bool(true)));
+    assert_eq!(eval("'a' < 'b' && 'a' != 'b'"), Ok(Literal::Bool(true)));
+    assert_eq!(eval("'a' as u8 + 1u8"), Ok(Literal::U8(98)));
+    assert_eq!(eval("98u8 as char"), Ok(Literal::Char('b')));
+    for s in [r#"substr("abc", 2, 2)"#, r#"substr("abc", -1, 1)"#] {
+        assert_eq!(eval(s).unwrap_err().kind, EvalErrorKind::OutOfBounds);
+    }
+    let ts: proc_macro2::TokenStream = "lower(1)".parse().unwrap();
+    let err = syn::parse2::<Expr>(ts).unwrap_err();
+    assert_eq!(err.to_string(), "unknown function `lower`");
+}
+
+#[test]
+fn eval_date() {
+    use crate::ast::EvalErrorKind;
+    let eval = |s: &str| {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        let p: Program = syn::parse2(ts)?;
+        p.type_check()?;
+        Ok::<_, syn::Error>(p.eval_with(Default::default()))
+    };
+    let date = |s: &str| Literal::Date(s.parse().unwrap());
+    assert_eq!(
+        eval(r#"d"2024-01-31" + 30"#).unwrap(),
+        Ok(date("2024-03-01"))
+    );
+    assert_eq!(
+        eval(r#"d"2023-01-31" + 30"#).unwrap(),
+        Ok(date("2023-03-02"))
+    );
+    assert_eq!(
+        eval(r#"d"2024-03-01" - 1"#).unwrap(),
+        Ok(date("2024-02-29"))
+    );
+    assert_eq!(
+        eval(r#"d"2025-01-01" - d"2024-01-01""#).unwrap(),
+        Ok(Literal::Int(366))
+    );
+    assert_eq!(
+        eval(r#"d"2024-02-29" < d"2024-03-01" && d"2024-02-29" == d"2024-03-01" - 1"#).unwrap(),
+        Ok(Literal::Bool(true))
+    );
+    assert_eq!(
+        eval(r#"d"9999-12-31" + 1"#).unwrap().unwrap_err().kind,
+        EvalErrorKind::Overflow
+    );
+    // in patterns and declarations, printed as literals
+    let p = r#"struct E { at: Date } match (E { at: d"2024-02-28" }).at + 1 { d"2024-02-29" => 1, _ => 0 }"#;
+    assert_eq!(eval(p).unwrap(), Ok(Literal::Int(1)));
+    assert_eq!(date("2024-02-29").to_string(), r#"d"2024-02-29""#);
+    // invalid dates are parse errors at the string
+    let err = eval("1 + d\"2022-02-29\"").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid day `29`, February 2022 has 28 days"
+    );
+    assert_eq!(err.span().start().column, 5);
+    let err = eval(r#"d"2024-2-1""#).unwrap_err();
+    assert_eq!(err.to_string(), "invalid date, expected `YYYY-MM-DD`");
+    // other names are variables
+    assert!(eval(r#"e"2024-02-29""#).is_err());
+}
+
+#[test]
+fn parse_tuple_array() {
+    let parse = |s: &str| {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        climb(syn::parse2(ts).unwrap())
+    };
+    assert_eq!(parse("(1)"), Par(Box::new(1.into())));
+    assert_eq!(parse("(1,)"), Tuple(vec![1.into()]));
+    assert_eq!(parse("()"), Tuple(vec![]));
+    assert_eq!(
+        parse("(1 - 2 - 3, true)"),
+        Tuple(vec![parse("1 - 2 - 3"), true.into()])
+    );
+    assert_eq!(
+        parse("[1, 2 * 3 - 4,]"),
+        Array(vec![1.into(), parse("2 * 3 - 4")], Span::default())
+    );
+    assert_eq!(parse("[0; 8]"), Repeat(Box::new(0.into()), 8));
+    assert_eq!(
+        parse("[1][0] + 1"),
+        Expr::bin_op(
+            Op::Add,
+            Index(
+                Box::new(Array(vec![1.into()], Span::default())),
+                Box::new(0.into()),
+                Span::default()
+            ),
+            1.into()
+        )
+    );
+    // `.0.1` is lexed as a float
+    assert_eq!(
+        parse("((1, (2, 3)),).0.1.0"),
+        parse("((1, (2, 3)),) . 0 . 1 . 0")
+    );
+    let ts: proc_macro2::TokenStream = "(1, 2).0u8".parse().unwrap();
+    assert!(syn::parse2::<Expr>(ts).is_err());
+    // the length is bounded, the error is at the length
+    let ts: proc_macro2::TokenStream = "[0; 100000000000]".parse().unwrap();
+    let err = syn::parse2::<Expr>(ts).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "array length `100000000000` exceeds the limit 65536"
+    );
+    assert_eq!(err.span().start().column, 4);
+    assert_eq!(parse("[0; 65536]"), Repeat(Box::new(0.into()), 65536));
+}
+
+#[test]
+fn eval_tuple_array() {
+    use crate::ast::EvalErrorKind;
+    let eval = |s: &str| {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        climb(syn::parse2(ts).unwrap()).try_eval()
+    };
+    assert_eq!(eval("[1, 2, 3][1 + 1] * 2"), Ok(Literal::Int(6)));
+    assert_eq!(eval("[[0; 2]; 3][2][1u8 as i32]"), Ok(Literal::Int(0)));
+    assert_eq!(eval("(1, (true, 'c')).1.1"), Ok(Literal::Char('c')));
+    assert_eq!(eval("[(1, 2), (3, 4)][1].0 - 1"), Ok(Literal::Int(2)));
+    assert_eq!(eval("(1, [2, 3]) == (1, [2, 3])"), Ok(Literal::Bool(true)));
+    assert_eq!(eval("[1, 2] != [1, 3]"), Ok(Literal::Bool(true)));
+    assert_eq!(eval("([1; 2], ())").unwrap().to_string(), "([1, 1], ())");
+    assert_eq!(eval("(1.5,)").unwrap().to_string(), "(1.5,)");
+    for s in ["[1, 2, 3][3]", "[1, 2, 3][1 - 2]", "[0; 0][0]"] {
+        assert_eq!(eval(s).unwrap_err().kind, EvalErrorKind::OutOfBounds);
+    }
+}
+
+// The out of bounds error points to the index expression
+#[test]
+fn eval_index_span() {
+    let src = "[1, 2, 3][0] + [1, 2, 3][2 + 1]";
+    let ts: proc_macro2::TokenStream = src.parse().unwrap();
+    let e = climb(syn::parse2(ts).unwrap());
+    let err = e.try_eval().unwrap_err();
+    let start = err.span.0.start();
+    assert_eq!(start.column, src.rfind("2 + 1").unwrap());
+}
+
+#[test]
+fn parse_program() {
+    use crate::ast::Item;
+    let ts: proc_macro2::TokenStream = "
+        struct Point { x: i32, y: i32 }
+        struct Line { from: Point, to: Point, tag: (bool, [char; 2]) }
+        Point { x: 1 - 2 - 3, y: 2, }.x
+    "
+    .parse()
+    .unwrap();
+    let p: Program = syn::parse2(ts).unwrap();
+    assert_eq!(p.items.len(), 2);
+    let line = match &p.items[1] {
+        Item::Struct(line) => line,
+        _ => panic!(),
+    };
+    assert_eq!(
+        line.fields[2].1,
+        Type::Tuple(vec![Type::Bool, Type::Array(Box::new(Type::Char), 2)])
+    );
+    assert_eq!(line.fields[0].1, Type::Struct("Point".to_string()));
+    assert_eq!(p.eval_with(Default::default()), Ok(Literal::Int(-4)));
+}
+
+#[test]
+fn eval_struct() {
+    let eval = |s: &str| {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        let p: Program = syn::parse2(ts).unwrap();
+        p.type_check().unwrap();
+        p.eval_with(Default::default()).unwrap()
+    };
+    let decls = "struct P { x: i32, y: i32 } struct L { a: P, b: P }";
+    assert_eq!(
+        eval(&format!(
+            "{} L {{ a: P {{ x: 1, y: 2 }}, b: P {{ y: 3, x: 4 }} }}.b.x",
+            decls
+        )),
+        Literal::Int(4)
+    );
+    // the order of the fields does not matter
+    assert_eq!(
+        eval(&format!(
+            "{} P {{ x: 1, y: 2 }} == P {{ y: 2, x: 1 }}",
+            decls
+        )),
+        Literal::Bool(true)
+    );
+    assert_eq!(
+        eval(&format!("{} P {{ y: 1 + 1, x: 3 }}", decls)).to_string(),
+        "P { x: 3, y: 2 }"
+    );
+}
+
+#[test]
+fn eval_enum_match() {
+    let eval = |s: &str| {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        let p: Program = syn::parse2(ts).unwrap();
+        p.type_check().unwrap();
+        p.eval_with(Default::default()).unwrap()
+    };
+    let decls = "enum O { S(i32), N } enum E { A(O, bool), B }";
+    assert_eq!(
+        eval(&format!(
+            "{} match E::A(O::S(2), true) {{ E::A(O::S(x), true) => x * 10, E::A(O::S(x), _) => x, _ => 0 }}",
+            decls
+        )),
+        Literal::Int(20)
+    );
+    // the first matching arm is taken, bindings shadow outer bindings
+    assert_eq!(
+        eval(&format!(
+            "{} match O::S(1) {{ O::S(x) => match O::S(x + 1) {{ O::S(x) => x, O::N => 0 }} + x, O::N => 0 }}",
+            decls
+        )),
+        Literal::Int(3)
+    );
+    assert_eq!(
+        eval("match 1 + 1 { 1 => 'a', 2 => 'b', _ => 'c' }"),
+        Literal::Char('b')
+    );
+    assert_eq!(
+        eval(&format!("{} E::A(O::S(1), false)", decls)).to_string(),
+        "E::A(O::S(1), false)"
+    );
+    assert_eq!(
+        eval(&format!("{} E::B == E::B && O::S(1) != O::S(2)", decls)),
+        Literal::Bool(true)
+    );
+}
+
+#[test]
+fn eval_block_ref() {
+    let eval = |s: &str| {
+        let ts: proc_macro2::TokenStream = s.parse().unwrap();
+        let p: Program = syn::parse2(ts).unwrap();
+        p.type_check().unwrap();
+        p.borrow_check().unwrap();
+        p.eval_with(Default::default()).unwrap()
+    };
+    assert_eq!(
+        eval("{ let mut x = 1; let r = &mut x; *r = *r * 10 + 2; x }"),
+        Literal::Int(12)
+    );
+    // `*` binds harder than `as` and binary operators
+    assert_eq!(
+        eval("{ let x = 3u8; let r = &x; *r as i32 * 2 }"),
+        Literal::Int(6)
+    );
+    // shadowing, and variables going out of scope at the end of a block
+    assert_eq!(
+        eval("{ let x = 1; let y = { let x = x + 1; x * 10 }; { x + y } }"),
+        Literal::Int(21)
+    );
+    assert_eq!(
+        eval("{ let mut a = 1; let mut b = 2; let r = &mut a; *r = 3; let s = &mut b; *s = *s + a; (a, b) }")
+            .to_string(),
+        "(3, 5)"
+    );
+    assert_eq!(eval("{ let x = 1; }"), Literal::Tuple(vec![]));
+    let ts: proc_macro2::TokenStream = "{ let x = 1 x }".parse().unwrap();
+    assert!(syn::parse2::<Program>(ts).is_err());
+}
+
+#[test]
+fn parse_expr_file() {
+    let src = "
+        // the sum, dropped by `proc_macro2`
+        /// The total
+        ///
+        /// of the parts
+        1 + /* 2 + */ 3 * 4;
+        /** Block docs */
+        { let x = 1; x };
+        true";
+    let f: ExprFile = syn::parse_str(src).unwrap();
+    assert_eq!(f.exprs.len(), 3);
+    assert_eq!(f.exprs[0].docs, vec![" The total", "", " of the parts"]);
+    assert_eq!(f.exprs[0].expr.eval(), Literal::Int(13));
+    assert_eq!(f.exprs[1].docs, vec![" Block docs "]);
+    assert!(f.exprs[2].docs.is_empty());
+    assert_eq!(
+        f.to_string(),
+        "/// The total\n///\n/// of the parts\n1 + 3 * 4;\n\
+         /// Block docs \n{ let x = 1; x };\n\
+         true;\n"
+    );
+    // printed back, the same file
+    let g: ExprFile = syn::parse_str(&f.to_string()).unwrap();
+    assert_eq!(g, f);
+    assert_eq!(g.to_string(), f.to_string());
+    // multi-line block docs are printed as such
+    let f: ExprFile = syn::parse_str("/** a\n b */ 1").unwrap();
+    assert_eq!(f.exprs[0].docs, vec![" a\n b "]);
+    assert_eq!(f.to_string(), "/** a\n b */\n1;\n");
+    assert_eq!(syn::parse_str::<ExprFile>(&f.to_string()).unwrap(), f);
+    // the doc comments are before top level expressions only
+    assert!(syn::parse_str::<ExprFile>("1 + 2 /// no").is_err());
+    assert!(syn::parse_str::<ExprFile>("{ /// no\n 1 }").is_err());
+    assert!(syn::parse_str::<ExprFile>("/// dangling").is_err());
+    let err = syn::parse_str::<ExprFile>("#[inline] 1").unwrap_err();
+    assert_eq!(err.to_string(), "expected a doc comment");
+    assert!(syn::parse_str::<ExprFile>("1 2").is_err());
+}
+
+#[test]
+fn expr_display() {
+    let e = Expr::bin_op(Op::Mul, Expr::bin_op(Op::Add, 1.into(), 2.into()), 3.into());
+    assert_eq!(e.to_string(), "(1 + 2) * 3");
+    let e = Expr::bin_op(Op::Sub, 1.into(), Expr::bin_op(Op::Sub, 2.into(), 3.into()));
+    assert_eq!(e.to_string(), "1 - (2 - 3)");
+    let ts: proc_macro2::TokenStream =
+        "{ let mut x = (1, 'a'); x = (2, 'b'); [*&x; 2][0].1 as u8 }"
+            .parse()
+            .unwrap();
+    let p: Program = syn::parse2(ts).unwrap();
+    assert_eq!(
+        p.expr.to_string(),
+        "{ let mut x = (1, 'a'); x = (2, 'b'); [*&x; 2][0].1 as u8 }"
+    );
+}
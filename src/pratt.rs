@@ -0,0 +1,174 @@
+// A Pratt (top down operator precedence) parser.
+//
+// Where `impl Parse for Expr` builds a right associative tree that is
+// then restructured by `climb`, the Pratt parser builds the correctly
+// associated tree directly. After an operand, an operator is consumed
+// only if it binds at least as hard as required (`min`), and its right
+// operand is parsed requiring strictly harder binding operators, making
+// operators of the same priority left associative.
+//
+// The operands are parsed as by `impl Parse for Expr`, with the Pratt
+// parser for all sub expressions. `Engine` selects the parser.
+use crate::{
+    ast::{Expr, Item, Op, Program},
+    parse::{climb, parse_operand, ExprParser},
+};
+use syn::{
+    parse::{discouraged::Speculative, ParseStream},
+    Result, Token,
+};
+
+struct Pratt;
+
+impl ExprParser for Pratt {
+    fn expr(input: ParseStream) -> Result<Expr> {
+        parse(input)
+    }
+}
+
+// Parse an expression, correctly associated
+pub fn parse(input: ParseStream) -> Result<Expr> {
+    parse_min(input, 0)
+}
+
+// An expression of operators with priority `min` or higher
+fn parse_min(input: ParseStream, min: u8) -> Result<Expr> {
+    let mut left = parse_operand::<Pratt>(input)?;
+    loop {
+        // the operator, consumed only if it binds hard enough
+        let span = input.span();
+        let fork = input.fork();
+        let op = match fork.parse::<Op>() {
+            Ok(op) if op.priority() >= min => op,
+            _ => return Ok(left),
+        };
+        input.advance_to(&fork);
+        let right = parse_min(input, op.priority() + 1)?;
+        left = Expr::BinOp(op, Box::new(left), Box::new(right), span.into());
+    }
+}
+
+// The parsing engine, both give the same trees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    // `impl Parse for Expr` followed by `climb`
+    Climb,
+    Pratt,
+}
+
+impl Engine {
+    pub fn expr(self, input: ParseStream) -> Result<Expr> {
+        match self {
+            Engine::Climb => Ok(climb(input.parse()?)),
+            Engine::Pratt => parse(input),
+        }
+    }
+
+    // The items followed by an expression, as `impl Parse for Program`
+    pub fn program(self, input: ParseStream) -> Result<Program> {
+        let mut items = vec![];
+        loop {
+            if input.peek(Token![struct]) {
+                items.push(Item::Struct(input.parse()?));
+            } else if input.peek(Token![enum]) {
+                items.push(Item::Enum(input.parse()?));
+            } else {
+                break;
+            }
+        }
+        let expr = self.expr(input)?;
+        Ok(Program { items, expr })
+    }
+
+    // Parse the program from source
+    pub fn parse_str(self, s: &str) -> Result<Program> {
+        syn::parse::Parser::parse_str(|input: ParseStream| self.program(input), s)
+    }
+}
+
+// The sources of the tests, both engines must agree on the trees
+#[cfg(test)]
+const CORPUS: &[&str] = &[
+    "1",
+    "2 - 3 - 4 - 5",
+    "2 - 3 * 4 - 5",
+    "4 - 5 - 2 * 8 * 3 - 1 - 2 * 5",
+    "8 - 7 - 6 * 5 - 4 * 3",
+    "(2 - 3) * 4 / 2",
+    "8 / 2 / 3",
+    "true && false || false",
+    "false || true && false",
+    "1 < 2 == true && 3 >= 4 || 5 != 6",
+    "-1i8 as u128 / 3u128 > 3_000_000_000u128",
+    "-1.0 / 0.0 < -1e308",
+    "1 as f64 * 2.5 as f64",
+    "\"hello\" + \" \" + upper(\"world\")",
+    "len(substr(\"abcdef\", 1, 3)) == 3",
+    "[[1, 2], [3, 4]][1][0] + (5, 6).1",
+    "([1, 2, 3], (1.5, 'x'))",
+    "[1 + 2; 3][2 - 1]",
+    "struct Point { x: i32, y: i32 } Point { x: 1 + 2, y: [4, 5][1] }",
+    "struct Point { x: i32, y: i32 } struct Line { from: Point, to: Point } \
+     Line { from: Point { x: 1, y: 2 }, to: Point { x: 3, y: 4 } }.to.x - 1 - 2",
+    "enum Shape { Circle(i32), Rect(i32, i32), Empty } \
+     match Shape::Rect(2, 3) { Shape::Circle(r) => 3 * r * r, Shape::Rect(w, h) => w * h - 1 - 1, Shape::Empty => 0 }",
+    "enum E { A(i32), B } match 2 - 1 - 1 { 0 => E::B, n => E::A(n - 1 - 1) }",
+    "{ let mut sum = 0; let r = &mut sum; *r = *r + 1 - 1; *r = *r * 10; let s = &sum; *s + 1 }",
+    "{ let x = 1; { let y = x - 1 - 1; y } - 1 - 1 }",
+    "{ let t = ((1, 2), 3); t.0.1 - t.1 - 1 }",
+    "{ let mut a = [0; 2]; let r = &mut a; *r = [1, 2]; a[1] + { let a = 3; a } }",
+    "{ let x = 1; match x { 1 => { x - 1 - 1 }, _ => 0 } - 1 - 1 }",
+];
+
+// Sources rejected by both engines
+#[cfg(test)]
+const REJECTED: &[&str] = &["1 +", "(1 - )", "{ let x = 1 x }", "5 % 2"];
+
+#[cfg(test)]
+pub(crate) fn corpus() -> Vec<String> {
+    use crate::generate::{Config, Generator};
+    let mut sources: Vec<String> = CORPUS.iter().map(|s| s.to_string()).collect();
+    let mut g = Generator::new(7, Config::default());
+    sources.extend((0..1000).map(|_| g.expr().to_string()));
+    sources
+}
+
+#[test]
+fn pratt_associativity() {
+    let p = Engine::Pratt.parse_str("8 - 4 - 2 * 3 / 2").unwrap();
+    assert_eq!(p.expr.to_string(), "8 - 4 - 2 * 3 / 2");
+    assert_eq!(
+        p.eval_with(Default::default()),
+        Ok(crate::ast::Literal::Int(8 - 4 - 2 * 3 / 2))
+    );
+    // the sub expressions are parsed by the Pratt parser, without climbing
+    let p = Engine::Pratt.parse_str("(8 - 4 - 2, [8 - 4 - 2])").unwrap();
+    assert_eq!(
+        p.eval_with(Default::default()).unwrap().to_string(),
+        "(2, [2])"
+    );
+}
+
+#[test]
+fn pratt_corpus() {
+    for s in corpus() {
+        let c = Engine::Climb.parse_str(&s).unwrap();
+        let p = Engine::Pratt.parse_str(&s).unwrap();
+        assert_eq!(c.expr, p.expr, "{}", s);
+        assert_eq!(c.items.len(), p.items.len(), "{}", s);
+    }
+    for s in REJECTED {
+        assert!(Engine::Climb.parse_str(s).is_err(), "{}", s);
+        assert!(Engine::Pratt.parse_str(s).is_err(), "{}", s);
+    }
+    // the parse tests, some of them are rejected (by both engines)
+    for s in crate::parse::TEST_SOURCES {
+        match (Engine::Climb.parse_str(s), Engine::Pratt.parse_str(s)) {
+            (Ok(c), Ok(p)) => {
+                assert_eq!(c.expr, p.expr, "{}", s);
+                assert_eq!(c.items.len(), p.items.len(), "{}", s);
+            }
+            (c, p) => assert!(c.is_err() && p.is_err(), "{}", s),
+        }
+    }
+}
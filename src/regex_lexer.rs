@@ -0,0 +1,345 @@
+// A configurable tokenizer, with the token classes given by regexes.
+//
+// A `Rule` gives the regex of a token class and its priority. At each
+// position the longest match wins, rules of higher priority break the
+// ties (e.g., `true` is a Bool rather than an Ident, while `trueish`
+// is an Ident). The tokens are those of the standalone lexer
+// (`src/lexer.rs`), so they can be parsed by `src/lexer_parse.rs`.
+//
+// The default rules cover integers, Booleans, identifiers, operators,
+// whitespace and comments (see `examples/ex1_regex.rs` for the regexes).
+// Block comments nest, as in Rust, so their rules match only the
+// opening `/*`. Other literals, e.g., `1.5` or `'a'`, are errors.
+use crate::lexer::{Error, Pos, Token, TokenKind, PUNCTS};
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    // digits and `_` with an optional suffix, e.g., `1_000u8`
+    Int,
+    Bool,
+    Ident,
+    // operators and delimiters, as in `lexer::PUNCTS`
+    Punct,
+    // skipped
+    Whitespace,
+    Comment,
+    // `///` or `/** */`, kept as `TokenKind::Doc`
+    Doc,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub class: Class,
+    pub regex: Regex,
+    pub priority: u32,
+}
+
+impl Rule {
+    // The pattern is matched at the current position only
+    pub fn new(class: Class, pattern: &str, priority: u32) -> Result<Rule, regex::Error> {
+        Ok(Rule {
+            class,
+            regex: Regex::new(&format!("^(?:{})", pattern))?,
+            priority,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tokenizer {
+    pub rules: Vec<Rule>,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        let rules = [
+            (Class::Whitespace, r"\s+", 0),
+            // block comments nest, the rules match the opening `/*`
+            (Class::Comment, r"//[^\n]*|/\*", 0),
+            // as long as the comments, `////` is longer, `/***/` and
+            // `/**/` are plain comments
+            (Class::Doc, r"///(?:[^/\n][^\n]*)?|/\*\*[^*/]", 1),
+            (Class::Int, r"[0-9][0-9_]*(?:[a-z][a-z0-9]*)?", 0),
+            (Class::Bool, r"true|false", 2),
+            (Class::Ident, r"[\p{Alphabetic}_][\p{Alphabetic}\d_]*", 1),
+            // the alternatives are tried in order, longer before shorter
+            (
+                Class::Punct,
+                r"<>|==|!=|<=|>=|&&|\|\||=>|::|[-+*/<>=&!;:,.()\[\]{}]",
+                0,
+            ),
+        ];
+        Tokenizer {
+            rules: rules
+                .iter()
+                .map(|(class, pattern, priority)| Rule::new(*class, pattern, *priority).unwrap())
+                .collect(),
+        }
+    }
+}
+
+impl Tokenizer {
+    // The tokens of the source, ending with `Eof`
+    pub fn tokenize(&self, src: &str) -> Result<Vec<Token>, Error> {
+        let mut tokens = vec![];
+        let mut pos = Pos {
+            offset: 0,
+            line: 1,
+            col: 1,
+        };
+        while pos.offset < src.len() {
+            let rest = &src[pos.offset..];
+            // the longest match, then the highest priority
+            let (rule, mut len) = self
+                .rules
+                .iter()
+                .filter_map(|rule| rule.regex.find(rest).map(|m| (rule, m.end())))
+                .filter(|(_, len)| *len > 0)
+                .max_by_key(|(rule, len)| (*len, rule.priority))
+                .ok_or_else(|| {
+                    let c = rest.chars().next().unwrap();
+                    Error::new(format!("unexpected character `{}`", c), pos)
+                })?;
+            if rest.starts_with("/*") && matches!(rule.class, Class::Comment | Class::Doc) {
+                len = block_comment(rest)
+                    .ok_or_else(|| Error::new("unterminated block comment", pos))?;
+            }
+            let text = &rest[..len];
+            let kind = match rule.class {
+                Class::Whitespace | Class::Comment => None,
+                // `1.5` is not `1`, `.`, `5`, but `t.0.1` is nested fields
+                Class::Int
+                    if rest[len..].starts_with('.')
+                        && rest[len + 1..].starts_with(|c: char| c.is_ascii_digit())
+                        && !matches!(
+                            tokens.last(),
+                            Some(Token {
+                                kind: TokenKind::Punct("."),
+                                ..
+                            })
+                        ) =>
+                {
+                    return Err(Error::new("float literals are not supported", pos))
+                }
+                Class::Int => {
+                    let suffix = text.find(char::is_alphabetic).unwrap_or(len);
+                    Some(TokenKind::Int(
+                        text[..suffix].replace('_', ""),
+                        text[suffix..].to_string(),
+                    ))
+                }
+                // without the `///` or `/** */`, the text of other (custom)
+                // doc comments is kept whole
+                Class::Doc => {
+                    let doc = text
+                        .strip_prefix("///")
+                        .or_else(|| text.strip_prefix("/**").and_then(|t| t.strip_suffix("*/")))
+                        .unwrap_or(text);
+                    Some(TokenKind::Doc(doc.to_string()))
+                }
+                Class::Bool | Class::Ident => Some(TokenKind::Ident(text.to_string())),
+                Class::Punct => match PUNCTS.iter().find(|p| **p == text) {
+                    Some(p) => Some(TokenKind::Punct(p)),
+                    None => return Err(Error::new(format!("unknown operator `{}`", text), pos)),
+                },
+            };
+            let start = pos;
+            for c in text.chars() {
+                if c == '\n' {
+                    pos.line += 1;
+                    pos.col = 1;
+                } else {
+                    pos.col += 1;
+                }
+            }
+            pos.offset += len;
+            if let Some(kind) = kind {
+                tokens.push(Token {
+                    kind,
+                    pos: start,
+                    end: pos.offset,
+                });
+            }
+        }
+        tokens.push(Token {
+            kind: TokenKind::Eof,
+            pos,
+            end: pos.offset,
+        });
+        Ok(tokens)
+    }
+}
+
+// The length of the block comment, up to the matching `*/`
+fn block_comment(src: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < src.len() {
+        if src[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if src[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return Some(i);
+            }
+        } else {
+            i += src[i..].chars().next().unwrap().len_utf8();
+        }
+    }
+    None
+}
+
+// The text and (line, column) of the tokens, columns from 0
+#[cfg(test)]
+fn regex_tokens(src: &str) -> Vec<(String, usize, usize)> {
+    let tokens = Tokenizer::default().tokenize(src).unwrap();
+    tokens
+        .iter()
+        .filter(|t| t.kind != TokenKind::Eof)
+        .map(|t| {
+            (
+                src[t.pos.offset..t.end].to_string(),
+                t.pos.line,
+                t.pos.col - 1,
+            )
+        })
+        .collect()
+}
+
+// The same for `proc_macro2`, where groups are flattened to their
+// delimiters and joint punctuation is split as by `lexer::PUNCTS`
+#[cfg(test)]
+fn proc_macro2_tokens(ts: proc_macro2::TokenStream, out: &mut Vec<(String, usize, usize)>) {
+    use proc_macro2::{Delimiter, Spacing, TokenTree};
+    let mut joint: Option<(String, proc_macro2::LineColumn)> = None;
+    for tt in ts {
+        let start = tt.span().start();
+        match tt {
+            TokenTree::Punct(p) => {
+                let (mut s, start) = joint.take().unwrap_or_else(|| (String::new(), start));
+                s.push(p.as_char());
+                if p.spacing() == Spacing::Joint {
+                    joint = Some((s, start));
+                    continue;
+                }
+                // longest first
+                let mut col = start.column;
+                let mut rest = s.as_str();
+                while let Some(p) = PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+                    out.push((p.to_string(), start.line, col));
+                    col += p.len();
+                    rest = &rest[p.len()..];
+                }
+                assert!(rest.is_empty(), "{}", s);
+            }
+            TokenTree::Group(g) => {
+                let (open, close) = match g.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", ""),
+                };
+                out.push((open.to_string(), start.line, start.column));
+                proc_macro2_tokens(g.stream(), out);
+                let end = g.span_close().start();
+                out.push((close.to_string(), end.line, end.column));
+            }
+            tt => out.push((tt.to_string(), start.line, start.column)),
+        }
+    }
+}
+
+#[test]
+fn regex_lexer_rules() {
+    use TokenKind::*;
+    let kinds = |src: &str| -> Vec<TokenKind> {
+        let tokens = Tokenizer::default().tokenize(src).unwrap();
+        tokens.into_iter().map(|t| t.kind).collect()
+    };
+    let ident = |x: &str| Ident(x.to_string());
+    // longest match, then priority
+    assert_eq!(
+        kinds("true trueish <> <= 1_000u8 /* x */ // y"),
+        vec![
+            ident("true"),
+            ident("trueish"),
+            Punct("<>"),
+            Punct("<="),
+            Int("1000".to_string(), "u8".to_string()),
+            Eof
+        ]
+    );
+    // a custom rule, `#` comments
+    let mut t = Tokenizer::default();
+    t.rules
+        .push(Rule::new(Class::Comment, r"#[^\n]*", 0).unwrap());
+    let tokens = t.tokenize("1 # one\n+ 2").unwrap();
+    assert_eq!(tokens.len(), 4);
+    assert_eq!((tokens[1].pos.line, tokens[1].pos.col), (2, 1));
+    // custom doc comments are kept whole
+    let mut t = Tokenizer::default();
+    t.rules.push(Rule::new(Class::Doc, "#!", 5).unwrap());
+    t.rules
+        .push(Rule::new(Class::Doc, r"//!/[^\n]*", 5).unwrap());
+    let tokens = t.tokenize("#! 1 //!/ one\n/** two */ 2").unwrap();
+    let found: Vec<_> = tokens.into_iter().map(|t| t.kind).collect();
+    assert_eq!(
+        found,
+        vec![
+            Doc("#!".to_string()),
+            Int("1".to_string(), String::new()),
+            Doc("//!/ one".to_string()),
+            Doc(" two ".to_string()),
+            Int("2".to_string(), String::new()),
+            Eof
+        ]
+    );
+    let err = |src: &str| Tokenizer::default().tokenize(src).unwrap_err().to_string();
+    assert_eq!(err("1 +\n 'a'"), "2:2: unexpected character `'`");
+    assert_eq!(err("1 /* abc"), "1:3: unterminated block comment");
+    assert_eq!(err("1 /* a /* b */"), "1:3: unterminated block comment");
+    assert_eq!(err("1.5 + 2"), "1:1: float literals are not supported");
+    assert_eq!(err("x + \"s\""), "1:5: unexpected character `\"`");
+    // nested block comments, and nested fields
+    let src = "1 /* a /* b */ c */ + t.0.1 /** d /* e */ */";
+    assert_eq!(kinds(src), kinds("1 + t.0.1 /** d /* e */ */"));
+    let tokens = Tokenizer::default().tokenize(src).unwrap();
+    assert_eq!(tokens, crate::lexer::lex(src).unwrap());
+    // the tokens are parsed by the standalone parser
+    let tokens = Tokenizer::default()
+        .tokenize("{ let x = 1 <> 2; /* */ x || false }")
+        .unwrap();
+    let e = crate::lexer_parse::Parser::new(&tokens).expr().unwrap();
+    assert_eq!(e.eval(), crate::ast::Literal::Bool(true));
+    // doc comments, as by the standalone lexer
+    let src = "/// a\n//// b\n1 /** c */ /***/ /**/ + 2";
+    let tokens = Tokenizer::default().tokenize(src).unwrap();
+    assert_eq!(tokens, crate::lexer::lex(src).unwrap());
+}
+
+// The tokens, and their positions, are those of proc_macro2
+#[test]
+fn regex_lexer_proc_macro2() {
+    use crate::generate::{Config, Generator};
+    let mut sources: Vec<String> = vec![
+        "{ let mut x = 1;\n  x = x + 2 * 3; // comment\n  x <= 10 && !false }".to_string(),
+        "match e { Enum::A(a) => [a, 1_000u8][0], _ => t.0 }".to_string(),
+        "/* a\n comment */ -1-2--3 <= (1<2) == true||x".to_string(),
+        "a=>b::c**d<>e&&&f".to_string(),
+        "1 /* a /* b\n */ c */ + t.0".to_string(),
+    ];
+    let config = Config {
+        parens: 30,
+        ..Default::default()
+    };
+    let mut g = Generator::new(9, config);
+    sources.extend((0..200).map(|_| g.expr().to_string()));
+    for s in &sources {
+        let mut expected = vec![];
+        proc_macro2_tokens(s.parse().unwrap(), &mut expected);
+        assert_eq!(regex_tokens(s), expected, "{}", s);
+    }
+}
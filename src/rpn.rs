@@ -0,0 +1,263 @@
+// Reverse Polish notation (RPN), by Dijkstra's shunting-yard algorithm.
+//
+// A third way (after `climb` and the Pratt parser) to respect the operator
+// priorities. The infix tokens are read left to right, operands go straight
+// to the output while operators wait on a stack until an operator binding
+// weaker (or as weak, all operators are left associative) arrives. So
+// `2 - 3 * 4` becomes `2 3 4 * -`.
+//
+// Only literals, binary operators and parentheses are supported.
+use crate::ast::{ArithMode, EvalError, Expr, Literal, Op, Span};
+use std::fmt;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    token, Result,
+};
+
+// An infix token, parentheses are not part of the RPN
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Lit(Literal),
+    Op(Op, Span),
+    LParen,
+    RParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Lit(l) => write!(f, "{}", l),
+            Token::Op(op, _) => write!(f, "{}", op),
+            Token::LParen => f.write_str("("),
+            Token::RParen => f.write_str(")"),
+        }
+    }
+}
+
+// The infix tokens of an expression, `operand (op operand)*` where
+// an operand is a literal or a parenthesized expression
+pub fn tokens(input: ParseStream) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    infix(input, &mut tokens)?;
+    Ok(tokens)
+}
+
+fn infix(input: ParseStream, tokens: &mut Vec<Token>) -> Result<()> {
+    loop {
+        if input.peek(token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            tokens.push(Token::LParen);
+            infix(&content, tokens)?;
+            tokens.push(Token::RParen);
+        } else {
+            tokens.push(Token::Lit(input.parse()?));
+        }
+        if input.is_empty() {
+            return Ok(());
+        }
+        let span = input.span();
+        tokens.push(Token::Op(input.parse()?, span.into()));
+    }
+}
+
+// Operands and operators in postfix order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rpn(Vec<Token>);
+
+impl Rpn {
+    pub fn tokens(&self) -> &[Token] {
+        &self.0
+    }
+
+    // Evaluate on a stack. Unlike `Expr::eval` both operands of `&&`
+    // and `||` are evaluated, RPN has no control flow.
+    pub fn eval(&self) -> std::result::Result<Literal, EvalError> {
+        self.eval_with(ArithMode::Checked)
+    }
+
+    // Evaluate using the given arithmetic mode
+    pub fn eval_with(&self, mode: ArithMode) -> std::result::Result<Literal, EvalError> {
+        let mut stack = vec![];
+        for t in &self.0 {
+            match t {
+                Token::Lit(l) => stack.push(l.clone()),
+                Token::Op(op, span) => {
+                    let r = stack.pop().unwrap();
+                    let l = stack.pop().unwrap();
+                    stack.push(
+                        op.eval_with(mode, l, r)
+                            .map_err(|kind| EvalError::new(kind, *span))?,
+                    );
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(stack.pop().unwrap())
+    }
+
+    // The expression tree, without parentheses
+    pub fn to_expr(&self) -> Expr {
+        let mut stack = vec![];
+        for t in &self.0 {
+            match t {
+                Token::Lit(l) => stack.push(Expr::Lit(l.clone())),
+                Token::Op(op, span) => {
+                    let r = stack.pop().unwrap();
+                    let l = stack.pop().unwrap();
+                    stack.push(Expr::BinOp(*op, Box::new(l), Box::new(r), *span));
+                }
+                _ => unreachable!(),
+            }
+        }
+        stack.pop().unwrap()
+    }
+}
+
+impl fmt::Display for Rpn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tokens: Vec<String> = self.0.iter().map(|t| t.to_string()).collect();
+        f.write_str(&tokens.join(" "))
+    }
+}
+
+// Dijkstra's shunting-yard algorithm, None if the tokens are not
+// a well formed infix expression (e.g., unbalanced parentheses)
+pub fn shunting_yard(tokens: &[Token]) -> Option<Rpn> {
+    let mut output = vec![];
+    let mut stack: Vec<Token> = vec![];
+    // alternating operands and operators
+    let mut operand = true;
+    for t in tokens {
+        match t {
+            Token::Lit(_) if operand => {
+                output.push(t.clone());
+                operand = false;
+            }
+            Token::LParen if operand => stack.push(Token::LParen),
+            Token::RParen if !operand => loop {
+                match stack.pop()? {
+                    Token::LParen => break,
+                    op => output.push(op),
+                }
+            },
+            Token::Op(op, _) if !operand => {
+                // pop the operators binding at least as hard
+                while let Some(Token::Op(top, _)) = stack.last() {
+                    if top.priority() < op.priority() {
+                        break;
+                    }
+                    output.push(stack.pop().unwrap());
+                }
+                stack.push(t.clone());
+                operand = true;
+            }
+            _ => return None,
+        }
+    }
+    if operand {
+        return None;
+    }
+    while let Some(t) = stack.pop() {
+        match t {
+            Token::LParen => return None,
+            op => output.push(op),
+        }
+    }
+    Some(Rpn(output))
+}
+
+impl Parse for Rpn {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let tokens = tokens(input)?;
+        // the tokens are well formed by construction
+        Ok(shunting_yard(&tokens).unwrap())
+    }
+}
+
+// The postfix order of a tree, as `Rpn` (parentheses dropped)
+#[cfg(test)]
+fn postfix(e: &Expr, out: &mut Vec<Token>) {
+    match e {
+        Expr::Lit(l) => out.push(Token::Lit(l.clone())),
+        Expr::Par(e) => postfix(e, out),
+        Expr::BinOp(op, l, r, span) => {
+            postfix(l, out);
+            postfix(r, out);
+            out.push(Token::Op(*op, *span));
+        }
+        _ => panic!("not supported {}", e),
+    }
+}
+
+#[test]
+fn rpn_shunting_yard() {
+    let rpn: Rpn = syn::parse_str("2 - 3 * 4").unwrap();
+    assert_eq!(rpn.to_string(), "2 3 4 * -");
+    assert_eq!(rpn.eval(), Ok(Literal::Int(2 - 3 * 4)));
+    let rpn: Rpn = syn::parse_str("8 - 4 - 2 * 3 / 2").unwrap();
+    assert_eq!(rpn.to_string(), "8 4 - 2 3 * 2 / -");
+    let rpn: Rpn = syn::parse_str("(2 - 3) * (4 - (5 - 6))").unwrap();
+    assert_eq!(rpn.to_string(), "2 3 - 4 5 6 - - *");
+    assert_eq!(rpn.to_expr().to_string(), "(2 - 3) * (4 - (5 - 6))");
+    let rpn: Rpn = syn::parse_str("1 < 2 == true && 3 >= -4 || false").unwrap();
+    assert_eq!(rpn.to_string(), "1 2 < true == 3 -4 >= && false ||");
+    assert_eq!(rpn.eval(), Ok(Literal::Bool(true)));
+}
+
+#[test]
+fn rpn_errors() {
+    use crate::ast::EvalErrorKind;
+    for s in &["", "1 +", "(1 - )", "1 2", "()", "(1 2)", "5 % 2", "x + 1"] {
+        assert!(syn::parse_str::<Rpn>(s).is_err(), "{}", s);
+    }
+    // unbalanced or ill formed tokens
+    let one = Token::Lit(Literal::Int(1));
+    let plus = Token::Op(Op::Add, Span::default());
+    for tokens in &[
+        vec![Token::LParen, one.clone()],
+        vec![one.clone(), Token::RParen],
+        vec![one.clone(), plus.clone()],
+        vec![one.clone(), one.clone()],
+        vec![Token::RParen, one.clone(), Token::LParen],
+    ] {
+        assert_eq!(shunting_yard(tokens), None, "{:?}", tokens);
+    }
+    let tokens = [Token::LParen, one.clone(), plus, one, Token::RParen];
+    assert_eq!(shunting_yard(&tokens).unwrap().to_string(), "1 1 +");
+    // both operands are evaluated
+    let rpn: Rpn = syn::parse_str("false && 1 / 0 == 1").unwrap();
+    assert_eq!(rpn.eval().unwrap_err().kind, EvalErrorKind::DivByZero);
+    assert_eq!(rpn.to_expr().eval(), Literal::Bool(false));
+    // the arithmetic modes
+    let rpn: Rpn = syn::parse_str("250u8 + 10u8 - 1u8").unwrap();
+    assert_eq!(rpn.eval().unwrap_err().kind, EvalErrorKind::Overflow);
+    assert_eq!(rpn.eval_with(ArithMode::Wrapping), Ok(Literal::U8(3)));
+    assert_eq!(rpn.eval_with(ArithMode::Saturating), Ok(Literal::U8(254)));
+}
+
+// Cross-check the shunting-yard against `climb`
+#[test]
+fn rpn_climb() {
+    for s in crate::generate::generated_sources(6, 1000) {
+        let rpn: Rpn = syn::parse_str(&s).unwrap();
+        let e = crate::parse::climb(syn::parse_str(&s).unwrap());
+        let mut expected = vec![];
+        postfix(&e, &mut expected);
+        assert_eq!(rpn.tokens(), &expected[..], "{}", s);
+        let mut round_trip = vec![];
+        postfix(&rpn.to_expr(), &mut round_trip);
+        assert_eq!(round_trip, expected, "{}", s);
+        // equal if the strict evaluation succeeds
+        for mode in &[
+            ArithMode::Checked,
+            ArithMode::Wrapping,
+            ArithMode::Saturating,
+        ] {
+            if let Ok(v) = rpn.eval_with(*mode) {
+                assert_eq!(e.eval_with(*mode), Ok(v), "{} {:?}", s, mode);
+            }
+        }
+    }
+}
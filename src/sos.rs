@@ -0,0 +1,446 @@
+// Small-step structural operational semantics (SOS).
+//
+// `Expr::step` performs exactly one reduction `<e, σ> → <e', σ'>`, where
+// the store σ maps variables to values. Operands are reduced left to right,
+// e.g., for binary operators (besides `&&` and `||`, which short circuit)
+//
+//   <e1, σ> → <e1', σ'>
+//   ---------------------------------   (left)
+//   <e1 op e2, σ> → <e1' op e2, σ'>
+//
+//   <e2, σ> → <e2', σ'>
+//   ---------------------------------   (right)
+//   <v1 op e2, σ> → <v1 op e2', σ'>
+//
+//   <v1 op v2, σ> → <v, σ>   where v = v1 op v2   (op)
+//
+// Parentheses (and blocks without statements) are dropped in the step
+// their content becomes a value, so `(2 - 3) * 4 → -1 * 4 → -4`.
+//
+// Tuples, arrays, structs and variants of values are values. A `let`
+// (and a pattern binding) adds the variable to σ and renames later uses
+// to a fresh name (`x'`) if the name is already in σ, references are
+// indices into σ.
+use crate::ast::{ArithMode, EvalError, EvalErrorKind, Expr, Literal, Member, Op, Span, Stmt};
+use std::fmt;
+
+// The variables and their values, a reference is an index
+pub type Store = Vec<(String, Literal)>;
+
+impl Expr {
+    pub fn is_value(&self) -> bool {
+        self.value().is_some()
+    }
+
+    // The value of a value expression
+    pub fn value(&self) -> Option<Literal> {
+        let all = |es: &[Expr]| es.iter().map(|e| e.value()).collect::<Option<Vec<_>>>();
+        match self {
+            Expr::Lit(l) => Some(l.clone()),
+            Expr::Tuple(es) => Some(Literal::Tuple(all(es)?)),
+            Expr::Array(es, _) => Some(Literal::Array(all(es)?)),
+            Expr::Variant(e, v, es, _) => Some(Literal::Enum(e.clone(), v.clone(), all(es)?)),
+            Expr::Struct(name, fields, _) => {
+                let mut fields = fields
+                    .iter()
+                    .map(|(f, e)| Some((f.clone(), e.value()?)))
+                    .collect::<Option<Vec<_>>>()?;
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Some(Literal::Struct(name.clone(), fields))
+            }
+            _ => None,
+        }
+    }
+
+    // One reduction step, None if the expression is a value
+    pub fn step(&self, mode: ArithMode, store: &mut Store) -> Result<Option<Expr>, EvalError> {
+        if self.is_value() {
+            return Ok(None);
+        }
+        let err = |kind, span: &Span| Err(EvalError::new(kind, *span));
+        Ok(Some(match self {
+            Expr::Par(e) => match e.step(mode, store)? {
+                Some(e) if !e.is_value() => Expr::Par(Box::new(e)),
+                Some(e) => e,
+                None => (**e).clone(),
+            },
+            // (and) and (or) short circuit
+            Expr::BinOp(op, l, r, span) => match (l.step(mode, store)?, op, l.value()) {
+                (Some(l), ..) => Expr::BinOp(*op, Box::new(l), r.clone(), *span),
+                (None, Op::And, Some(Literal::Bool(false)))
+                | (None, Op::Or, Some(Literal::Bool(true))) => (**l).clone(),
+                (None, _, Some(lv)) => match r.step(mode, store)? {
+                    Some(r) => Expr::BinOp(*op, l.clone(), Box::new(r), *span),
+                    None => match op.eval_with(mode, lv, r.value().unwrap()) {
+                        Ok(v) => Expr::Lit(v),
+                        Err(kind) => return err(kind, span),
+                    },
+                },
+                (None, _, None) => unreachable!(),
+            },
+            Expr::Cast(e, ty, span) => match e.step(mode, store)? {
+                Some(e) => Expr::Cast(Box::new(e), ty.clone(), *span),
+                None => match e.value().unwrap().cast(ty) {
+                    Some(v) => Expr::Lit(v),
+                    None => return err(EvalErrorKind::InvalidCast, span),
+                },
+            },
+            Expr::Call(f, args, span) => match step_first(args, mode, store)? {
+                Some(args) => Expr::Call(*f, args, *span),
+                None => match f.eval(args.iter().map(|a| a.value().unwrap()).collect()) {
+                    Ok(v) => Expr::Lit(v),
+                    Err(kind) => return err(kind, span),
+                },
+            },
+            Expr::Tuple(es) => Expr::Tuple(step_first(es, mode, store)?.unwrap()),
+            Expr::Array(es, span) => Expr::Array(step_first(es, mode, store)?.unwrap(), *span),
+            Expr::Variant(e, v, es, span) => Expr::Variant(
+                e.clone(),
+                v.clone(),
+                step_first(es, mode, store)?.unwrap(),
+                *span,
+            ),
+            Expr::Struct(name, fields, span) => {
+                let (fs, es): (Vec<_>, Vec<_>) = fields.iter().cloned().unzip();
+                let es = step_first(&es, mode, store)?.unwrap();
+                Expr::Struct(name.clone(), fs.into_iter().zip(es).collect(), *span)
+            }
+            Expr::Repeat(e, n) => match e.step(mode, store)? {
+                Some(e) => Expr::Repeat(Box::new(e), *n),
+                None => Expr::Lit(Literal::Array(vec![e.value().unwrap(); *n])),
+            },
+            Expr::Index(a, i, span) => match a.step(mode, store)? {
+                Some(a) => Expr::Index(Box::new(a), i.clone(), *span),
+                None => match i.step(mode, store)? {
+                    Some(i) => Expr::Index(a.clone(), Box::new(i), *span),
+                    None => match (a.value().unwrap(), i.value().unwrap().to_index()) {
+                        (Literal::Array(a), Some(i)) if i < a.len() => Expr::Lit(a[i].clone()),
+                        _ => return err(EvalErrorKind::OutOfBounds, span),
+                    },
+                },
+            },
+            Expr::Field(t, m, span) => match t.step(mode, store)? {
+                Some(t) => Expr::Field(Box::new(t), m.clone(), *span),
+                None => match (t.value().unwrap(), m) {
+                    (Literal::Tuple(t), Member::Index(n)) if *n < t.len() => {
+                        Expr::Lit(t[*n].clone())
+                    }
+                    (Literal::Struct(_, fields), Member::Named(name)) => {
+                        match fields.into_iter().find(|(f, _)| f == name) {
+                            Some((_, v)) => Expr::Lit(v),
+                            None => return err(EvalErrorKind::TypeMismatch, span),
+                        }
+                    }
+                    _ => return err(EvalErrorKind::TypeMismatch, span),
+                },
+            },
+            Expr::Var(x, span) => match store.iter().rev().find(|(y, _)| x == y) {
+                Some((_, v)) => Expr::Lit(v.clone()),
+                None => return err(EvalErrorKind::UnboundVar, span),
+            },
+            // the first matching arm, with the bindings added to the store
+            Expr::Match(e, arms, span) => match e.step(mode, store)? {
+                Some(e) => Expr::Match(Box::new(e), arms.clone(), *span),
+                None => {
+                    let v = e.value().unwrap();
+                    let mut bound = vec![];
+                    match arms.iter().find(|arm| {
+                        bound.clear();
+                        arm.pat.matches(&v, &mut bound)
+                    }) {
+                        Some(arm) => {
+                            let mut body = arm.body.clone();
+                            for (x, v) in bound {
+                                let y = bind(store, &x, v);
+                                rename(&mut body, &x, &y);
+                            }
+                            body
+                        }
+                        None => return err(EvalErrorKind::NoMatch, span),
+                    }
+                }
+            },
+            Expr::Block(stmts, e, span) => match stmts.split_first() {
+                None => match e.step(mode, store)? {
+                    Some(e) if !e.is_value() => Expr::Block(vec![], Box::new(e), *span),
+                    Some(e) => e,
+                    None => (**e).clone(),
+                },
+                Some((stmt, rest)) => {
+                    let mut rest = rest.to_vec();
+                    let mut e = e.clone();
+                    let stmt = match stmt {
+                        Stmt::Let(x, m, init, s) => match init.step(mode, store)? {
+                            Some(init) => Some(Stmt::Let(x.clone(), *m, init, *s)),
+                            None => {
+                                let y = bind(store, x, init.value().unwrap());
+                                if rename_stmts(&mut rest, x, &y) {
+                                    rename(&mut e, x, &y);
+                                }
+                                None
+                            }
+                        },
+                        Stmt::Assign(place, rhs, s) => match rhs.step(mode, store)? {
+                            Some(rhs) => Some(Stmt::Assign(place.clone(), rhs, *s)),
+                            None => match step_place(place, mode, store)? {
+                                Ok(place) => Some(Stmt::Assign(place, rhs.clone(), *s)),
+                                Err(i) => {
+                                    store[i].1 = rhs.value().unwrap();
+                                    None
+                                }
+                            },
+                        },
+                        Stmt::Expr(s) => s.step(mode, store)?.map(Stmt::Expr),
+                    };
+                    Expr::Block(stmt.into_iter().chain(rest).collect(), e, *span)
+                }
+            },
+            Expr::Ref(m, place, span) => match step_place(place, mode, store)? {
+                Ok(place) => Expr::Ref(*m, Box::new(place), *span),
+                Err(i) => Expr::Lit(Literal::Ref(i)),
+            },
+            Expr::Deref(e, span) => match e.step(mode, store)? {
+                Some(e) => Expr::Deref(Box::new(e), *span),
+                None => match e.value().unwrap() {
+                    Literal::Ref(i) if i < store.len() => Expr::Lit(store[i].1.clone()),
+                    Literal::Ref(_) => return err(EvalErrorKind::Dangling, span),
+                    _ => return err(EvalErrorKind::TypeMismatch, span),
+                },
+            },
+            Expr::Lit(_) => unreachable!(),
+        }))
+    }
+
+    // The reduction sequence, from the expression to its value,
+    // with the store after each step
+    pub fn trace(&self, mode: ArithMode) -> Result<Trace, EvalError> {
+        let mut store = vec![];
+        let mut steps = vec![(self.clone(), store.clone())];
+        while let Some(e) = steps.last().unwrap().0.step(mode, &mut store)? {
+            steps.push((e, store.clone()));
+        }
+        Ok(Trace(steps))
+    }
+}
+
+// A reduction sequence, printed as `(2 - 3) * 4 → -1 * 4 → -4`,
+// configurations with a non empty store as `<e, [x ↦ 1]>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace(pub Vec<(Expr, Store)>);
+
+impl Trace {
+    // The final value
+    pub fn value(&self) -> Literal {
+        self.0.last().unwrap().0.value().unwrap()
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (e, store)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" → ")?;
+            }
+            if store.is_empty() {
+                write!(f, "{}", e)?;
+            } else {
+                let store: Vec<_> = store
+                    .iter()
+                    .map(|(x, v)| format!("{} ↦ {}", x, v))
+                    .collect();
+                write!(f, "<{}, [{}]>", e, store.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Reduce the first expression that is not a value, None if all are values
+fn step_first(
+    es: &[Expr],
+    mode: ArithMode,
+    store: &mut Store,
+) -> Result<Option<Vec<Expr>>, EvalError> {
+    for (i, e) in es.iter().enumerate() {
+        if let Some(e) = e.step(mode, store)? {
+            let mut es = es.to_vec();
+            es[i] = e;
+            return Ok(Some(es));
+        }
+    }
+    Ok(None)
+}
+
+// Reduce a place expression (`x` or `*e`), the index of the
+// variable once reduced
+fn step_place(
+    place: &Expr,
+    mode: ArithMode,
+    store: &mut Store,
+) -> Result<Result<Expr, usize>, EvalError> {
+    match place {
+        Expr::Var(x, span) => match store.iter().rposition(|(y, _)| x == y) {
+            Some(i) => Ok(Err(i)),
+            None => Err(EvalError::new(EvalErrorKind::UnboundVar, *span)),
+        },
+        Expr::Par(e) => step_place(e, mode, store),
+        Expr::Deref(e, span) => match e.step(mode, store)? {
+            Some(e) => Ok(Ok(Expr::Deref(Box::new(e), *span))),
+            None => match e.value().unwrap() {
+                Literal::Ref(i) => Ok(Err(i)),
+                _ => Err(EvalError::new(EvalErrorKind::TypeMismatch, *span)),
+            },
+        },
+        // rejected by the type checker
+        _ => Err(EvalError::new(EvalErrorKind::TypeMismatch, Span::default())),
+    }
+}
+
+// Add `x` to the store, as `x'` (`x''`, ...) if `x` is already in the store
+fn bind(store: &mut Store, x: &str, v: Literal) -> String {
+    let mut y = x.to_string();
+    while store.iter().any(|(z, _)| *z == y) {
+        y.push('\'');
+    }
+    store.push((y.clone(), v));
+    y
+}
+
+// Rename the free occurrences of `x` to `y`
+fn rename(e: &mut Expr, x: &str, y: &str) {
+    if x == y {
+        return;
+    }
+    match e {
+        Expr::Var(z, _) if z == x => *z = y.to_string(),
+        Expr::Lit(_) | Expr::Var(..) => {}
+        Expr::BinOp(_, l, r, _) | Expr::Index(l, r, _) => {
+            rename(l, x, y);
+            rename(r, x, y);
+        }
+        Expr::Par(e)
+        | Expr::Cast(e, ..)
+        | Expr::Repeat(e, _)
+        | Expr::Field(e, ..)
+        | Expr::Ref(_, e, _)
+        | Expr::Deref(e, _) => rename(e, x, y),
+        Expr::Call(_, es, _)
+        | Expr::Tuple(es)
+        | Expr::Array(es, _)
+        | Expr::Variant(_, _, es, _) => es.iter_mut().for_each(|e| rename(e, x, y)),
+        Expr::Struct(_, fields, _) => fields.iter_mut().for_each(|(_, e)| rename(e, x, y)),
+        Expr::Match(e, arms, _) => {
+            rename(e, x, y);
+            for arm in arms {
+                if !arm.pat.bindings().iter().any(|z| z == x) {
+                    rename(&mut arm.body, x, y);
+                }
+            }
+        }
+        Expr::Block(stmts, e, _) => {
+            if rename_stmts(stmts, x, y) {
+                rename(e, x, y);
+            }
+        }
+    }
+}
+
+// Rename in the statements, false if `x` is shadowed by a `let`
+fn rename_stmts(stmts: &mut [Stmt], x: &str, y: &str) -> bool {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Let(z, _, e, _) => {
+                rename(e, x, y);
+                if z == x {
+                    return false;
+                }
+            }
+            Stmt::Assign(place, e, _) => {
+                rename(place, x, y);
+                rename(e, x, y);
+            }
+            Stmt::Expr(e) => rename(e, x, y),
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+fn trace(s: &str) -> Trace {
+    let ts: proc_macro2::TokenStream = s.parse().unwrap();
+    let p: crate::ast::Program = syn::parse2(ts).unwrap();
+    p.expr.trace(Default::default()).unwrap()
+}
+
+#[test]
+fn sos_arith() {
+    assert_eq!(
+        trace("(2 - 3) * 4").to_string(),
+        "(2 - 3) * 4 → -1 * 4 → -4"
+    );
+    assert_eq!(
+        trace("8 - 7 - 6 * 5").to_string(),
+        "8 - 7 - 6 * 5 → 1 - 6 * 5 → 1 - 30 → -29"
+    );
+    // one reduction per step
+    let ts: proc_macro2::TokenStream = "1 + 2 * 3".parse().unwrap();
+    let e = crate::parse::climb(syn::parse2(ts).unwrap());
+    let mut store = vec![];
+    let e = e.step(Default::default(), &mut store).unwrap().unwrap();
+    assert_eq!(e.to_string(), "1 + 6");
+    let e = e.step(Default::default(), &mut store).unwrap().unwrap();
+    assert_eq!(e.to_string(), "7");
+    assert_eq!(e.step(Default::default(), &mut store), Ok(None));
+}
+
+#[test]
+fn sos_short_circuit() {
+    assert_eq!(
+        trace("1 > 2 && 1 / 0 == 1").to_string(),
+        "1 > 2 && 1 / 0 == 1 → false && 1 / 0 == 1 → false"
+    );
+    let ts: proc_macro2::TokenStream = "2 - 1 / (1 - 1)".parse().unwrap();
+    let p: crate::ast::Program = syn::parse2(ts).unwrap();
+    let err = p.expr.trace(Default::default()).unwrap_err();
+    assert_eq!(err.kind, EvalErrorKind::DivByZero);
+}
+
+#[test]
+fn sos_compound() {
+    assert_eq!(
+        trace("[1 + 1, 3][0] as u8").to_string(),
+        "[1 + 1, 3][0] as u8 → [2, 3][0] as u8 → 2 as u8 → 2u8"
+    );
+    assert_eq!(
+        trace("enum E { A(i32), B } match E::A(1 + 1) { E::A(x) => x * 2, E::B => 0 }").to_string(),
+        "match E::A(1 + 1) { E::A(x) => x * 2, E::B => 0 } → match E::A(2) { E::A(x) => x * 2, E::B => 0 } \
+         → <x * 2, [x ↦ 2]> → <2 * 2, [x ↦ 2]> → <4, [x ↦ 2]>"
+    );
+}
+
+#[test]
+fn sos_store() {
+    assert_eq!(
+        trace("{ let mut x = 1; let r = &mut x; *r = 2; x }").to_string(),
+        "{ let mut x = 1; let r = &mut x; *r = 2; x } \
+         → <{ let r = &mut x; *r = 2; x }, [x ↦ 1]> \
+         → <{ let r = &{0}; *r = 2; x }, [x ↦ 1]> \
+         → <{ *r = 2; x }, [x ↦ 1, r ↦ &{0}]> \
+         → <{ *&{0} = 2; x }, [x ↦ 1, r ↦ &{0}]> \
+         → <{ x }, [x ↦ 2, r ↦ &{0}]> \
+         → <2, [x ↦ 2, r ↦ &{0}]>"
+    );
+    // shadowed variables are renamed
+    let t = trace("{ let x = 1; let y = { let x = 2; x }; x + y }");
+    assert_eq!(t.value(), Literal::Int(3));
+    assert!(t.to_string().contains("x' ↦ 2"));
+    // the trace agrees with the big-step evaluation
+    let ts: proc_macro2::TokenStream = "{ let x = (1, 2); let y = x.1 * 3; [x.0, y][1] }"
+        .parse()
+        .unwrap();
+    let e = crate::parse::climb(syn::parse2(ts).unwrap());
+    assert_eq!(
+        Ok(e.trace(Default::default()).unwrap().value()),
+        e.eval_with(Default::default())
+    );
+}
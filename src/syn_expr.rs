@@ -0,0 +1,444 @@
+// Conversion of a `syn::Expr` to an `ast::Expr`.
+//
+// `syn` parses the complete Rust expression syntax, with Rust's precedence
+// and associativity. The conversion accepts the subset supported by our own
+// parser (see `src/grammar.rs`), other expressions are errors at their span.
+//
+// The two parsers should agree, `differential` parses the same source with
+// both, `impl Parse for Expr` followed by `climb` and `syn::Expr` followed
+// by the conversion, and compares the trees and their values.
+use crate::{
+    ast::{Arm, Builtin, EvalError, Expr, Literal, Member, Op, Pattern, Stmt},
+    typecheck::Type,
+};
+use std::{convert::TryFrom, fmt};
+use syn::spanned::Spanned;
+
+fn unsupported<T>(node: &impl Spanned, what: &str) -> syn::Result<T> {
+    Err(syn::Error::new(
+        node.span(),
+        format!("{} are not supported", what),
+    ))
+}
+
+fn all(
+    es: impl IntoIterator<Item = impl std::borrow::Borrow<syn::Expr>>,
+) -> syn::Result<Vec<Expr>> {
+    es.into_iter().map(|e| Expr::try_from(e.borrow())).collect()
+}
+
+// `-1` is a (negative) literal in our syntax, but negation
+// of a literal in Rust's
+fn negative(lit: &syn::Lit) -> Option<syn::Lit> {
+    match lit {
+        syn::Lit::Int(l) => Some(syn::LitInt::new(&format!("-{}", l), l.span()).into()),
+        syn::Lit::Float(l) => Some(syn::LitFloat::new(&format!("-{}", l), l.span()).into()),
+        _ => None,
+    }
+}
+
+fn literal(e: &syn::Expr) -> syn::Result<Literal> {
+    match e {
+        syn::Expr::Lit(l) => Literal::try_from(l.lit.clone()),
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => match &**expr {
+            syn::Expr::Lit(l) => match negative(&l.lit) {
+                Some(l) => Literal::try_from(l),
+                None => unsupported(e, "negated non numeric literals"),
+            },
+            _ => unsupported(e, "negations"),
+        },
+        _ => Err(syn::Error::new(e.span(), "expected literal")),
+    }
+}
+
+// `Enum::Variant`, or a single identifier
+fn path(p: &syn::Path) -> syn::Result<Vec<syn::Ident>> {
+    if p.leading_colon.is_some()
+        || p.segments.len() > 2
+        || p.segments
+            .iter()
+            .any(|s| !matches!(s.arguments, syn::PathArguments::None))
+    {
+        return unsupported(p, "paths other than `x` and `Enum::Variant`");
+    }
+    Ok(p.segments.iter().map(|s| s.ident.clone()).collect())
+}
+
+fn op(op: &syn::BinOp) -> syn::Result<Op> {
+    Ok(match op {
+        syn::BinOp::Add(_) => Op::Add,
+        syn::BinOp::Sub(_) => Op::Sub,
+        syn::BinOp::Mul(_) => Op::Mul,
+        syn::BinOp::Div(_) => Op::Div,
+        syn::BinOp::Eq(_) => Op::Eq,
+        syn::BinOp::Ne(_) => Op::Ne,
+        syn::BinOp::Lt(_) => Op::Lt,
+        syn::BinOp::Le(_) => Op::Le,
+        syn::BinOp::Gt(_) => Op::Gt,
+        syn::BinOp::Ge(_) => Op::Ge,
+        syn::BinOp::And(_) => Op::And,
+        syn::BinOp::Or(_) => Op::Or,
+        _ => return Err(syn::Error::new(
+            op.span(),
+            "expected operator `+`, `-`, `*`, `/`, `==`, `!=`, `<`, `<=`, `>`, `>=`, `&&` or `||`",
+        )),
+    })
+}
+
+impl TryFrom<&syn::Expr> for Expr {
+    type Error = syn::Error;
+
+    fn try_from(e: &syn::Expr) -> syn::Result<Self> {
+        Ok(match e {
+            syn::Expr::Lit(_) => Expr::Lit(literal(e)?),
+            syn::Expr::Binary(b) => Expr::BinOp(
+                op(&b.op)?,
+                Box::new(Expr::try_from(&*b.left)?),
+                Box::new(Expr::try_from(&*b.right)?),
+                b.op.span().into(),
+            ),
+            syn::Expr::Paren(p) => Expr::Par(Box::new(Expr::try_from(&*p.expr)?)),
+            // invisible delimiters, from macro expansion
+            syn::Expr::Group(g) => Expr::try_from(&*g.expr)?,
+            syn::Expr::Unary(u) => match u.op {
+                syn::UnOp::Deref(star) => {
+                    Expr::Deref(Box::new(Expr::try_from(&*u.expr)?), star.span.into())
+                }
+                syn::UnOp::Neg(_) => Expr::Lit(literal(e)?),
+                syn::UnOp::Not(_) => return unsupported(e, "`!` expressions"),
+            },
+            syn::Expr::Reference(r) => Expr::Ref(
+                r.mutability.is_some(),
+                Box::new(Expr::try_from(&*r.expr)?),
+                r.and_token.span.into(),
+            ),
+            syn::Expr::Cast(c) => {
+                let ty = match &*c.ty {
+                    syn::Type::Path(p) if p.qself.is_none() => p
+                        .path
+                        .get_ident()
+                        .and_then(|ty| Type::from_name(&ty.to_string())),
+                    _ => None,
+                };
+                match ty {
+                    Some(ty) => Expr::Cast(
+                        Box::new(Expr::try_from(&*c.expr)?),
+                        ty,
+                        c.as_token.span.into(),
+                    ),
+                    None => return Err(syn::Error::new(c.ty.span(), "unknown type")),
+                }
+            }
+            // `f(e, ...)` or `Enum::Variant(e, ...)`
+            syn::Expr::Call(c) => {
+                let p = match &*c.func {
+                    syn::Expr::Path(p) if p.qself.is_none() => path(&p.path)?,
+                    _ => return unsupported(&c.func, "calls of expressions"),
+                };
+                let args = all(&c.args)?;
+                match &p[..] {
+                    [f] => match Builtin::from_name(&f.to_string()) {
+                        Some(b) => Expr::Call(b, args, f.span().into()),
+                        None => {
+                            return Err(syn::Error::new(
+                                f.span(),
+                                format!("unknown function `{}`", f),
+                            ))
+                        }
+                    },
+                    [en, v] => Expr::Variant(en.to_string(), v.to_string(), args, v.span().into()),
+                    _ => unreachable!(),
+                }
+            }
+            syn::Expr::Tuple(t) => Expr::Tuple(all(&t.elems)?),
+            syn::Expr::Array(a) => Expr::Array(all(&a.elems)?, a.bracket_token.span.into()),
+            syn::Expr::Repeat(r) => {
+                let n = match &*r.len {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Int(n),
+                        ..
+                    }) => crate::parse::repeat_len(n)?,
+                    len => return Err(syn::Error::new(len.span(), "expected integer literal")),
+                };
+                Expr::Repeat(Box::new(Expr::try_from(&*r.expr)?), n)
+            }
+            syn::Expr::Index(i) => Expr::Index(
+                Box::new(Expr::try_from(&*i.expr)?),
+                Box::new(Expr::try_from(&*i.index)?),
+                i.index.span().into(),
+            ),
+            syn::Expr::Field(f) => {
+                let (m, span) = match &f.member {
+                    syn::Member::Named(x) => (Member::Named(x.to_string()), x.span()),
+                    syn::Member::Unnamed(i) => (Member::Index(i.index as usize), i.span),
+                };
+                Expr::Field(Box::new(Expr::try_from(&*f.base)?), m, span.into())
+            }
+            syn::Expr::Struct(s) => {
+                let name = match &path(&s.path)?[..] {
+                    [name] => name.clone(),
+                    _ => return unsupported(&s.path, "enum struct variants"),
+                };
+                if let Some(rest) = &s.rest {
+                    return unsupported(rest, "struct update expressions");
+                }
+                let mut fields = vec![];
+                for f in &s.fields {
+                    match (&f.member, f.colon_token) {
+                        (syn::Member::Named(x), Some(_)) => {
+                            fields.push((x.to_string(), Expr::try_from(&f.expr)?))
+                        }
+                        _ => return unsupported(f, "shorthand and numbered fields"),
+                    }
+                }
+                Expr::Struct(name.to_string(), fields, name.span().into())
+            }
+            syn::Expr::Path(p) if p.qself.is_none() => match &path(&p.path)?[..] {
+                [x] => Expr::Var(x.to_string(), x.span().into()),
+                [en, v] => Expr::Variant(en.to_string(), v.to_string(), vec![], v.span().into()),
+                _ => unreachable!(),
+            },
+            syn::Expr::Match(m) => {
+                let mut arms = vec![];
+                for arm in &m.arms {
+                    if let Some((if_token, _)) = &arm.guard {
+                        return unsupported(if_token, "match guards");
+                    }
+                    arms.push(Arm {
+                        pat: Pattern::try_from(&arm.pat)?,
+                        body: Expr::try_from(&*arm.body)?,
+                        span: arm.pat.span().into(),
+                    });
+                }
+                Expr::Match(
+                    Box::new(Expr::try_from(&*m.expr)?),
+                    arms,
+                    m.match_token.span.into(),
+                )
+            }
+            syn::Expr::Block(b) if b.label.is_none() => block(&b.block)?,
+            _ => return unsupported(e, "expressions of this kind"),
+        })
+    }
+}
+
+// The last expression without `;` is the value of the block
+fn block(b: &syn::Block) -> syn::Result<Expr> {
+    let mut stmts = vec![];
+    let mut tail = Expr::Tuple(vec![]);
+    for (i, s) in b.stmts.iter().enumerate() {
+        match s {
+            syn::Stmt::Local(l) => {
+                let (x, mutable) = match &l.pat {
+                    syn::Pat::Ident(p) if p.by_ref.is_none() && p.subpat.is_none() => {
+                        (&p.ident, p.mutability.is_some())
+                    }
+                    pat => return unsupported(pat, "patterns other than `x` in `let`"),
+                };
+                let e = match &l.init {
+                    Some((_, e)) => Expr::try_from(&**e)?,
+                    None => return unsupported(l, "declarations without a value"),
+                };
+                stmts.push(Stmt::Let(x.to_string(), mutable, e, x.span().into()));
+            }
+            syn::Stmt::Semi(syn::Expr::Assign(a), _) => stmts.push(Stmt::Assign(
+                Expr::try_from(&*a.left)?,
+                Expr::try_from(&*a.right)?,
+                a.eq_token.span.into(),
+            )),
+            syn::Stmt::Semi(e, _) => stmts.push(Stmt::Expr(Expr::try_from(e)?)),
+            syn::Stmt::Expr(e) if i + 1 == b.stmts.len() => tail = Expr::try_from(e)?,
+            syn::Stmt::Expr(e) => stmts.push(Stmt::Expr(Expr::try_from(e)?)),
+            syn::Stmt::Item(item) => return unsupported(item, "items in blocks"),
+        }
+    }
+    Ok(Expr::Block(
+        stmts,
+        Box::new(tail),
+        b.brace_token.span.into(),
+    ))
+}
+
+impl TryFrom<&syn::Pat> for Pattern {
+    type Error = syn::Error;
+
+    fn try_from(p: &syn::Pat) -> syn::Result<Self> {
+        Ok(match p {
+            syn::Pat::Wild(_) => Pattern::Wild,
+            syn::Pat::Ident(i)
+                if i.by_ref.is_none() && i.mutability.is_none() && i.subpat.is_none() =>
+            {
+                Pattern::Bind(i.ident.to_string())
+            }
+            syn::Pat::Lit(l) => Pattern::Lit(literal(&l.expr)?),
+            syn::Pat::Path(pp) if pp.qself.is_none() => match &path(&pp.path)?[..] {
+                [en, v] => Pattern::Variant(en.to_string(), v.to_string(), vec![]),
+                _ => return unsupported(p, "paths other than `Enum::Variant` in patterns"),
+            },
+            syn::Pat::TupleStruct(t) => match &path(&t.path)?[..] {
+                [en, v] => Pattern::Variant(
+                    en.to_string(),
+                    v.to_string(),
+                    t.pat
+                        .elems
+                        .iter()
+                        .map(Pattern::try_from)
+                        .collect::<syn::Result<_>>()?,
+                ),
+                _ => return unsupported(p, "paths other than `Enum::Variant` in patterns"),
+            },
+            _ => return unsupported(p, "patterns of this kind"),
+        })
+    }
+}
+
+// A difference between the two parsers
+#[derive(Debug)]
+pub enum Mismatch {
+    // only one of them accepts the source, the error of the other
+    Parse {
+        custom: Option<String>,
+        syn: Option<String>,
+    },
+    Tree {
+        custom: Expr,
+        syn: Expr,
+    },
+    Value {
+        custom: Result<Literal, EvalError>,
+        syn: Result<Literal, EvalError>,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Parse {
+                custom: Some(err), ..
+            } => write!(f, "rejected by the custom parser only: {}", err),
+            Mismatch::Parse { syn: Some(err), .. } => {
+                write!(f, "rejected by the syn parser only: {}", err)
+            }
+            Mismatch::Parse { .. } => write!(f, "rejected by one of the parsers"),
+            Mismatch::Tree { custom, syn } => {
+                write!(f, "the trees differ, custom `{}`, syn `{}`", custom, syn)
+            }
+            Mismatch::Value { custom, syn } => write!(
+                f,
+                "the values differ, custom `{:?}`, syn `{:?}`",
+                custom, syn
+            ),
+        }
+    }
+}
+
+// Parse the source with both parsers, and compare the trees and their values.
+// Sources rejected by both are not mismatches.
+pub fn differential(src: &str) -> Result<(), Box<Mismatch>> {
+    let ts: proc_macro2::TokenStream = match src.parse() {
+        Ok(ts) => ts,
+        Err(_) => return Ok(()),
+    };
+    let custom = syn::parse2::<Expr>(ts.clone()).map(crate::parse::climb);
+    let syn = syn::parse2::<syn::Expr>(ts).and_then(|e| Expr::try_from(&e));
+    let (custom, syn) = match (custom, syn) {
+        (Ok(c), Ok(s)) => (c, s),
+        (Err(_), Err(_)) => return Ok(()),
+        (c, s) => {
+            return Err(Box::new(Mismatch::Parse {
+                custom: c.err().map(|e| e.to_string()),
+                syn: s.err().map(|e| e.to_string()),
+            }))
+        }
+    };
+    if custom != syn {
+        return Err(Box::new(Mismatch::Tree { custom, syn }));
+    }
+    let (c, s) = (
+        custom.eval_with(Default::default()),
+        syn.eval_with(Default::default()),
+    );
+    if c != s {
+        return Err(Box::new(Mismatch::Value { custom: c, syn: s }));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn convert(s: &str) -> syn::Result<Expr> {
+    let e: syn::Expr = syn::parse_str(s)?;
+    Expr::try_from(&e)
+}
+
+#[test]
+fn syn_expr_convert() {
+    assert_eq!(
+        convert("1 - 2 * 3").unwrap(),
+        Expr::bin_op(Op::Sub, 1.into(), Expr::bin_op(Op::Mul, 2.into(), 3.into()))
+    );
+    assert_eq!(
+        convert("(1 - 2) * -3").unwrap().eval(),
+        Literal::Int((1 - 2) * -3)
+    );
+    assert_eq!(
+        convert("true && !false").unwrap_err().to_string(),
+        "`!` expressions are not supported"
+    );
+    assert_eq!(
+        convert("5 % 2").unwrap_err().to_string(),
+        "expected operator `+`, `-`, `*`, `/`, `==`, `!=`, `<`, `<=`, `>`, `>=`, `&&` or `||`"
+    );
+    assert_eq!(
+        convert("foo(1)").unwrap_err().to_string(),
+        "unknown function `foo`"
+    );
+    assert!(convert("-x").is_err());
+    assert!(convert("match x { n if n > 0 => 1, _ => 0 }").is_err());
+    assert!(convert("{ let x: i32 = 1; x }").is_err());
+    assert_eq!(
+        convert("[0; 65537]").unwrap_err().to_string(),
+        "array length `65537` exceeds the limit 65536"
+    );
+}
+
+#[test]
+fn syn_expr_differential() {
+    for s in &[
+        "1 - 2 * 3 - 4 / 5",
+        "(1 - 2) * -3 + -2147483648",
+        "1 < 2 == true",
+        "true && false || 1 < 2 && 3 >= -4",
+        "-1.5 as u8 + 3u8",
+        "len(upper(substr(\"hello\", 1, 3))) == 3",
+        "([1, 2, 3][1], (1, 'a').1, [0; 3])",
+        "match 1 + 1 { 0 => false, 2 => true, _ => false }",
+        "{ let mut x = 1; let r = &mut x; *r = *r + 1; { x } }",
+        "{ let t = ((1, 2), 3); t.0.1 + t.1 }",
+        // rejected by both
+        "5 % 2",
+        "-x",
+    ] {
+        if let Err(m) = differential(s) {
+            panic!("{}: {}", s, m);
+        }
+    }
+    // Rust takes the `<` after a cast as the start of generic arguments
+    let m = differential("1 as u8 < 2u8").unwrap_err();
+    assert_eq!(
+        m.to_string(),
+        "rejected by the syn parser only: expected `,`"
+    );
+}
+
+#[test]
+fn syn_expr_generated() {
+    for s in crate::generate::generated_sources(6, 1000) {
+        if let Err(m) = differential(&s) {
+            panic!("{}: {}", s, m);
+        }
+    }
+}
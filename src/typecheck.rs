@@ -0,0 +1,1242 @@
+use crate::ast::{
+    list, Arm, Builtin, Expr, Item, Literal, Member, Op, Pattern, Program, Stmt, MAX_REPEAT,
+};
+use std::{collections::HashMap, fmt};
+
+// The fields of the declared structs, by struct name
+pub type Structs = HashMap<String, Vec<(String, Type)>>;
+
+// The variants of the declared enums and their payload types, by enum name
+pub type Enums = HashMap<String, Vec<(String, Vec<Type>)>>;
+
+// The declarations and the variables in scope
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    pub structs: Structs,
+    pub enums: Enums,
+    // later variables shadow earlier ones
+    pub vars: Vec<(String, Type)>,
+}
+
+impl Env {
+    // The payload types of the variant
+    fn variant(&self, e: &str, v: &str, span: proc_macro2::Span) -> syn::Result<&Vec<Type>> {
+        let variants = self
+            .enums
+            .get(e)
+            .ok_or_else(|| syn::Error::new(span, format!("unknown enum `{}`", e)))?;
+        variants
+            .iter()
+            .find(|(w, _)| w == v)
+            .map(|(_, tys)| tys)
+            .ok_or_else(|| syn::Error::new(span, format!("no variant `{}` in `{}`", v, e)))
+    }
+}
+
+// Int is the default integer type i32
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    F32,
+    F64,
+    Int,
+    I8,
+    I16,
+    I64,
+    I128,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    Bool,
+    Char,
+    Str,
+    Date,
+    Tuple(Vec<Type>),
+    // the element type and the length
+    Array(Box<Type>, usize),
+    // a declared struct, by name
+    Struct(String),
+    // a declared enum, by name
+    Enum(String),
+    // `&T` or `&mut T`
+    Ref(bool, Box<Type>),
+}
+
+impl Type {
+    // The types with a name, see `from_name`
+    pub const PRIMITIVES: [Type; 16] = [
+        Type::I8,
+        Type::I16,
+        Type::Int,
+        Type::I64,
+        Type::I128,
+        Type::U8,
+        Type::U16,
+        Type::U32,
+        Type::U64,
+        Type::U128,
+        Type::F32,
+        Type::F64,
+        Type::Bool,
+        Type::Char,
+        Type::Str,
+        Type::Date,
+    ];
+
+    // The number of values in arrays and tuples (at least one per
+    // array element), saturating
+    pub fn elements(&self) -> usize {
+        match self {
+            Type::Array(t, n) => n.saturating_mul(t.elements().max(1)),
+            Type::Tuple(ts) => ts.iter().fold(0, |sum, t| sum.saturating_add(t.elements())),
+            _ => 1,
+        }
+    }
+
+    pub fn is_int(&self) -> bool {
+        matches!(
+            self,
+            Type::Int
+                | Type::I8
+                | Type::I16
+                | Type::I64
+                | Type::I128
+                | Type::U8
+                | Type::U16
+                | Type::U32
+                | Type::U64
+                | Type::U128
+        )
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Type::F32 | Type::F64)
+    }
+
+    // Integers and floats support arithmetic and ordering
+    pub fn is_numeric(&self) -> bool {
+        self.is_int() || self.is_float()
+    }
+
+    // Types supporting ordering comparisons
+    pub fn is_ord(&self) -> bool {
+        self.is_numeric() || matches!(self, Type::Char | Type::Str | Type::Date)
+    }
+
+    // The names of the structs in the type
+    fn structs(&self) -> Box<dyn Iterator<Item = &String> + '_> {
+        match self {
+            Type::Struct(name) => Box::new(std::iter::once(name)),
+            Type::Tuple(ts) => Box::new(ts.iter().flat_map(|t| t.structs())),
+            Type::Array(t, _) => t.structs(),
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+
+    // Declarations parse all names as structs, names of enums are resolved here
+    fn resolve(self, enums: &Enums) -> Type {
+        match self {
+            Type::Struct(name) if enums.contains_key(&name) => Type::Enum(name),
+            Type::Tuple(ts) => Type::Tuple(ts.into_iter().map(|t| t.resolve(enums)).collect()),
+            Type::Array(t, n) => Type::Array(Box::new(t.resolve(enums)), n),
+            t => t,
+        }
+    }
+
+    // The type from its Rust name, e.g., `u8`
+    pub fn from_name(name: &str) -> Option<Type> {
+        Some(match name {
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i32" => Type::Int,
+            "i64" => Type::I64,
+            "i128" => Type::I128,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "u128" => Type::U128,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
+            "bool" => Type::Bool,
+            "char" => Type::Char,
+            "String" => Type::Str,
+            "Date" => Type::Date,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Type::Tuple(ts) if ts.len() == 1 => return write!(f, "({},)", ts[0]),
+            Type::Tuple(ts) => return write!(f, "({})", list(ts)),
+            Type::Array(t, n) => return write!(f, "[{}; {}]", t, n),
+            Type::Ref(false, t) => return write!(f, "&{}", t),
+            Type::Ref(true, t) => return write!(f, "&mut {}", t),
+            Type::Struct(name) | Type::Enum(name) => name,
+            Type::Int => "i32",
+            Type::I8 => "i8",
+            Type::I16 => "i16",
+            Type::I64 => "i64",
+            Type::I128 => "i128",
+            Type::U8 => "u8",
+            Type::U16 => "u16",
+            Type::U32 => "u32",
+            Type::U64 => "u64",
+            Type::U128 => "u128",
+            Type::F32 => "f32",
+            Type::F64 => "f64",
+            Type::Bool => "bool",
+            Type::Char => "char",
+            Type::Str => "String",
+            Type::Date => "Date",
+        })
+    }
+}
+
+impl Literal {
+    pub fn get_type(&self) -> Type {
+        match self {
+            Literal::Bool(_) => Type::Bool,
+            Literal::Int(_) => Type::Int,
+            #[cfg(feature = "bigint")]
+            Literal::Big(_) => Type::Int,
+            Literal::I8(_) => Type::I8,
+            Literal::I16(_) => Type::I16,
+            Literal::I64(_) => Type::I64,
+            Literal::I128(_) => Type::I128,
+            Literal::U8(_) => Type::U8,
+            Literal::U16(_) => Type::U16,
+            Literal::U32(_) => Type::U32,
+            Literal::U64(_) => Type::U64,
+            Literal::U128(_) => Type::U128,
+            Literal::F32(_) => Type::F32,
+            Literal::F64(_) => Type::F64,
+            Literal::Char(_) => Type::Char,
+            Literal::Str(_) => Type::Str,
+            Literal::Date(_) => Type::Date,
+            Literal::Struct(name, _) => Type::Struct(name.clone()),
+            Literal::Enum(name, ..) => Type::Enum(name.clone()),
+            // the type referred to is not known from the value
+            Literal::Ref(_) => Type::Ref(false, Box::new(Type::Tuple(vec![]))),
+            Literal::Tuple(t) => Type::Tuple(t.iter().map(|l| l.get_type()).collect()),
+            // the element type of an empty array value is unknown, we use unit
+            Literal::Array(a) => Type::Array(
+                Box::new(a.first().map_or(Type::Tuple(vec![]), |l| l.get_type())),
+                a.len(),
+            ),
+        }
+    }
+}
+
+impl Op {
+    // The result type of applying the operator to the operand types,
+    // None if the operator is not defined for the operands.
+    // As in Rust, integers of different types are never mixed implicitly.
+    pub fn get_type(&self, left: Type, right: Type) -> Option<Type> {
+        match (self, left, right) {
+            // days added to a date
+            (Op::Add | Op::Sub, Type::Date, Type::Int) => Some(Type::Date),
+            (_, l, r) if l != r => None,
+            // references would be compared by address
+            (_, Type::Ref(..), _) => None,
+            (Op::Add | Op::Sub | Op::Mul | Op::Div, l, _) if l.is_numeric() => Some(l),
+            // concatenation
+            (Op::Add, Type::Str, _) => Some(Type::Str),
+            // the days between two dates
+            (Op::Sub, Type::Date, _) => Some(Type::Int),
+            (Op::Eq | Op::Ne, _, _) => Some(Type::Bool),
+            (Op::Lt | Op::Le | Op::Gt | Op::Ge, l, _) if l.is_ord() => Some(Type::Bool),
+            (Op::And | Op::Or, Type::Bool, _) => Some(Type::Bool),
+            _ => None,
+        }
+    }
+}
+
+impl Builtin {
+    // The parameter types and the result type
+    pub fn signature(&self) -> (&'static [Type], Type) {
+        match self {
+            Builtin::Len => (&[Type::Str], Type::Int),
+            Builtin::Upper => (&[Type::Str], Type::Str),
+            Builtin::Substr => (&[Type::Str, Type::Int, Type::Int], Type::Str),
+        }
+    }
+}
+
+impl Expr {
+    // Type check the expression, errors point to the offending operator
+    pub fn type_check(&self) -> syn::Result<Type> {
+        self.type_check_with(&Env::default())
+    }
+
+    // Type check the expression given the declarations and variables in scope
+    pub fn type_check_with(&self, env: &Env) -> syn::Result<Type> {
+        match self {
+            Expr::Lit(l) => Ok(l.get_type()),
+            Expr::BinOp(op, left, right, span) => {
+                let l = left.type_check_with(env)?;
+                let r = right.type_check_with(env)?;
+                op.get_type(l.clone(), r.clone()).ok_or_else(|| {
+                    let hint = if l.is_numeric() && r.is_numeric() {
+                        ", use `as` to convert between numeric types"
+                    } else {
+                        ""
+                    };
+                    syn::Error::new(
+                        span.0,
+                        format!("cannot apply `{}` to `{}` and `{}`{}", op, l, r, hint),
+                    )
+                })
+            }
+            Expr::Par(e) => e.type_check_with(env),
+            // numeric types can be cast to each other,
+            // Bool and char can be cast to integers, u8 to char
+            Expr::Cast(e, ty, span) => match (e.type_check_with(env)?, ty) {
+                (from, ty) if from.is_numeric() && ty.is_numeric() => Ok(ty.clone()),
+                (Type::Bool | Type::Char, ty) if ty.is_int() => Ok(ty.clone()),
+                (Type::Bool, Type::Bool) => Ok(Type::Bool),
+                (Type::U8 | Type::Char, Type::Char) => Ok(Type::Char),
+                (from, ty) => Err(syn::Error::new(
+                    span.0,
+                    format!("cannot cast `{}` as `{}`", from, ty),
+                )),
+            },
+            // errors point to the function name
+            Expr::Call(f, args, span) => {
+                let args = args
+                    .iter()
+                    .map(|a| a.type_check_with(env))
+                    .collect::<syn::Result<Vec<_>>>()?;
+                let (params, result) = f.signature();
+                if args != params {
+                    return Err(syn::Error::new(
+                        span.0,
+                        format!(
+                            "`{}` expects arguments `({})`, found `({})`",
+                            f,
+                            list(params),
+                            list(&args)
+                        ),
+                    ));
+                }
+                Ok(result)
+            }
+            Expr::Tuple(es) => Ok(Type::Tuple(
+                es.iter()
+                    .map(|e| e.type_check_with(env))
+                    .collect::<syn::Result<_>>()?,
+            )),
+            // all elements must have the same type
+            Expr::Array(es, span) => {
+                let mut types = es.iter().map(|e| e.type_check_with(env));
+                let t = match types.next() {
+                    Some(t) => t?,
+                    None => {
+                        return Err(syn::Error::new(
+                            span.0,
+                            "cannot infer the element type of an empty array",
+                        ))
+                    }
+                };
+                for u in types {
+                    let u = u?;
+                    if u != t {
+                        return Err(syn::Error::new(
+                            span.0,
+                            format!("mismatched array element types `{}` and `{}`", t, u),
+                        ));
+                    }
+                }
+                Ok(Type::Array(Box::new(t), es.len()))
+            }
+            Expr::Repeat(e, n) => {
+                let t = Type::Array(Box::new(e.type_check_with(env)?), *n);
+                // nested arrays multiply
+                match t.elements() {
+                    len if len > MAX_REPEAT => Err(syn::Error::new(
+                        proc_macro2::Span::call_site(),
+                        format!("array `{}` exceeds the limit of {} elements", t, MAX_REPEAT),
+                    )),
+                    _ => Ok(t),
+                }
+            }
+            Expr::Index(a, i, span) => match (a.type_check_with(env)?, i.type_check_with(env)?) {
+                (Type::Array(t, _), i) if i.is_int() => Ok(*t),
+                (a, i) => Err(syn::Error::new(
+                    span.0,
+                    format!("cannot index `{}` with `{}`", a, i),
+                )),
+            },
+            Expr::Field(t, m, span) => match (t.type_check_with(env)?, m) {
+                (Type::Tuple(ts), Member::Index(n)) if *n < ts.len() => Ok(ts[*n].clone()),
+                (Type::Struct(s), Member::Named(name)) => env
+                    .structs
+                    .get(&s)
+                    .and_then(|fields| fields.iter().find(|(f, _)| f == name))
+                    .map(|(_, ty)| ty.clone())
+                    .ok_or_else(|| {
+                        syn::Error::new(span.0, format!("no field `{}` on type `{}`", m, s))
+                    }),
+                (t, _) => Err(syn::Error::new(
+                    span.0,
+                    format!("no field `{}` on type `{}`", m, t),
+                )),
+            },
+            // each declared field must be given exactly once
+            Expr::Struct(name, fields, span) => {
+                let decl = env
+                    .structs
+                    .get(name)
+                    .ok_or_else(|| syn::Error::new(span.0, format!("unknown struct `{}`", name)))?;
+                for (i, (f, e)) in fields.iter().enumerate() {
+                    let ty = e.type_check_with(env)?;
+                    let err = |msg: String| Err(syn::Error::new(span.0, msg));
+                    match decl.iter().find(|(d, _)| d == f) {
+                        None => return err(format!("no field `{}` in `{}`", f, name)),
+                        Some(_) if fields[..i].iter().any(|(g, _)| g == f) => {
+                            return err(format!("field `{}` specified more than once", f))
+                        }
+                        Some((_, expected)) if *expected != ty => {
+                            return err(format!(
+                                "mismatched types for field `{}`, expected `{}`, found `{}`",
+                                f, expected, ty
+                            ))
+                        }
+                        _ => {}
+                    }
+                }
+                match decl
+                    .iter()
+                    .find(|(d, _)| fields.iter().all(|(f, _)| f != d))
+                {
+                    Some((d, _)) => Err(syn::Error::new(
+                        span.0,
+                        format!("missing field `{}` in `{}`", d, name),
+                    )),
+                    None => Ok(Type::Struct(name.clone())),
+                }
+            }
+            // errors point to the variant
+            Expr::Variant(e, v, args, span) => {
+                let params = env.variant(e, v, span.0)?;
+                let args = args
+                    .iter()
+                    .map(|a| a.type_check_with(env))
+                    .collect::<syn::Result<Vec<_>>>()?;
+                if args != *params {
+                    return Err(syn::Error::new(
+                        span.0,
+                        format!(
+                            "`{}::{}` expects arguments `({})`, found `({})`",
+                            e,
+                            v,
+                            list(params),
+                            list(&args)
+                        ),
+                    ));
+                }
+                Ok(Type::Enum(e.clone()))
+            }
+            Expr::Var(x, span) => env
+                .vars
+                .iter()
+                .rev()
+                .find(|(y, _)| x == y)
+                .map(|(_, ty)| ty.clone())
+                .ok_or_else(|| {
+                    syn::Error::new(span.0, format!("cannot find value `{}` in this scope", x))
+                }),
+            // all arms must have the same type, each arm must be reachable
+            // and together the arms must cover all values
+            Expr::Match(e, arms, span) => {
+                let ty = e.type_check_with(env)?;
+                let mut result: Option<Type> = None;
+                for Arm { pat, body, span } in arms {
+                    let mut env = env.clone();
+                    let vars = pat.type_check(&ty, &env, span.0)?;
+                    env.vars.extend(vars);
+                    let t = body.type_check_with(&env)?;
+                    match &result {
+                        Some(r) if *r != t => {
+                            return Err(syn::Error::new(
+                                span.0,
+                                format!(
+                                "`match` arms have incompatible types, expected `{}`, found `{}`",
+                                r, t
+                            ),
+                            ))
+                        }
+                        _ => result = Some(t),
+                    }
+                }
+                let mut rows = vec![];
+                for arm in arms {
+                    if useful(
+                        &rows,
+                        std::slice::from_ref(&arm.pat),
+                        std::slice::from_ref(&ty),
+                        env,
+                    )
+                    .is_none()
+                    {
+                        return Err(syn::Error::new(arm.span.0, "unreachable pattern"));
+                    }
+                    rows.push(vec![arm.pat.clone()]);
+                }
+                if let Some(w) = useful(&rows, &[Pattern::Wild], std::slice::from_ref(&ty), env) {
+                    return Err(syn::Error::new(
+                        span.0,
+                        format!("non-exhaustive patterns: `{}` not covered", w[0]),
+                    ));
+                }
+                // an empty match never produces a value, we use unit
+                Ok(result.unwrap_or_else(|| Type::Tuple(vec![])))
+            }
+            // the variables declared in the block are in scope for
+            // the rest of the block
+            Expr::Block(stmts, e, _) => {
+                let mut env = env.clone();
+                for stmt in stmts {
+                    match stmt {
+                        Stmt::Let(x, _, e, _) => {
+                            let ty = e.type_check_with(&env)?;
+                            env.vars.push((x.clone(), ty));
+                        }
+                        Stmt::Assign(place, e, span) => {
+                            let expected = place.place_type(&env, Access::Assign, span.0)?;
+                            let ty = e.type_check_with(&env)?;
+                            if ty != expected {
+                                return Err(syn::Error::new(
+                                    span.0,
+                                    format!(
+                                        "mismatched types, expected `{}`, found `{}`",
+                                        expected, ty
+                                    ),
+                                ));
+                            }
+                        }
+                        Stmt::Expr(e) => {
+                            e.type_check_with(&env)?;
+                        }
+                    }
+                }
+                e.type_check_with(&env)
+            }
+            Expr::Ref(mutable, e, span) => {
+                let access = if *mutable {
+                    Access::Mut
+                } else {
+                    Access::Shared
+                };
+                let ty = e.place_type(env, access, span.0)?;
+                Ok(Type::Ref(*mutable, Box::new(ty)))
+            }
+            Expr::Deref(e, span) => match e.type_check_with(env)? {
+                Type::Ref(_, ty) => Ok(*ty),
+                ty => Err(syn::Error::new(
+                    span.0,
+                    format!("type `{}` cannot be dereferenced", ty),
+                )),
+            },
+        }
+    }
+
+    // The type of a place expression (`x` or `*r`), mutable access
+    // through a `&` reference is not allowed
+    fn place_type(&self, env: &Env, access: Access, span: proc_macro2::Span) -> syn::Result<Type> {
+        match self {
+            Expr::Var(..) => self.type_check_with(env),
+            Expr::Par(e) => e.place_type(env, access, span),
+            Expr::Deref(e, deref) => match (e.type_check_with(env)?, access) {
+                (Type::Ref(false, _), Access::Mut) => Err(syn::Error::new(
+                    span,
+                    format!(
+                        "cannot borrow `{}` as mutable, as it is behind a `&` reference",
+                        self.place_name()
+                    ),
+                )),
+                (Type::Ref(false, _), Access::Assign) => Err(syn::Error::new(
+                    span,
+                    format!(
+                        "cannot assign to `{}`, which is behind a `&` reference",
+                        self.place_name()
+                    ),
+                )),
+                (Type::Ref(_, ty), _) => Ok(*ty),
+                (ty, _) => Err(syn::Error::new(
+                    deref.0,
+                    format!("type `{}` cannot be dereferenced", ty),
+                )),
+            },
+            _ => Err(syn::Error::new(
+                span,
+                match access {
+                    Access::Assign => "invalid left-hand side of assignment",
+                    _ => "only variables and dereferences can be borrowed",
+                },
+            )),
+        }
+    }
+
+    // A place expression as written, e.g., `*r`
+    pub(crate) fn place_name(&self) -> String {
+        match self {
+            Expr::Var(x, _) => x.clone(),
+            Expr::Par(e) => format!("({})", e.place_name()),
+            Expr::Deref(e, _) => format!("*{}", e.place_name()),
+            _ => "_".to_string(),
+        }
+    }
+}
+
+// How a place expression is used
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    Shared,
+    Mut,
+    Assign,
+}
+
+impl Pattern {
+    // Check the pattern against the type of the scrutinee,
+    // returns the bound variables and their types
+    pub fn type_check(
+        &self,
+        ty: &Type,
+        env: &Env,
+        span: proc_macro2::Span,
+    ) -> syn::Result<Vec<(String, Type)>> {
+        let mut bound = vec![];
+        self.bind(ty, env, span, &mut bound)?;
+        Ok(bound)
+    }
+
+    fn bind(
+        &self,
+        ty: &Type,
+        env: &Env,
+        span: proc_macro2::Span,
+        bound: &mut Vec<(String, Type)>,
+    ) -> syn::Result<()> {
+        let err = |msg: String| Err(syn::Error::new(span, msg));
+        match self {
+            Pattern::Wild => Ok(()),
+            Pattern::Bind(x) if bound.iter().any(|(y, _)| x == y) => err(format!(
+                "identifier `{}` is bound more than once in the same pattern",
+                x
+            )),
+            Pattern::Bind(x) => {
+                bound.push((x.clone(), ty.clone()));
+                Ok(())
+            }
+            Pattern::Lit(l) if l.get_type() != *ty => err(format!(
+                "mismatched types, expected `{}`, found `{}`",
+                ty,
+                l.get_type()
+            )),
+            Pattern::Lit(_) => Ok(()),
+            Pattern::Variant(e, _, _) if *ty != Type::Enum(e.clone()) => err(format!(
+                "mismatched types, expected `{}`, found `{}`",
+                ty, e
+            )),
+            Pattern::Variant(e, v, ps) => {
+                let tys = env.variant(e, v, span)?;
+                if ps.len() != tys.len() {
+                    return err(format!(
+                        "this pattern has {}, but the corresponding tuple variant has {}",
+                        fields(ps.len()),
+                        fields(tys.len())
+                    ));
+                }
+                for (p, ty) in ps.iter().zip(tys) {
+                    p.bind(ty, env, span, bound)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // The constructor at the head of the pattern, None for `_` and bindings
+    fn ctor(&self) -> Option<Ctor> {
+        match self {
+            Pattern::Wild | Pattern::Bind(_) => None,
+            Pattern::Lit(l) => Some(Ctor::Lit(l.clone())),
+            Pattern::Variant(e, v, _) => Some(Ctor::Variant(e.clone(), v.clone())),
+        }
+    }
+}
+
+// E.g., `1 field` or `2 fields`
+fn fields(n: usize) -> String {
+    format!("{} field{}", n, if n == 1 { "" } else { "s" })
+}
+
+// A value constructor, as matched by a pattern
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Lit(Literal),
+    Variant(String, String),
+}
+
+impl Ctor {
+    // The types of the constructor arguments
+    fn args(&self, env: &Env) -> Vec<Type> {
+        match self {
+            Ctor::Lit(_) => vec![],
+            Ctor::Variant(e, v) => env.enums[e]
+                .iter()
+                .find(|(w, _)| w == v)
+                .map(|(_, tys)| tys.clone())
+                .unwrap(),
+        }
+    }
+
+    // The constructor applied to the argument patterns
+    fn apply(&self, args: Vec<Pattern>) -> Pattern {
+        match self {
+            Ctor::Lit(l) => Pattern::Lit(l.clone()),
+            Ctor::Variant(e, v) => Pattern::Variant(e.clone(), v.clone(), args),
+        }
+    }
+
+    // All constructors of the type, None if infinitely many
+    fn all(ty: &Type, env: &Env) -> Option<Vec<Ctor>> {
+        match ty {
+            Type::Bool => Some(vec![
+                Ctor::Lit(Literal::Bool(false)),
+                Ctor::Lit(Literal::Bool(true)),
+            ]),
+            Type::Enum(e) => Some(
+                env.enums[e]
+                    .iter()
+                    .map(|(v, _)| Ctor::Variant(e.clone(), v.clone()))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+// The rows matching the constructor, with the head replaced by its arguments
+fn specialize(rows: &[Vec<Pattern>], c: &Ctor, arity: usize) -> Vec<Vec<Pattern>> {
+    rows.iter()
+        .filter_map(|row| {
+            let args = match &row[0] {
+                Pattern::Wild | Pattern::Bind(_) => vec![Pattern::Wild; arity],
+                Pattern::Variant(_, _, ps) if row[0].ctor().as_ref() == Some(c) => ps.clone(),
+                Pattern::Lit(_) if row[0].ctor().as_ref() == Some(c) => vec![],
+                _ => return None,
+            };
+            Some(args.into_iter().chain(row[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+// Whether the pattern row `q` (of types `tys`) matches a value not matched
+// by any of the rows, if so returns such a value as a row of patterns.
+// This is the usefulness algorithm of Maranget, "Warnings for pattern matching".
+fn useful(rows: &[Vec<Pattern>], q: &[Pattern], tys: &[Type], env: &Env) -> Option<Vec<Pattern>> {
+    if q.is_empty() {
+        return if rows.is_empty() { Some(vec![]) } else { None };
+    }
+    // specialize by the constructor c and rebuild the witness
+    let by = |c: &Ctor, q: Vec<Pattern>| {
+        let args = c.args(env);
+        let n = args.len();
+        let tys: Vec<_> = args.into_iter().chain(tys[1..].iter().cloned()).collect();
+        let mut w = useful(&specialize(rows, c, n), &q, &tys, env)?;
+        let rest = w.split_off(n);
+        Some(std::iter::once(c.apply(w)).chain(rest).collect())
+    };
+    match q[0].ctor() {
+        Some(c) => by(
+            &c,
+            specialize(&[q.to_vec()], &c, c.args(env).len()).remove(0),
+        ),
+        None => {
+            let used: Vec<Ctor> = rows.iter().filter_map(|row| row[0].ctor()).collect();
+            match Ctor::all(&tys[0], env) {
+                // all constructors are matched by some row, try each of them
+                Some(all) if all.iter().all(|c| used.contains(c)) => all.iter().find_map(|c| {
+                    let n = c.args(env).len();
+                    by(c, specialize(&[q.to_vec()], c, n).remove(0))
+                }),
+                // the rows starting with `_` must match the rest
+                all => {
+                    let default: Vec<_> = rows
+                        .iter()
+                        .filter(|row| row[0].ctor().is_none())
+                        .map(|row| row[1..].to_vec())
+                        .collect();
+                    let rest = useful(&default, &q[1..], &tys[1..], env)?;
+                    // a missing constructor, if any is used
+                    let head = match all.and_then(|all| all.into_iter().find(|c| !used.contains(c)))
+                    {
+                        Some(c) if !used.is_empty() => {
+                            let n = c.args(env).len();
+                            c.apply(vec![Pattern::Wild; n])
+                        }
+                        _ => Pattern::Wild,
+                    };
+                    Some(std::iter::once(head).chain(rest).collect())
+                }
+            }
+        }
+    }
+}
+
+impl Program {
+    // Check the declarations and type check the expression,
+    // declaration errors point to the struct name
+    pub fn type_check(&self) -> syn::Result<Type> {
+        self.expr.type_check_with(&self.env()?)
+    }
+
+    // Check the declarations, the environment of the expression
+    pub fn env(&self) -> syn::Result<Env> {
+        let mut env = Env::default();
+        for item in &self.items {
+            let (kind, name, span) = match item {
+                Item::Struct(decl) => ("struct", &decl.name, decl.span),
+                Item::Enum(decl) => ("enum", &decl.name, decl.span),
+            };
+            let err = |msg: String| Err(syn::Error::new(span.0, msg));
+            if env.structs.contains_key(name) || env.enums.contains_key(name) {
+                return err(format!("{} `{}` is defined multiple times", kind, name));
+            }
+            match item {
+                Item::Struct(decl) => {
+                    for (i, (f, _)) in decl.fields.iter().enumerate() {
+                        if decl.fields[..i].iter().any(|(g, _)| g == f) {
+                            return err(format!("field `{}` is already declared", f));
+                        }
+                    }
+                    env.structs.insert(name.clone(), decl.fields.clone());
+                }
+                Item::Enum(decl) => {
+                    for (i, (v, _)) in decl.variants.iter().enumerate() {
+                        if decl.variants[..i].iter().any(|(w, _)| w == v) {
+                            return err(format!("variant `{}` is already declared", v));
+                        }
+                    }
+                    env.enums.insert(name.clone(), decl.variants.clone());
+                }
+            }
+        }
+        // types may refer to structs and enums declared later
+        let enums = env.enums.clone();
+        for fields in env.structs.values_mut() {
+            for (_, ty) in fields.iter_mut() {
+                *ty = ty.clone().resolve(&enums);
+            }
+        }
+        for variants in env.enums.values_mut() {
+            for (_, tys) in variants.iter_mut() {
+                for ty in tys.iter_mut() {
+                    *ty = ty.clone().resolve(&enums);
+                }
+            }
+        }
+        for item in &self.items {
+            let (name, span) = match item {
+                Item::Struct(decl) => (&decl.name, decl.span),
+                Item::Enum(decl) => (&decl.name, decl.span),
+            };
+            let members: Vec<(&String, &Type)> = match item {
+                Item::Struct(_) => env.structs[name].iter().map(|(f, ty)| (f, ty)).collect(),
+                Item::Enum(_) => env.enums[name]
+                    .iter()
+                    .flat_map(|(v, tys)| tys.iter().map(move |ty| (v, ty)))
+                    .collect(),
+            };
+            for (m, ty) in members {
+                if let Some(s) = ty.structs().find(|s| !env.structs.contains_key(*s)) {
+                    let kind = if let Item::Struct(_) = item {
+                        "field"
+                    } else {
+                        "variant"
+                    };
+                    return Err(syn::Error::new(
+                        span.0,
+                        format!("unknown type `{}` of {} `{}`", s, kind, m),
+                    ));
+                }
+            }
+        }
+        Ok(env)
+    }
+}
+
+#[cfg(test)]
+fn parse_climb(s: &str) -> Expr {
+    let ts: proc_macro2::TokenStream = s.parse().unwrap();
+    crate::parse::climb(syn::parse2(ts).unwrap())
+}
+
+#[test]
+fn type_check_int() {
+    assert_eq!(
+        parse_climb("2 - 3 * (4 / 5)").type_check().unwrap(),
+        Type::Int
+    );
+}
+
+#[test]
+fn type_check_bool() {
+    assert_eq!(
+        parse_climb("true && (false || true)").type_check().unwrap(),
+        Type::Bool
+    );
+}
+
+#[test]
+fn type_check_cmp() {
+    assert_eq!(
+        parse_climb("1 < 2 == (true || false)")
+            .type_check()
+            .unwrap(),
+        Type::Bool
+    );
+}
+
+#[test]
+fn type_check_cmp_fail() {
+    assert!(parse_climb("true < false").type_check().is_err());
+    assert!(parse_climb("1 == true").type_check().is_err());
+}
+
+#[test]
+fn type_check_fail() {
+    let err = parse_climb("1 + 2 && true").type_check().unwrap_err();
+    println!("err {}", err);
+    assert!(err.to_string().contains("`&&`"));
+}
+
+#[test]
+fn type_check_widths() {
+    assert_eq!(
+        parse_climb("1u8 + 2u8 * 3u8").type_check().unwrap(),
+        Type::U8
+    );
+    assert_eq!(parse_climb("1i64 < 2i64").type_check().unwrap(), Type::Bool);
+    let err = parse_climb("1u8 + 2").type_check().unwrap_err();
+    println!("err {}", err);
+    assert!(err.to_string().contains("`as`"));
+    assert!(parse_climb("1u64 == 2u32").type_check().is_err());
+}
+
+#[test]
+fn type_check_cast() {
+    assert_eq!(
+        parse_climb("1u8 as i32 + 2").type_check().unwrap(),
+        Type::Int
+    );
+    assert_eq!(parse_climb("true as u8").type_check().unwrap(), Type::U8);
+    assert!(parse_climb("1 as bool").type_check().is_err());
+}
+
+#[test]
+fn type_check_float() {
+    assert_eq!(
+        parse_climb("1.5 * 2.0 - 0.5").type_check().unwrap(),
+        Type::F64
+    );
+    assert_eq!(
+        parse_climb("1.5f32 < 2f32").type_check().unwrap(),
+        Type::Bool
+    );
+    assert_eq!(
+        parse_climb("1 as f32 / 3f32").type_check().unwrap(),
+        Type::F32
+    );
+    assert!(parse_climb("1.5 + 1").type_check().is_err());
+    assert!(parse_climb("1.5f32 + 1.5").type_check().is_err());
+    assert!(parse_climb("true as f64").type_check().is_err());
+    assert!(parse_climb("1.5 as bool").type_check().is_err());
+}
+
+#[test]
+fn type_check_str() {
+    assert_eq!(
+        parse_climb(r#""a" + "b" + upper("c")"#)
+            .type_check()
+            .unwrap(),
+        Type::Str
+    );
+    assert_eq!(
+        parse_climb(r#"len(substr("abc", 1, 2)) * 2"#)
+            .type_check()
+            .unwrap(),
+        Type::Int
+    );
+    assert_eq!(
+        parse_climb(r#""a" < "b" && 'a' <= 'b'"#)
+            .type_check()
+            .unwrap(),
+        Type::Bool
+    );
+    assert_eq!(parse_climb("'a' as u8").type_check().unwrap(), Type::U8);
+    assert_eq!(
+        parse_climb("97u8 as char").type_check().unwrap(),
+        Type::Char
+    );
+    let err = parse_climb(r#""a" * 2"#).type_check().unwrap_err();
+    println!("err {}", err);
+    assert!(err.to_string().contains("`*`"));
+    assert!(parse_climb(r#""a" * "b""#).type_check().is_err());
+    assert!(parse_climb("'a' + 'b'").type_check().is_err());
+    assert!(parse_climb("97 as char").type_check().is_err());
+    let err = parse_climb(r#"substr("abc", 1)"#).type_check().unwrap_err();
+    println!("err {}", err);
+    assert_eq!(
+        err.to_string(),
+        "`substr` expects arguments `(String, i32, i32)`, found `(String, i32)`"
+    );
+    assert!(parse_climb("len(1)").type_check().is_err());
+}
+
+#[test]
+fn type_check_date() {
+    let ty = |s: &str| parse_climb(s).type_check();
+    assert_eq!(ty(r#"d"2024-02-29" + 30 - 1"#).unwrap(), Type::Date);
+    assert_eq!(ty(r#"d"2024-03-01" - d"2024-02-01""#).unwrap(), Type::Int);
+    assert_eq!(
+        ty(r#"d"2024-02-29" < d"2024-03-01" + 1"#).unwrap(),
+        Type::Bool
+    );
+    let err = ty(r#"d"2024-02-29" + d"2024-03-01""#).unwrap_err();
+    assert_eq!(err.to_string(), "cannot apply `+` to `Date` and `Date`");
+    assert!(ty(r#"30 + d"2024-02-29""#).is_err());
+    assert!(ty(r#"d"2024-02-29" + 30u8"#).is_err());
+    assert!(ty(r#"d"2024-02-29" as i32"#).is_err());
+}
+
+#[test]
+fn type_check_tuple_array() {
+    assert_eq!(
+        parse_climb("(1, [true; 3])").type_check().unwrap(),
+        Type::Tuple(vec![Type::Int, Type::Array(Box::new(Type::Bool), 3)])
+    );
+    assert_eq!(
+        parse_climb("[(1u8, 'a'), (2u8, 'b')][1u8].1")
+            .type_check()
+            .unwrap(),
+        Type::Char
+    );
+    assert_eq!(
+        parse_climb("(1, 2) == (1, 2)").type_check().unwrap(),
+        Type::Bool
+    );
+    let err = parse_climb("[1, true]").type_check().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "mismatched array element types `i32` and `bool`"
+    );
+    assert!(parse_climb("[]").type_check().is_err());
+    assert!(parse_climb("[1, 2][true]").type_check().is_err());
+    assert!(parse_climb("(1, 2)[0]").type_check().is_err());
+    let err = parse_climb("(1, 2).2").type_check().unwrap_err();
+    assert_eq!(err.to_string(), "no field `2` on type `(i32, i32)`");
+    assert!(parse_climb("(1, 2) < (1, 2)").type_check().is_err());
+    let err = parse_climb("[[(0, 0); 512]; 512]")
+        .type_check()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "array `[[(i32, i32); 512]; 512]` exceeds the limit of 65536 elements"
+    );
+    assert!(parse_climb("[[0; 256]; 256]").type_check().is_ok());
+}
+
+#[cfg(test)]
+fn check_program(s: &str) -> syn::Result<Type> {
+    let ts: proc_macro2::TokenStream = s.parse().unwrap();
+    syn::parse2::<Program>(ts).unwrap().type_check()
+}
+
+#[test]
+fn type_check_struct() {
+    let decls = "struct P { x: i32, y: (bool, u8) } struct L { a: P, b: [P; 2] }";
+    let check = |e: &str| check_program(&format!("{} {}", decls, e));
+    assert_eq!(
+        check("P { y: (true, 2u8), x: 1 }").unwrap(),
+        Type::Struct("P".to_string())
+    );
+    assert_eq!(
+        check("L { a: P { x: 1, y: (true, 2u8) }, b: [P { x: 2, y: (false, 0u8) }; 2] }.b[1].y.1")
+            .unwrap(),
+        Type::U8
+    );
+    let err = |e: &str| check(e).unwrap_err().to_string();
+    assert_eq!(err("Q { x: 1 }"), "unknown struct `Q`");
+    assert_eq!(err("P { x: 1 }"), "missing field `y` in `P`");
+    assert_eq!(
+        err("P { x: 1, y: (true, 2u8), z: 3 }"),
+        "no field `z` in `P`"
+    );
+    assert_eq!(
+        err("P { x: 1, x: 2, y: (true, 2u8) }"),
+        "field `x` specified more than once"
+    );
+    assert_eq!(
+        err("P { x: true, y: (true, 2u8) }"),
+        "mismatched types for field `x`, expected `i32`, found `bool`"
+    );
+    assert_eq!(
+        err("P { x: 1, y: (true, 2u8) }.z"),
+        "no field `z` on type `P`"
+    );
+    assert_eq!(
+        check_program("struct P { x: i32 } struct P { y: i32 } 1")
+            .unwrap_err()
+            .to_string(),
+        "struct `P` is defined multiple times"
+    );
+    assert_eq!(
+        check_program("struct P { x: i32, x: bool } 1")
+            .unwrap_err()
+            .to_string(),
+        "field `x` is already declared"
+    );
+    assert_eq!(
+        check_program("struct P { x: [(Q, i32); 2] } 1")
+            .unwrap_err()
+            .to_string(),
+        "unknown type `Q` of field `x`"
+    );
+}
+
+#[test]
+fn type_check_match() {
+    let decls = "enum O { S(i32), N } enum E { A(O, bool), B }";
+    let check = |e: &str| check_program(&format!("{} {}", decls, e));
+    assert_eq!(
+        check("match E::A(O::S(1), true) { E::A(O::S(x), b) => b && x < 2, E::A(O::N, _) => true, E::B => false }")
+            .unwrap(),
+        Type::Bool
+    );
+    assert_eq!(
+        check("match true { true => O::N, false => O::S(1) }").unwrap(),
+        Type::Enum("O".to_string())
+    );
+    let err = |e: &str| check(e).unwrap_err().to_string();
+    assert_eq!(
+        err("match E::B { E::A(O::S(_), _) => 1, E::B => 2 }"),
+        "non-exhaustive patterns: `E::A(O::N, _)` not covered"
+    );
+    assert_eq!(
+        err("match E::B { E::A(_, true) => 1, E::B => 2 }"),
+        "non-exhaustive patterns: `E::A(_, false)` not covered"
+    );
+    assert_eq!(
+        err("match 1 { 0 => 1, 1 => 2 }"),
+        "non-exhaustive patterns: `_` not covered"
+    );
+    assert_eq!(
+        err("match O::N { O::S(x) => x, _ => 0, O::N => 1 }"),
+        "unreachable pattern"
+    );
+    assert_eq!(
+        err("match 1 { 1 => 1, 1 => 2, _ => 3 }"),
+        "unreachable pattern"
+    );
+    assert_eq!(
+        err("match O::N { O::S(x) => x, O::N => true }"),
+        "`match` arms have incompatible types, expected `i32`, found `bool`"
+    );
+    assert_eq!(
+        err("match O::N { O::S(x) => y, O::N => 0 }"),
+        "cannot find value `y` in this scope"
+    );
+    assert_eq!(
+        err("match E::B { E::A(x, x) => 1, _ => 0 }"),
+        "identifier `x` is bound more than once in the same pattern"
+    );
+    assert_eq!(
+        err("match E::B { O::N => 1, _ => 0 }"),
+        "mismatched types, expected `E`, found `O`"
+    );
+    assert_eq!(
+        err("match O::N { O::S(1, 2) => 1, _ => 0 }"),
+        "this pattern has 2 fields, but the corresponding tuple variant has 1 field"
+    );
+    assert_eq!(err("O::T"), "no variant `T` in `O`");
+    assert_eq!(
+        err("E::A(O::N)"),
+        "`E::A` expects arguments `(O, bool)`, found `(O)`"
+    );
+    // the scope of a binding is its arm
+    assert_eq!(
+        err("match O::N { O::S(x) => 0, O::N => x }"),
+        "cannot find value `x` in this scope"
+    );
+    assert_eq!(
+        check_program("enum E { A, A } 1").unwrap_err().to_string(),
+        "variant `A` is already declared"
+    );
+    assert_eq!(
+        check_program("struct P { x: i32 } enum P { A } 1")
+            .unwrap_err()
+            .to_string(),
+        "enum `P` is defined multiple times"
+    );
+    assert_eq!(
+        check_program("enum E { A(Q) } 1").unwrap_err().to_string(),
+        "unknown type `Q` of variant `A`"
+    );
+}
+
+#[test]
+fn type_check_ref() {
+    assert_eq!(
+        parse_climb("{ let mut x = 1; let r = &mut x; *r = *r + 1; &x }")
+            .type_check()
+            .unwrap(),
+        Type::Ref(false, Box::new(Type::Int))
+    );
+    assert_eq!(
+        parse_climb("{ let x = (1, true); let r = &x; let rr = &r; (**rr).1 }")
+            .type_check()
+            .unwrap(),
+        Type::Bool
+    );
+    assert_eq!(
+        parse_climb("{ let x = 1; &x }")
+            .type_check()
+            .unwrap()
+            .to_string(),
+        "&i32"
+    );
+    let err = |s: &str| parse_climb(s).type_check().unwrap_err().to_string();
+    assert_eq!(
+        err("{ let x = 1; *x }"),
+        "type `i32` cannot be dereferenced"
+    );
+    assert_eq!(
+        err("{ let x = 1; let r = &x; *r = 2; x }"),
+        "cannot assign to `*r`, which is behind a `&` reference"
+    );
+    assert_eq!(
+        err("{ let x = 1; let r = &x; let m = &mut *r; x }"),
+        "cannot borrow `*r` as mutable, as it is behind a `&` reference"
+    );
+    assert_eq!(
+        err("{ let mut x = 1; x = true; x }"),
+        "mismatched types, expected `i32`, found `bool`"
+    );
+    assert_eq!(err("{ 1 = 2; 0 }"), "invalid left-hand side of assignment");
+    assert_eq!(err("&1"), "only variables and dereferences can be borrowed");
+    assert_eq!(
+        err("{ let x = 1; &&x }"),
+        "only variables and dereferences can be borrowed"
+    );
+    assert_eq!(
+        err("{ let x = 1; y }"),
+        "cannot find value `y` in this scope"
+    );
+    assert!(parse_climb("{ let x = 1; &x == &x }").type_check().is_err());
+}
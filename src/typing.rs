@@ -0,0 +1,237 @@
+// Typing derivation trees.
+//
+// `Expr::derive_type` records the typing derivation of the expression,
+// each node concludes `Γ ⊢ e : T` from the typing of the sub expressions,
+// e.g.,
+//
+//   Γ ⊢ 2 : i32   Γ ⊢ 3 : i32
+//   ─────────────────────────
+//       Γ ⊢ 2 - 3 : i32
+//
+// The types are given by the type checker. On a type error the derivation
+// stops at the failing premise, concluding `Γ ⊢ e : ✗` with the error.
+// Γ lists the variables in scope, e.g., `Γ, x : i32` in a block after
+// `let x = 1;`.
+use crate::{
+    ast::{Expr, Program, Stmt},
+    derive::{escape, texttt, Derivation, Judgement, Term},
+    typecheck::{Env, Type},
+};
+
+// `Γ ⊢ e : T`, or the type error
+#[derive(Debug, Clone, PartialEq)]
+pub struct Typing {
+    pub vars: Vec<(String, Type)>,
+    pub term: Term,
+    pub ty: Result<Type, String>,
+}
+
+impl Judgement for Typing {
+    fn latex(&self) -> String {
+        let mut gamma = "\\Gamma".to_string();
+        for (x, ty) in &self.vars {
+            gamma += &format!(", {} : {}", texttt(x), texttt(&ty.to_string()));
+        }
+        let ty = match &self.ty {
+            Ok(ty) => texttt(&ty.to_string()),
+            Err(msg) => format!("\\bot \\; \\text{{{}}}", escape(msg)),
+        };
+        format!(
+            "{} \\vdash {} : {}",
+            gamma,
+            texttt(&self.term.to_string()),
+            ty
+        )
+    }
+
+    fn unicode(&self) -> String {
+        let mut gamma = "Γ".to_string();
+        for (x, ty) in &self.vars {
+            gamma += &format!(", {} : {}", x, ty);
+        }
+        let ty = match &self.ty {
+            Ok(ty) => ty.to_string(),
+            Err(msg) => format!("✗ {}", msg),
+        };
+        format!("{} ⊢ {} : {}", gamma, self.term, ty)
+    }
+}
+
+impl Derivation<Typing> {
+    // The type concluded, or the type error
+    pub fn ty(&self) -> &Result<Type, String> {
+        &self.conclusion.ty
+    }
+
+    // The failing judgement, whose premises are all well typed
+    pub fn failing(&self) -> Option<&Derivation<Typing>> {
+        self.ty().as_ref().err()?;
+        match self.premises.last() {
+            Some(p) if p.ty().is_err() => p.failing(),
+            _ => Some(self),
+        }
+    }
+}
+
+// Derive the typing of the premise, None if it fails
+fn premise(premises: &mut Vec<Derivation<Typing>>, e: &Expr, env: &Env) -> Option<Type> {
+    let d = e.derive_type(env);
+    let ty = d.ty().clone().ok();
+    premises.push(d);
+    ty
+}
+
+// The premises of `e`, up to the first failing one
+fn premises(e: &Expr, env: &Env, premises: &mut Vec<Derivation<Typing>>) -> Option<()> {
+    match e {
+        Expr::Lit(_) | Expr::Var(..) => {}
+        Expr::BinOp(_, l, r, _) | Expr::Index(l, r, _) => {
+            premise(premises, l, env)?;
+            premise(premises, r, env)?;
+        }
+        Expr::Par(e)
+        | Expr::Cast(e, ..)
+        | Expr::Repeat(e, _)
+        | Expr::Field(e, ..)
+        | Expr::Ref(_, e, _)
+        | Expr::Deref(e, _) => {
+            premise(premises, e, env)?;
+        }
+        Expr::Call(_, es, _)
+        | Expr::Tuple(es)
+        | Expr::Array(es, _)
+        | Expr::Variant(_, _, es, _) => {
+            for e in es {
+                premise(premises, e, env)?;
+            }
+        }
+        Expr::Struct(_, fields, _) => {
+            for (_, e) in fields {
+                premise(premises, e, env)?;
+            }
+        }
+        // the arms are typed with the variables bound by the pattern
+        Expr::Match(e, arms, _) => {
+            let ty = premise(premises, e, env)?;
+            for arm in arms {
+                let mut env = env.clone();
+                let vars = arm.pat.type_check(&ty, &env, arm.span.0).ok()?;
+                env.vars.extend(vars);
+                premise(premises, &arm.body, &env)?;
+            }
+        }
+        // the statements are typed in order, `let` adds to Γ
+        Expr::Block(stmts, e, _) => {
+            let mut env = env.clone();
+            for stmt in stmts {
+                match stmt {
+                    Stmt::Let(x, _, e, _) => {
+                        let ty = premise(premises, e, &env)?;
+                        env.vars.push((x.clone(), ty));
+                    }
+                    Stmt::Assign(place, e, _) => {
+                        premise(premises, place, &env)?;
+                        premise(premises, e, &env)?;
+                    }
+                    Stmt::Expr(e) => {
+                        premise(premises, e, &env)?;
+                    }
+                }
+            }
+            premise(premises, e, &env)?;
+        }
+    }
+    Some(())
+}
+
+impl Expr {
+    // Type check the expression, recording the typing derivation
+    pub fn derive_type(&self, env: &Env) -> Derivation<Typing> {
+        let mut ps = vec![];
+        premises(self, env, &mut ps);
+        Derivation {
+            conclusion: Typing {
+                vars: env.vars.clone(),
+                term: Term::Expr(self.clone()),
+                ty: self.type_check_with(env).map_err(|e| e.to_string()),
+            },
+            premises: ps,
+        }
+    }
+}
+
+impl Program {
+    // The typing derivation of the expression, given the declarations
+    pub fn derive_type(&self) -> syn::Result<Derivation<Typing>> {
+        Ok(self.expr.derive_type(&self.env()?))
+    }
+}
+
+#[cfg(test)]
+fn derive_program(s: &str) -> Derivation<Typing> {
+    let ts: proc_macro2::TokenStream = s.parse().unwrap();
+    let p: Program = syn::parse2(ts).unwrap();
+    p.derive_type().unwrap()
+}
+
+#[test]
+fn typing_latex() {
+    let d = derive_program("2 - 3");
+    assert_eq!(
+        d.to_latex(),
+        "\\frac{\\Gamma \\vdash \\texttt{2} : \\texttt{i32} \\quad \
+         \\Gamma \\vdash \\texttt{3} : \\texttt{i32}}\
+         {\\Gamma \\vdash \\texttt{2 - 3} : \\texttt{i32}}"
+    );
+    let d = derive_program("{ let x = 1 < 2; x && true }");
+    assert_eq!(d.ty(), &Ok(Type::Bool));
+    assert!(d.to_latex().contains(
+        "\\Gamma, \\texttt{x} : \\texttt{bool} \\vdash \\texttt{x \\&\\& true} : \\texttt{bool}"
+    ));
+}
+
+#[test]
+fn typing_unicode() {
+    let d = derive_program("(2 - 3) * 4");
+    assert_eq!(
+        d.to_unicode(),
+        "\
+Γ ⊢ 2 : i32   Γ ⊢ 3 : i32
+─────────────────────────
+     Γ ⊢ 2 - 3 : i32
+─────────────────────────
+    Γ ⊢ (2 - 3) : i32       Γ ⊢ 4 : i32
+───────────────────────────────────────
+         Γ ⊢ (2 - 3) * 4 : i32"
+    );
+    let d = derive_program("enum E { A(i32), B } match E::B { E::A(n) => n, E::B => 0 }");
+    assert!(d
+        .to_unicode()
+        .ends_with("Γ ⊢ match E::B { E::A(n) => n, E::B => 0 } : i32"));
+    assert!(d.to_unicode().contains("Γ, n : i32 ⊢ n : i32"));
+}
+
+#[test]
+fn typing_failing() {
+    let d = derive_program("1 + (2 * (true - 1))");
+    assert!(d.ty().is_err());
+    let f = d.failing().unwrap();
+    assert_eq!(f.conclusion.term.to_string(), "true - 1");
+    assert_eq!(f.premises.len(), 2);
+    assert_eq!(
+        f.conclusion.unicode(),
+        "Γ ⊢ true - 1 : ✗ cannot apply `-` to `bool` and `i32`"
+    );
+    assert!(d.to_latex().ends_with(
+        "{\\Gamma \\vdash \\texttt{1 + (2 * (true - 1))} : \
+         \\bot \\; \\text{cannot apply `-` to `bool` and `i32`}}"
+    ));
+    // the derivation stops at the failing premise
+    let d = derive_program("{ let x = y; x + 1 }");
+    assert_eq!(d.premises.len(), 1);
+    assert_eq!(
+        d.failing().unwrap().conclusion.ty,
+        Err("cannot find value `y` in this scope".to_string())
+    );
+    assert!(derive_program("1 + 2").failing().is_none());
+}